@@ -8,6 +8,9 @@ pub fn immediate(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let vis = &input.vis;
     let ident = &input.ident;
     let internal_ident = format_ident!("__Internal_{}", ident);
+    let values_ident = format_ident!("{}Values", ident);
+    let values_doc =
+        format!("Plain copy of every field of [`{ident}`], for `set_all` and `get_all`.");
 
     let fields = if let Data::Struct(ref mut data_struct) = input.data {
         match &data_struct.fields {
@@ -39,12 +42,16 @@ pub fn immediate(_attr: TokenStream, item: TokenStream) -> TokenStream {
     });
 
     let expanded = quote! {
+        #[doc = #values_doc]
         #[repr(C)]
         #[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone, Debug, Default)]
-        struct #internal_ident {
+        #vis struct #values_ident {
             #fields
         }
 
+        #[allow(non_camel_case_types)]
+        type #internal_ident = #values_ident;
+
         #[derive(Debug, Clone)]
         #vis struct #ident {
             immediate: crate::Immediate,
@@ -52,10 +59,33 @@ pub fn immediate(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
 
         impl #ident {
+            /// Size of the data in bytes, to check against the pipeline's immediate range.
+            #vis const SIZE: usize = core::mem::size_of::<#internal_ident>();
+
+            /// Wraps the range at `offset` as is. Fresh immediates are zeroed, call
+            /// [`Self::init_default`] before the first `get_*` when sharing one or when the
+            /// field defaults aren't zero.
             #vis fn new(immediate: crate::Immediate, offset: usize) -> Self {
                 Self { immediate, offset }
             }
 
+            /// Writes the `Default` value of every field into the immediate range.
+            #vis fn init_default(&mut self) {
+                let value = #internal_ident::default();
+                self.immediate.write(self.offset, bytemuck::bytes_of(&value));
+            }
+
+            /// Writes every field in a single copy.
+            #vis fn set_all(&mut self, value: #values_ident) {
+                self.immediate.write(self.offset, bytemuck::bytes_of(&value));
+            }
+
+            /// Reads every field in a single copy.
+            #vis fn get_all(&self) -> #values_ident {
+                let bytes = self.immediate.read::<{ Self::SIZE }>(self.offset);
+                bytemuck::pod_read_unaligned(&bytes)
+            }
+
             #(#field_accessors)*
         }
     };
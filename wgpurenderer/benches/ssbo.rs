@@ -0,0 +1,100 @@
+//! Upload cost of per-object SSBO data on wgpu's noop backend, which does the same CPU-side work
+//! as a real device (validation, staging, copies) without a GPU.
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use wgpurenderer::{Context, Renderer};
+
+const OBJECTS: usize = 10_000;
+
+type Uniform = [f32; 4];
+
+fn noop_context() -> Context {
+    Context::builder()
+        .backends(wgpu::Backends::NOOP)
+        .backend_options(wgpu::BackendOptions {
+            noop: wgpu::NoopBackendOptions {
+                enable: true,
+            },
+            ..Default::default()
+        })
+        .build()
+        .expect("noop device")
+}
+
+/// 10k small uniforms updated every frame: one staging belt flush per frame against one
+/// `queue.write_buffer` per object.
+fn uploads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("10k uniforms");
+
+    let mut renderer = Renderer::with_context(noop_context());
+    let objects: Vec<_> = (0..OBJECTS).map(|_| renderer.create_object_data()).collect();
+    group.bench_function("staging belt", |b| {
+        b.iter(|| {
+            for (i, object) in objects.iter().enumerate() {
+                object.upload(black_box(&[i as f32; 4]));
+            }
+            renderer.render(&mut []);
+        })
+    });
+
+    let context = noop_context();
+    let buffer = context.device().create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (OBJECTS * size_of::<Uniform>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    group.bench_function("write_buffer per object", |b| {
+        b.iter(|| {
+            for i in 0..OBJECTS {
+                let offset = (i * size_of::<Uniform>()) as wgpu::BufferAddress;
+                let uniform: Uniform = black_box([i as f32; 4]);
+                context.queue().write_buffer(&buffer, offset, bytemuck::bytes_of(&uniform));
+            }
+            context.queue().submit([]);
+        })
+    });
+
+    group.finish();
+}
+
+/// Inserting 10k objects and uploading them in one frame, with and without reserving room for
+/// all of them up front.
+fn bulk_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("10k inserts");
+
+    for reserve in [false, true] {
+        let name = if reserve {
+            "reserved"
+        } else {
+            "unreserved"
+        };
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || Renderer::with_context(noop_context()),
+                |mut renderer| {
+                    if reserve {
+                        renderer.reserve_ssbo::<Uniform>(OBJECTS);
+                    }
+                    let objects: Vec<_> = (0..OBJECTS)
+                        .map(|i| {
+                            let object = renderer.create_object_data();
+                            object.upload(black_box(&[i as f32; 4]));
+                            object
+                        })
+                        .collect();
+                    renderer.render(&mut []);
+                    // Dropped with the renderer outside the measurement
+                    (renderer, objects)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, uploads, bulk_insert);
+criterion_main!(benches);
@@ -0,0 +1,253 @@
+use bytemuck::{Pod, Zeroable};
+use glfw::{Action, Key};
+use std::num::NonZeroU32;
+use wgpu::util::DeviceExt;
+use wgpurenderer::{Geometry, renderpass::RenderPass};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Instance {
+    offset: [f32; 3],
+    color: [f32; 3],
+}
+
+const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![2 => Float32x3, 3 => Float32x3];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Camera {
+    view: [[f32; 4]; 4],
+    projection: [[f32; 4]; 4],
+}
+
+fn create_cube_mesh() -> (Vec<Vertex>, Vec<u32>) {
+    let faces = [
+        (glam::Vec3::Z, glam::Vec3::X),
+        (glam::Vec3::NEG_Z, glam::Vec3::NEG_X),
+        (glam::Vec3::Y, glam::Vec3::X),
+        (glam::Vec3::NEG_Y, glam::Vec3::X),
+        (glam::Vec3::X, glam::Vec3::NEG_Z),
+        (glam::Vec3::NEG_X, glam::Vec3::Z),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+
+    for (normal, right) in faces {
+        let up = normal.cross(right);
+        let base = vertices.len() as u32;
+
+        for (u, v) in [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+            let position = normal * 0.5 + right * u + up * v;
+            vertices.push(Vertex {
+                position: position.to_array(),
+                normal: normal.to_array(),
+            });
+        }
+
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    (vertices, indices)
+}
+
+fn create_instances() -> Vec<Instance> {
+    let mut instances = vec![];
+
+    for x in -2..=2 {
+        for z in -2..=2 {
+            instances.push(Instance {
+                offset: [x as f32 * 1.5, 0.0, z as f32 * 1.5],
+                color: [(x + 2) as f32 / 4.0, 0.5, (z + 2) as f32 / 4.0],
+            });
+        }
+    }
+
+    instances
+}
+
+fn main() {
+    env_logger::init();
+    let mut glfw = glfw::init(glfw::fail_on_errors).unwrap();
+
+    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
+    glfw.window_hint(glfw::WindowHint::Resizable(true));
+
+    let (mut window, events) = glfw
+        .create_window(800, 600, "WGPU Instancing", glfw::WindowMode::Windowed)
+        .expect("Failed to create GLFW window.");
+
+    window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
+
+    let mut size = (800, 600);
+    let mut renderer = wgpurenderer::Renderer::new()
+        .unwrap_or_else(|e| panic!("Failed to initialize renderer: {e}"));
+    let surface = renderer
+        .context()
+        .create_surface(window.render_context())
+        .unwrap_or_else(|e| panic!("Failed to initialize renderer: {e}"));
+    let mut surface = wgpurenderer::SurfaceState::new(
+        renderer.context(),
+        surface,
+        size.0,
+        size.1,
+        1,
+        Some(wgpu::TextureFormat::Depth32Float),
+    );
+
+    let (vertices, indices) = create_cube_mesh();
+    let instances = create_instances();
+
+    let device = renderer.context().device();
+    let mut geometry =
+        Geometry::from_interleaved(device, &vertices, &VERTEX_ATTRIBUTES[..], &indices, None);
+    geometry.set_instance_buffer(device, 1, &instances, &INSTANCE_ATTRIBUTES[..]).unwrap();
+
+    let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Camera Buffer"),
+        contents: bytemuck::bytes_of(&Camera::zeroed()),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let bind_group_layout = vec![wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }];
+
+    let material = wgpurenderer::Material {
+        label: Some("Instanced cubes".to_string()),
+        bind_groups: vec![bind_group_layout.clone()],
+        vertex: wgpurenderer::Vertex {
+            buffers: vec![
+                wgpu::VertexBufferLayout {
+                    array_stride: size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &VERTEX_ATTRIBUTES,
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: size_of::<Instance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &INSTANCE_ATTRIBUTES,
+                },
+            ],
+        },
+        fragment: Some(wgpurenderer::Fragment {
+            targets: vec![Some(wgpu::ColorTargetState {
+                format: surface.format(),
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: surface.depth_format().map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: surface.sample_count(),
+            ..Default::default()
+        },
+        immediate_size: 0,
+        source: include_str!("../shaders/instanced.wgsl").into(),
+    };
+
+    let pipeline_handle = renderer.create_render_pipeline(&material);
+    let bindgroup_handle = renderer.create_bindgroup(
+        &bind_group_layout,
+        &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: camera_buffer.as_entire_binding(),
+        }],
+    );
+
+    let draw_call = wgpurenderer::DrawCall {
+        geometry,
+        shader_data: wgpurenderer::ShaderData {
+            immediates: None,
+            bind_groups: smallvec::smallvec![bindgroup_handle],
+        },
+        instance_count: NonZeroU32::MIN,
+        render_pipeline_handle: pipeline_handle,
+        scissor: None,
+        stencil_reference: None,
+    };
+
+    let start_time = std::time::Instant::now();
+
+    while !window.should_close() {
+        glfw.poll_events();
+        for (_, event) in glfw::flush_messages(&events) {
+            match event {
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+                    window.set_should_close(true)
+                }
+                glfw::WindowEvent::FramebufferSize(width, height) if width > 0 && height > 0 => {
+                    size = (width as u32, height as u32);
+                    surface.resize(size.0, size.1);
+                }
+                _ => {}
+            }
+        }
+
+        let angle = start_time.elapsed().as_secs_f32() * 0.5;
+        let eye = glam::Vec3::new(angle.sin() * 8.0, 5.0, angle.cos() * 8.0);
+        let aspect = size.0 as f32 / size.1 as f32;
+        let camera = Camera {
+            view: glam::Mat4::look_at_rh(eye, glam::Vec3::ZERO, glam::Vec3::Y).to_cols_array_2d(),
+            projection: glam::Mat4::perspective_rh(45.0_f32.to_radians(), aspect, 0.1, 100.0)
+                .to_cols_array_2d(),
+        };
+        renderer.context().queue().write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&camera));
+
+        let frame = match surface.acquire() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                eprintln!("Out of memory!");
+                break;
+            }
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+
+        let mut render_pass = RenderPass {
+            name: Some("Main".to_string()),
+            render_target: frame.render_target.clone(),
+            multiview_mask: None,
+            viewport: None,
+            draw_calls: vec![draw_call.clone()],
+            bundle: None,
+            executor: None,
+        };
+
+        renderer.render(&mut [&mut render_pass]);
+        // The surface views have to be gone before presenting
+        drop(render_pass);
+        frame.present();
+    }
+}
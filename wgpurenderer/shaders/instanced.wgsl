@@ -0,0 +1,43 @@
+struct Camera {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(2) offset: vec3<f32>,
+    @location(3) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_position = vec4<f32>(vertex.position + instance.offset, 1.0);
+    out.clip_position = camera.projection * camera.view * world_position;
+    out.normal = vertex.normal;
+    out.color = instance.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light_dir = normalize(vec3<f32>(1.0, 1.0, 1.0));
+    let diffuse = max(dot(normalize(in.normal), light_dir), 0.0);
+
+    return vec4<f32>((0.3 + diffuse) * in.color, 1.0);
+}
@@ -0,0 +1,198 @@
+use crate::{Handle, ResourcePool, utils::InstanceCounter};
+use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+
+/// A resource that can be placed into a bind group. The layout entry is derived from the
+/// resource itself so it can't drift from what is actually bound.
+pub trait Binding {
+    fn binding_type(&self) -> wgpu::BindingType;
+
+    fn binding_resource(&self) -> wgpu::BindingResource<'_>;
+
+    /// Tracks the lifetime of the resource so cached bind groups using it can be evicted once
+    /// it's dropped. Untracked resources are never cached.
+    fn counter(&self) -> Option<&InstanceCounter> {
+        None
+    }
+
+    fn layout_entry(
+        &self,
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: self.binding_type(),
+            count: None,
+        }
+    }
+
+    fn entry(&self, binding: u32) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: self.binding_resource(),
+        }
+    }
+}
+
+/// A whole buffer bound as a uniform or storage buffer.
+#[derive(Debug)]
+pub struct BufferBinding {
+    buffer: wgpu::Buffer,
+    ty: wgpu::BufferBindingType,
+    counter: InstanceCounter,
+}
+
+impl BufferBinding {
+    pub fn uniform(buffer: wgpu::Buffer) -> Self {
+        Self::new(buffer, wgpu::BufferBindingType::Uniform)
+    }
+
+    pub fn storage(buffer: wgpu::Buffer, read_only: bool) -> Self {
+        Self::new(
+            buffer,
+            wgpu::BufferBindingType::Storage {
+                read_only,
+            },
+        )
+    }
+
+    fn new(buffer: wgpu::Buffer, ty: wgpu::BufferBindingType) -> Self {
+        Self {
+            buffer,
+            ty,
+            counter: InstanceCounter::new(),
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+impl Binding for BufferBinding {
+    fn binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: self.ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    fn counter(&self) -> Option<&InstanceCounter> {
+        Some(&self.counter)
+    }
+}
+
+impl Clone for BufferBinding {
+    fn clone(&self) -> Self {
+        self.counter.increment();
+
+        Self {
+            buffer: self.buffer.clone(),
+            ty: self.ty,
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+impl Drop for BufferBinding {
+    fn drop(&mut self) {
+        self.counter.decrement();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BindGroupKey {
+    layout: usize,
+    resources: SmallVec<[usize; 4]>,
+}
+
+#[derive(Debug)]
+struct CachedBindGroup {
+    handle: Handle<wgpu::BindGroup>,
+    // Keeping the counters alive also keeps their ids in the key unique
+    counters: SmallVec<[InstanceCounter; 4]>,
+}
+
+/// Reuses bind groups created from the same layout and the same tracked resources.
+#[derive(Debug, Default)]
+pub struct BindGroupCache {
+    entries: FxHashMap<BindGroupKey, CachedBindGroup>,
+}
+
+impl BindGroupCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `layout` identifies the bind group layout, `bindings` are ordered by binding index.
+    pub fn get(
+        &self,
+        layout: usize,
+        bindings: &[&dyn Binding],
+        pool: &ResourcePool<wgpu::BindGroup>,
+    ) -> Option<Handle<wgpu::BindGroup>> {
+        let key = cache_key(layout, bindings)?;
+        self.entries.get(&key).map(|entry| entry.handle).filter(|&handle| pool.is_alive(handle))
+    }
+
+    /// Does nothing when one of the bindings is untracked.
+    pub fn insert(
+        &mut self,
+        layout: usize,
+        bindings: &[&dyn Binding],
+        handle: Handle<wgpu::BindGroup>,
+    ) {
+        let Some(key) = cache_key(layout, bindings) else {
+            return;
+        };
+        let counters = bindings.iter().filter_map(|binding| binding.counter().cloned()).collect();
+        self.entries.insert(
+            key,
+            CachedBindGroup {
+                handle,
+                counters,
+            },
+        );
+    }
+
+    /// Removes bind groups that use a dropped resource from the cache and from `pool`.
+    /// Returns how many were evicted.
+    pub fn evict_dropped(&mut self, pool: &mut ResourcePool<wgpu::BindGroup>) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| {
+            let alive = entry.counters.iter().all(|counter| counter.value() > 0);
+            if !alive {
+                pool.remove(entry.handle);
+            }
+            alive
+        });
+        before - self.entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn cache_key(layout: usize, bindings: &[&dyn Binding]) -> Option<BindGroupKey> {
+    let resources = bindings
+        .iter()
+        .map(|binding| binding.counter().map(InstanceCounter::id))
+        .collect::<Option<_>>()?;
+
+    Some(BindGroupKey {
+        layout,
+        resources,
+    })
+}
@@ -1,10 +1,22 @@
 use crate::transform::Transform;
-use glam::{Mat4, Quat, Vec3};
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3, Vec4};
+
+/// Parameters of the last [`Camera::set_perspective`] call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perspective {
+    pub fov: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
     projection: Mat4,
+    perspective: Option<Perspective>,
+    jitter: Vec2,
     view_projection: Mat4,
+    inverse_view_projection: Mat4,
     cached_position: Vec3,
     cached_orientation: Quat,
 }
@@ -12,15 +24,128 @@ pub struct Camera {
 impl Camera {
     pub fn set_projection(&mut self, projection: Mat4) {
         self.projection = projection;
+        self.perspective = None;
         self.invalidate_view_projection();
     }
 
     pub fn set_perspective(&mut self, fov: f32, aspect: f32, near: f32, far: f32) {
         self.projection = Mat4::perspective_rh(fov, aspect, near, far);
+        self.perspective = Some(Perspective {
+            fov,
+            aspect,
+            near,
+            far,
+        });
+        self.invalidate_view_projection();
+    }
+
+    /// Rebuilds the perspective projection with a new aspect ratio, keeping fov and clip
+    /// planes. Does nothing if the projection wasn't set through [`Camera::set_perspective`].
+    pub fn set_aspect(&mut self, aspect: f32) {
+        let Some(perspective) = self.perspective else {
+            log::warn!("Camera::set_aspect called without a perspective projection");
+            return;
+        };
+        self.set_perspective(perspective.fov, aspect, perspective.near, perspective.far);
+    }
+
+    pub fn perspective(&self) -> Option<Perspective> {
+        self.perspective
+    }
+
+    /// Off-center perspective projection, with the bounds given on the near plane. Used for
+    /// stereo eyes and tiled rendering. With `right = -left = near * tan(fov / 2) * aspect` and
+    /// `top = -bottom = near * tan(fov / 2)` this matches [`Camera::set_perspective`].
+    pub fn set_frustum(
+        &mut self,
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) {
+        let width = right - left;
+        let height = top - bottom;
+        let depth = far / (near - far);
+
+        self.projection = Mat4::from_cols(
+            Vec4::new(2.0 * near / width, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 2.0 * near / height, 0.0, 0.0),
+            Vec4::new((right + left) / width, (top + bottom) / height, depth, -1.0),
+            Vec4::new(0.0, 0.0, depth * near, 0.0),
+        );
+        self.perspective = None;
+        self.invalidate_view_projection();
+    }
+
+    /// Orthographic projection with depth mapped to `0..1`, e.g. for directional light shadows.
+    pub fn set_orthographic(
+        &mut self,
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) {
+        self.projection = Mat4::orthographic_rh(left, right, bottom, top, near, far);
+        self.perspective = None;
+        self.invalidate_view_projection();
+    }
+
+    /// Camera and transform for rendering the shadow map of a directional light shining along
+    /// `direction`. The orthographic volume is a cube of half-size `radius` around `center`, so
+    /// everything within `radius` of `center` casts and receives shadows.
+    pub fn directional_light(direction: Vec3, center: Vec3, radius: f32) -> (Camera, Transform) {
+        let direction = direction.normalize();
+        let mut camera = Camera::default();
+        camera.set_orthographic(-radius, radius, -radius, radius, 0.0, 2.0 * radius);
+
+        let mut transform = Transform::default();
+        transform.set_position(center - direction * radius);
+        transform.set_orientation(Quat::from_rotation_arc(Vec3::NEG_Z, direction));
+        (camera, transform)
+    }
+
+    /// Sub-pixel jitter for temporal anti-aliasing, applied as a clip-space translation of
+    /// `2 * offset / viewport_size` on top of whichever projection is set.
+    pub fn set_jitter(&mut self, offset: Vec2, viewport_size: Vec2) {
+        self.jitter = 2.0 * offset / viewport_size;
+        self.invalidate_view_projection();
+    }
+
+    pub fn clear_jitter(&mut self) {
+        self.jitter = Vec2::ZERO;
         self.invalidate_view_projection();
     }
 
+    /// Projection including jitter, as used by [`Camera::get_view_projection`].
+    pub fn projection(&self) -> Mat4 {
+        if self.jitter == Vec2::ZERO {
+            return self.projection;
+        }
+        Mat4::from_translation(self.jitter.extend(0.0)) * self.projection
+    }
+
+    /// Projection without jitter, e.g. for reprojecting the previous frame.
+    pub fn unjittered_projection(&self) -> Mat4 {
+        self.projection
+    }
+
     pub fn get_view_projection(&mut self, transform: &Transform) -> Mat4 {
+        self.update_view_projection(transform);
+        self.view_projection
+    }
+
+    /// Inverse of [`Camera::get_view_projection`], cached and recomputed alongside it. Used to
+    /// reconstruct world positions from depth.
+    pub fn get_inverse_view_projection(&mut self, transform: &Transform) -> Mat4 {
+        self.update_view_projection(transform);
+        self.inverse_view_projection
+    }
+
+    fn update_view_projection(&mut self, transform: &Transform) {
         if self.is_invalid_view_projection() || self.is_transform_changed(transform) {
             let position = transform.position();
             let orientation = transform.orientation();
@@ -29,13 +154,18 @@ impl Camera {
             let rotation = Mat4::from_quat(orientation.inverse());
             let translation = Mat4::from_translation(-position);
             let view = rotation * translation;
-            self.view_projection = self.projection * view;
+            self.view_projection = self.projection() * view;
+            self.inverse_view_projection = self.view_projection.inverse();
 
             // Update cached values
             self.cached_position = position;
             self.cached_orientation = orientation;
         }
-        self.view_projection
+    }
+
+    /// Compares the projections elementwise, ignoring the cached view-projection.
+    pub fn approx_eq(&self, other: &Camera, epsilon: f32) -> bool {
+        self.projection().abs_diff_eq(other.projection(), epsilon)
     }
 
     fn is_invalid_view_projection(&self) -> bool {
@@ -60,9 +190,172 @@ impl Default for Camera {
     fn default() -> Self {
         Self {
             projection: Mat4::IDENTITY,
+            perspective: None,
+            jitter: Vec2::ZERO,
             view_projection: Self::invalid_view_projection(),
+            inverse_view_projection: Mat4::IDENTITY,
             cached_position: Vec3::ZERO,
             cached_orientation: Quat::IDENTITY,
         }
     }
 }
+
+/// Element `index` of the Halton low-discrepancy sequence in `base`, in `0..1`.
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Pixel offset in `-0.5..0.5` for TAA frame `frame`, cycling through the first `sample_count`
+/// points of the Halton(2, 3) sequence. Meant for [`Camera::set_jitter`].
+pub fn halton_jitter(frame: u32, sample_count: u32) -> Vec2 {
+    let index = frame % sample_count.max(1) + 1;
+    Vec2::new(halton(index, 2), halton(index, 3)) - 0.5
+}
+
+/// Trauma-based camera shake. Trauma decays over time and the shake strength is its square.
+/// Offsets come from seeded gradient noise, so the same seed and updates give the same shake.
+///
+/// The logical transform is never modified, the shaken copy from [`CameraShake::apply`] is
+/// what gets passed to [`Camera::get_view_projection`].
+#[derive(Debug, Clone)]
+pub struct CameraShake {
+    seed: u32,
+    trauma: f32,
+    time: f32,
+    /// Largest positional offset along each local axis.
+    pub max_offset: Vec3,
+    /// Largest yaw, pitch and roll in radians.
+    pub max_angles: Vec3,
+    /// Noise samples per second.
+    pub frequency: f32,
+    /// Trauma lost per second.
+    pub decay: f32,
+}
+
+impl CameraShake {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            trauma: 0.0,
+            time: 0.0,
+            max_offset: Vec3::splat(0.3),
+            max_angles: Vec3::splat(0.1),
+            frequency: 15.0,
+            decay: 1.0,
+        }
+    }
+
+    /// Adds trauma, clamped to `0..=1`.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+    }
+
+    /// `transform` with the current shake applied in its local space.
+    pub fn apply(&self, transform: &Transform) -> Transform {
+        let mut shaken = *transform;
+        if self.trauma <= 0.0 {
+            return shaken;
+        }
+
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+        let sample = |channel| gradient_noise(self.seed, channel, t) * shake;
+
+        let offset = self.max_offset * Vec3::new(sample(0), sample(1), sample(2));
+        let angles = self.max_angles * Vec3::new(sample(3), sample(4), sample(5));
+        let rotation = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+
+        shaken.set_position(transform.position() + transform.orientation() * offset);
+        shaken.set_orientation(transform.orientation() * rotation);
+        shaken
+    }
+}
+
+/// 1D Perlin-style noise in roughly `-1..1`, one independent curve per `channel`.
+fn gradient_noise(seed: u32, channel: u32, t: f32) -> f32 {
+    let cell = t.floor();
+    let f = t - cell;
+    let cell = cell as i32;
+
+    let g0 = lattice_gradient(seed, channel, cell);
+    let g1 = lattice_gradient(seed, channel, cell.wrapping_add(1));
+    let u = f * f * (3.0 - 2.0 * f);
+
+    (g0 * f + (g1 * (f - 1.0) - g0 * f) * u) * 2.0
+}
+
+fn lattice_gradient(seed: u32, channel: u32, cell: i32) -> f32 {
+    let mut h = seed ^ channel.wrapping_mul(0x9E37_79B9) ^ (cell as u32).wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centered_frustum_matches_perspective() {
+        let (fov, aspect, near, far) = (1.2f32, 16.0 / 9.0, 0.1, 100.0);
+        let top = near * (fov / 2.0).tan();
+        let right = top * aspect;
+
+        let mut symmetric = Camera::default();
+        symmetric.set_perspective(fov, aspect, near, far);
+        let mut frustum = Camera::default();
+        frustum.set_frustum(-right, right, -top, top, near, far);
+
+        assert!(frustum.approx_eq(&symmetric, 1e-5));
+    }
+
+    #[test]
+    fn inverse_view_projection_undoes_view_projection() {
+        let mut camera = Camera::default();
+        camera.set_perspective(1.0, 1.5, 0.1, 50.0);
+        let mut transform = Transform::default();
+        transform.set_position(Vec3::new(1.0, 2.0, 5.0));
+        transform.set_orientation(Quat::from_rotation_y(0.7));
+
+        let view_projection = camera.get_view_projection(&transform);
+        let inverse = camera.get_inverse_view_projection(&transform);
+
+        assert!((inverse * view_projection).abs_diff_eq(Mat4::IDENTITY, 1e-4));
+    }
+
+    #[test]
+    fn set_aspect_keeps_fov_and_clip_planes() {
+        let mut camera = Camera::default();
+        camera.set_perspective(1.0, 4.0 / 3.0, 0.1, 50.0);
+        camera.set_aspect(2.0);
+
+        let perspective = Perspective {
+            fov: 1.0,
+            aspect: 2.0,
+            near: 0.1,
+            far: 50.0,
+        };
+        assert_eq!(camera.perspective(), Some(perspective));
+        assert_eq!(camera.unjittered_projection(), Mat4::perspective_rh(1.0, 2.0, 0.1, 50.0));
+    }
+}
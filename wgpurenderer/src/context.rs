@@ -0,0 +1,414 @@
+#[cfg(feature = "image")]
+use crate::readback::{self, ReadbackError};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Context {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+impl Context {
+    pub fn new() -> Result<Self, ContextError> {
+        ContextBuilder::new().build()
+    }
+
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
+    fn with_adapter(
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+    ) -> Result<Self, ContextError> {
+        let device_queue = adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            // The driver pipeline cache is optional, only request it where it exists
+            required_features: wgpu::Features::IMMEDIATES
+                | (adapter.features() & wgpu::Features::PIPELINE_CACHE),
+            required_limits: wgpu::Limits {
+                max_immediate_size: 128,
+                ..Default::default()
+            },
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            memory_hints: wgpu::MemoryHints::Performance,
+            trace: wgpu::Trace::Off,
+        });
+        let (device, queue) =
+            pollster::block_on(device_queue).map_err(ContextError::DeviceRequest)?;
+
+        Ok(Context {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+
+    /// Context on wgpu's noop backend, which validates like a real device and executes buffer
+    /// copies but never draws. Lets tests run without a GPU.
+    #[cfg(test)]
+    pub(crate) fn noop() -> Self {
+        ContextBuilder::new()
+            .backends(wgpu::Backends::NOOP)
+            .backend_options(wgpu::BackendOptions {
+                noop: wgpu::NoopBackendOptions {
+                    enable: true,
+                },
+                ..Default::default()
+            })
+            .build()
+            .expect("noop device")
+    }
+
+    /// Lists the adapters available for `backends` without creating a device, e.g. for a GPU
+    /// picker. Pass the chosen one to [`ContextBuilder::adapter`].
+    pub fn enumerate_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        pollster::block_on(instance.enumerate_adapters(backends))
+            .iter()
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    pub fn instance(&self) -> &wgpu::Instance {
+        &self.instance
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Limits and features of the device, as requested at creation rather than the adapter's
+    /// maximums.
+    pub fn capabilities(&self) -> Capabilities {
+        let limits = self.device.limits();
+        Capabilities {
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment: limits.min_storage_buffer_offset_alignment,
+            max_uniform_buffer_binding_size: limits.max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+            max_buffer_size: limits.max_buffer_size,
+            max_bind_groups: limits.max_bind_groups,
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            features: self.device.features(),
+        }
+    }
+
+    /// Highest MSAA sample count up to `requested` that every one of `formats` supports on this
+    /// adapter, falling back to 1. Logs a warning when it differs from `requested`. The color
+    /// and depth attachments and the pipeline's `MultisampleState` all have to use the result.
+    pub fn supported_sample_count(&self, formats: &[wgpu::TextureFormat], requested: u32) -> u32 {
+        let supported = [16, 8, 4, 2]
+            .into_iter()
+            .filter(|&count| count <= requested)
+            .find(|&count| {
+                formats.iter().all(|&format| {
+                    self.adapter
+                        .get_texture_format_features(format)
+                        .flags
+                        .sample_count_supported(count)
+                })
+            })
+            .unwrap_or(1);
+
+        if supported != requested {
+            log::warn!(
+                "Sample count {requested} is not supported for {formats:?}, using {supported}"
+            );
+        }
+        supported
+    }
+
+    /// `requested` if the device can use it as a depth attachment, otherwise `Depth24PlusStencil8`
+    /// for formats with a stencil aspect and `Depth32Float` for the rest, which are always
+    /// supported. `Depth32FloatStencil8` needs the device feature of the same name. Logs a
+    /// warning when it differs from `requested`. The depth attachment and the pipeline's
+    /// `DepthStencilState` both have to use the result.
+    pub fn supported_depth_format(&self, requested: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        let supported = requested.is_depth_stencil_format()
+            && self.supports(requested.required_features())
+            && self
+                .adapter
+                .get_texture_format_features(requested)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+        if supported {
+            return requested;
+        }
+
+        let fallback = if requested.has_stencil_aspect() {
+            wgpu::TextureFormat::Depth24PlusStencil8
+        } else {
+            wgpu::TextureFormat::Depth32Float
+        };
+        log::warn!("Depth format {requested:?} is not supported, using {fallback:?}");
+        fallback
+    }
+
+    /// Whether the device was created with all of `features`.
+    pub fn supports(&self, features: wgpu::Features) -> bool {
+        self.device.features().contains(features)
+    }
+
+    /// Reads mip 0 of an 8-bit RGBA/BGRA texture with `COPY_SRC` usage, e.g. a render target
+    /// attachment, blocking until the copy completes.
+    #[cfg(feature = "image")]
+    pub fn capture_texture(
+        &self,
+        texture: &wgpu::Texture,
+    ) -> Result<image::RgbaImage, ReadbackError> {
+        readback::read_rgba_image(&self.device, &self.queue, texture)
+    }
+
+    /// [`Context::capture_texture`] written to `path` as a PNG.
+    #[cfg(feature = "image")]
+    pub fn save_png(
+        &self,
+        texture: &wgpu::Texture,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), ReadbackError> {
+        self.capture_texture(texture)?
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(ReadbackError::Image)
+    }
+
+    pub fn create_surface<'window>(
+        &self,
+        target: impl Into<wgpu::SurfaceTarget<'window>>,
+    ) -> Result<wgpu::Surface<'window>, ContextError> {
+        self.instance.create_surface(target).map_err(ContextError::SurfaceCreation)
+    }
+}
+
+/// Device limits relevant for sizing buffers, bind groups and textures, see
+/// [`Context::capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub min_uniform_buffer_offset_alignment: u32,
+    pub min_storage_buffer_offset_alignment: u32,
+    pub max_uniform_buffer_binding_size: u32,
+    pub max_storage_buffer_binding_size: u32,
+    pub max_buffer_size: u64,
+    pub max_bind_groups: u32,
+    pub max_texture_dimension_2d: u32,
+    pub features: wgpu::Features,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    backends: wgpu::Backends,
+    backend_options: wgpu::BackendOptions,
+    power_preference: wgpu::PowerPreference,
+    adapter_name: Option<String>,
+    adapter: Option<wgpu::AdapterInfo>,
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self {
+            backends: wgpu::Backends::PRIMARY,
+            backend_options: wgpu::BackendOptions::default(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            adapter_name: None,
+            adapter: None,
+        }
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Per-backend instance options, e.g. enabling wgpu's noop backend (with its `noop`
+    /// feature) for headless tests and benchmarks.
+    pub fn backend_options(mut self, backend_options: wgpu::BackendOptions) -> Self {
+        self.backend_options = backend_options;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Only consider adapters whose name contains `name` (case-insensitive).
+    pub fn adapter_name(mut self, name: impl Into<String>) -> Self {
+        self.adapter_name = Some(name.into());
+        self
+    }
+
+    /// Use exactly the adapter described by `info`, as returned by
+    /// [`Context::enumerate_adapters`]. Its backend is added to the enabled ones. Takes
+    /// precedence over the power preference.
+    pub fn adapter(mut self, info: wgpu::AdapterInfo) -> Self {
+        self.backends |= info.backend.into();
+        self.adapter = Some(info);
+        self
+    }
+
+    pub fn build(self) -> Result<Context, ContextError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: self.backends,
+            backend_options: self.backend_options.clone(),
+            ..Default::default()
+        });
+
+        let adapters = pollster::block_on(instance.enumerate_adapters(self.backends));
+        if adapters.is_empty() {
+            return Err(ContextError::NoAdapter {
+                backends: self.backends,
+                power_preference: self.power_preference,
+            });
+        }
+        let filter = self.adapter_name.as_ref().map(|name| name.to_lowercase());
+
+        let adapter = adapters
+            .iter()
+            .filter(|adapter| {
+                let info = adapter.get_info();
+                filter.as_ref().is_none_or(|f| info.name.to_lowercase().contains(f))
+                    && self.adapter.as_ref().is_none_or(|wanted| is_same_adapter(&info, wanted))
+            })
+            .min_by_key(|adapter| {
+                device_type_rank(adapter.get_info().device_type, self.power_preference)
+            })
+            .cloned();
+
+        let Some(adapter) = adapter else {
+            return Err(ContextError::NoMatchingAdapter {
+                filter: self.adapter_name.or(self.adapter.map(|info| info.name)),
+                available: adapters.iter().map(|a| a.get_info().name).collect(),
+            });
+        };
+
+        Context::with_adapter(instance, adapter)
+    }
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_same_adapter(a: &wgpu::AdapterInfo, b: &wgpu::AdapterInfo) -> bool {
+    a.backend == b.backend && a.vendor == b.vendor && a.device == b.device && a.name == b.name
+}
+
+/// Lower is better.
+fn device_type_rank(device_type: wgpu::DeviceType, power_preference: wgpu::PowerPreference) -> u32 {
+    match (power_preference, device_type) {
+        (wgpu::PowerPreference::HighPerformance, wgpu::DeviceType::DiscreteGpu) => 0,
+        (wgpu::PowerPreference::HighPerformance, wgpu::DeviceType::IntegratedGpu) => 1,
+        (wgpu::PowerPreference::LowPower, wgpu::DeviceType::IntegratedGpu) => 0,
+        (wgpu::PowerPreference::LowPower, wgpu::DeviceType::DiscreteGpu) => 1,
+        (wgpu::PowerPreference::None, wgpu::DeviceType::DiscreteGpu)
+        | (wgpu::PowerPreference::None, wgpu::DeviceType::IntegratedGpu) => 0,
+        (_, wgpu::DeviceType::VirtualGpu) => 2,
+        (_, wgpu::DeviceType::Other) => 3,
+        (_, wgpu::DeviceType::Cpu) => 4,
+    }
+}
+
+#[derive(Debug)]
+pub enum ContextError {
+    /// No adapter at all for the requested backends.
+    NoAdapter {
+        backends: wgpu::Backends,
+        power_preference: wgpu::PowerPreference,
+    },
+    /// Adapters exist, but none matches the name filter.
+    NoMatchingAdapter {
+        filter: Option<String>,
+        available: Vec<String>,
+    },
+    DeviceRequest(wgpu::RequestDeviceError),
+    SurfaceCreation(wgpu::CreateSurfaceError),
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter {
+                backends,
+                power_preference,
+            } => write!(
+                f,
+                "no adapter found for backends {backends:?} with power preference \
+                 {power_preference:?}"
+            ),
+            Self::NoMatchingAdapter {
+                filter,
+                available,
+            } => {
+                write!(f, "no adapter matches")?;
+                if let Some(filter) = filter {
+                    write!(f, " {filter:?}")?;
+                }
+                write!(f, ", available adapters: [{}]", available.join(", "))
+            }
+            Self::DeviceRequest(e) => write!(f, "failed to request device: {e}"),
+            Self::SurfaceCreation(e) => write!(f, "failed to create surface: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_report_device_limits() {
+        let context = Context::noop();
+        let capabilities = context.capabilities();
+
+        assert!(capabilities.min_uniform_buffer_offset_alignment > 0);
+        assert!(capabilities.min_storage_buffer_offset_alignment > 0);
+        assert!(capabilities.max_uniform_buffer_binding_size > 0);
+        assert!(capabilities.max_storage_buffer_binding_size > 0);
+        assert!(capabilities.max_buffer_size > 0);
+        assert!(capabilities.max_bind_groups > 0);
+        assert!(capabilities.max_texture_dimension_2d > 0);
+        assert!(capabilities.features.contains(wgpu::Features::IMMEDIATES));
+        assert_eq!(capabilities.max_bind_groups, context.device().limits().max_bind_groups);
+    }
+
+    #[test]
+    fn unsupported_sample_counts_degrade() {
+        let context = Context::noop();
+        let formats = [wgpu::TextureFormat::Rgba8UnormSrgb, wgpu::TextureFormat::Depth32Float];
+        let supported = |count| {
+            formats.iter().all(|&format| {
+                context
+                    .adapter()
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(count)
+            })
+        };
+
+        for requested in [1, 3, 4, 64] {
+            let count = context.supported_sample_count(&formats, requested);
+            assert!(count <= requested);
+            assert!(count == 1 || supported(count), "{count} for {requested}");
+        }
+        // The noop backend reports no multisampling for any format
+        assert_eq!(context.supported_sample_count(&formats, 4), 1);
+    }
+}
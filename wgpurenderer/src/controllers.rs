@@ -0,0 +1,136 @@
+use crate::transform::Transform;
+use glam::{EulerRot, Quat, Vec2, Vec3};
+use std::f32::consts::FRAC_PI_2;
+
+/// Pitch stays this far away from straight up or down, where yaw becomes ambiguous.
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+
+/// Input of one [`FirstPersonController::update`], collected from any windowing backend.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FirstPersonInput {
+    /// Movement direction in camera space: `x` right, `y` up, `z` forward, each in `-1..=1`,
+    /// e.g. `z = 1` while W is held and `z = -1` while S is held.
+    pub movement: Vec3,
+    /// Mouse movement in pixels since the previous update, `y` pointing down.
+    pub look: Vec2,
+}
+
+/// Free-fly camera: mouse-look turns by yaw and pitch without roll and movement follows the
+/// view direction, including its vertical part.
+#[derive(Debug, Clone)]
+pub struct FirstPersonController {
+    yaw: f32,
+    pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+}
+
+impl FirstPersonController {
+    /// Controller continuing from the current orientation of `transform`. Roll is dropped on
+    /// the first update.
+    pub fn new(transform: &Transform) -> Self {
+        let (yaw, pitch, _) = transform.orientation().to_euler(EulerRot::YXZ);
+        Self {
+            yaw,
+            pitch: pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT),
+            speed: 5.0,
+            sensitivity: 0.002,
+        }
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Applies one frame of `input` to `transform`, `dt` being the frame time in seconds.
+    /// Diagonal movement is normalized so it isn't faster than along a single axis.
+    pub fn update(&mut self, transform: &mut Transform, input: FirstPersonInput, dt: f32) {
+        self.yaw -= input.look.x * self.sensitivity;
+        self.pitch =
+            (self.pitch - input.look.y * self.sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        transform.set_orientation(Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0));
+
+        let movement = if input.movement.length_squared() > 1.0 {
+            input.movement.normalize()
+        } else {
+            input.movement
+        };
+        transform.translate_local(movement * self.speed * dt);
+    }
+}
+
+/// Input of one [`OrbitController::update`], collected from any windowing backend.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OrbitInput {
+    /// Mouse movement in pixels while the orbit button is held, `y` pointing down.
+    pub drag: Vec2,
+    /// Scroll steps since the previous update, positive zooming in.
+    pub scroll: f32,
+}
+
+/// Camera circling `target` at `distance`, facing it. Dragging orbits in the same directions
+/// as [`FirstPersonController`] looks around and scrolling zooms exponentially, so each step
+/// covers the same fraction of the distance.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub target: Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+    /// Radians per pixel of dragging.
+    pub sensitivity: f32,
+    /// Fraction of the distance, roughly, covered by one scroll step.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Yaw in radians per second applied on every update, for turntables. 0 by default.
+    pub auto_rotate: f32,
+}
+
+impl OrbitController {
+    /// Controller orbiting `target` from the current position of `transform`. The orientation
+    /// is replaced on the first update to face `target`.
+    pub fn new(transform: &Transform, target: Vec3) -> Self {
+        let offset = target - transform.position();
+        let distance = offset.length();
+        let direction = offset.try_normalize().unwrap_or(Vec3::NEG_Z);
+
+        Self {
+            target,
+            distance,
+            yaw: f32::atan2(-direction.x, -direction.z),
+            pitch: direction.y.asin().clamp(-PITCH_LIMIT, PITCH_LIMIT),
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            max_distance: f32::INFINITY,
+            auto_rotate: 0.0,
+        }
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    /// Applies one frame of `input` to `transform`, `dt` being the frame time in seconds.
+    pub fn update(&mut self, transform: &mut Transform, input: OrbitInput, dt: f32) {
+        self.yaw += self.auto_rotate * dt - input.drag.x * self.sensitivity;
+        self.pitch =
+            (self.pitch - input.drag.y * self.sensitivity).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.set_distance(self.distance * (-input.scroll * self.zoom_speed).exp());
+
+        let orientation = Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0);
+        transform.set_orientation(orientation);
+        transform.set_position(self.target + orientation * Vec3::Z * self.distance);
+    }
+}
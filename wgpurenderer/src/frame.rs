@@ -0,0 +1,142 @@
+use bytemuck::{Pod, Zeroable};
+use std::time::{Duration, Instant};
+
+/// Per-frame shader globals laid out like `FrameGlobals` in
+/// `modules::uniforms::FRAME_GLOBALS`, written by [`crate::Renderer`] once per rendered frame.
+/// Conventionally bound next to the camera at `@group(0) @binding(1)`, see
+/// [`crate::Renderer::frame_globals_buffer`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Pod, Zeroable)]
+pub struct FrameGlobals {
+    /// Seconds since the renderer was created.
+    pub time: f32,
+    /// Seconds since the previous frame, 0 for the first one.
+    pub delta_time: f32,
+    /// Index of the frame, starting at 0.
+    pub frame: u32,
+    _padding: u32,
+    /// Size in pixels of the last render pass target of the frame.
+    pub resolution: [f32; 2],
+    _padding_end: [f32; 2],
+}
+
+const _: () = assert!(size_of::<FrameGlobals>() == 32);
+
+/// Owns the uniform buffer behind [`FrameGlobals`] and advances it every frame.
+#[derive(Debug)]
+pub(crate) struct FrameGlobalsState {
+    globals: FrameGlobals,
+    buffer: wgpu::Buffer,
+    start: Instant,
+    last_frame: Option<Instant>,
+}
+
+impl FrameGlobalsState {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            globals: FrameGlobals::default(),
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Frame Globals"),
+                size: size_of::<FrameGlobals>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            start: Instant::now(),
+            last_frame: None,
+        }
+    }
+
+    /// Advances time and the frame index and uploads the result. `resolution` keeps the
+    /// previous value when `None`.
+    pub fn advance(&mut self, queue: &wgpu::Queue, resolution: Option<(u32, u32)>) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.globals.frame = self.globals.frame.wrapping_add(1);
+            self.globals.delta_time = (now - last_frame).as_secs_f32();
+        }
+        self.last_frame = Some(now);
+        self.globals.time = (now - self.start).as_secs_f32();
+        if let Some((width, height)) = resolution {
+            self.globals.resolution = [width as f32, height as f32];
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.globals));
+    }
+
+    pub fn globals(&self) -> FrameGlobals {
+        self.globals
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// Weight of the newest frame in the smoothed frame time.
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// Measures frame times and optionally caps the frame rate by sleeping. Call
+/// [`FrameClock::tick`] once per frame, e.g. right before acquiring the surface texture.
+#[derive(Debug, Clone)]
+pub struct FrameClock {
+    last_tick: Instant,
+    target_frame_time: Option<Duration>,
+    smoothed_frame_time: f32,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            last_tick: Instant::now(),
+            target_frame_time: None,
+            smoothed_frame_time: 0.0,
+        }
+    }
+
+    /// Caps the frame rate at `fps` frames per second. `thread::sleep` may overshoot by around
+    /// a millisecond, so the achieved rate can be slightly lower. Non-positive values disable
+    /// the cap.
+    pub fn with_target_fps(mut self, fps: f32) -> Self {
+        self.target_frame_time = (fps > 0.0).then(|| Duration::from_secs_f32(1.0 / fps));
+        self
+    }
+
+    /// Sleeps out the rest of the target frame time if one is set and returns the time since
+    /// the previous tick, or since creation for the first one.
+    pub fn tick(&mut self) -> Duration {
+        if let Some(target_frame_time) = self.target_frame_time {
+            let elapsed = self.last_tick.elapsed();
+            if elapsed < target_frame_time {
+                std::thread::sleep(target_frame_time - elapsed);
+            }
+        }
+
+        let now = Instant::now();
+        let delta = now - self.last_tick;
+        self.last_tick = now;
+
+        let frame_time = delta.as_secs_f32();
+        self.smoothed_frame_time = if self.smoothed_frame_time == 0.0 {
+            frame_time
+        } else {
+            self.smoothed_frame_time + (frame_time - self.smoothed_frame_time) * FPS_SMOOTHING
+        };
+        delta
+    }
+
+    /// Frames per second from an exponential moving average of the frame times, 0 before the
+    /// first tick.
+    pub fn fps(&self) -> f32 {
+        if self.smoothed_frame_time > 0.0 {
+            1.0 / self.smoothed_frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
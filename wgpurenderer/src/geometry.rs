@@ -0,0 +1,2013 @@
+use crate::utils;
+use glam::{Mat3, Mat4, Vec3};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    borrow::Cow,
+    f32::consts::{FRAC_PI_2, TAU},
+    fmt,
+    hash::Hash,
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
+use wgpu::util::DeviceExt;
+
+/// Upper bound for [`Geometry::subdivide`], every level quadruples the triangle count.
+pub const MAX_SUBDIVISION_LEVELS: u32 = 6;
+
+#[derive(Debug, Clone)]
+pub struct Geometry {
+    pub index_buffer: Option<wgpu::Buffer>,
+    pub index_buffer_range: Option<Range<u32>>,
+    pub index_format: wgpu::IndexFormat,
+    /// Signed offset added to every index before fetching the vertex, so sub-meshes packed
+    /// into one vertex buffer can keep indices relative to their own first vertex. Ignored by
+    /// non-indexed geometry.
+    pub base_vertex: i32,
+    /// Primitive topology the indices are laid out for. Draw calls are encoded the same for
+    /// every topology, so the `primitive.topology` of the material drawing this geometry has to
+    /// agree with it; a mismatch isn't detected and reinterprets the vertices.
+    pub topology: wgpu::PrimitiveTopology,
+    pub buffers: Vec<VertexBuffer>,
+    pub count: u32,
+    /// CPU copy of the index buffer, only kept with [`GeometryBuilder::keep_cpu_data`].
+    pub indices: Option<Arc<[u32]>>,
+    /// Number of instances in the attached instance buffer. Takes precedence over
+    /// `DrawCall::instance_count` when set.
+    pub instance_count: Option<u32>,
+}
+
+impl Geometry {
+    /// Starts uploading geometry to `device`, see [`GeometryBuilder`].
+    pub fn builder(device: &wgpu::Device) -> GeometryBuilder<'_> {
+        GeometryBuilder::new(device)
+    }
+
+    /// [`GeometryBuilder::interleaved`] without CPU copies.
+    pub fn from_interleaved<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+        indices: &[u32],
+        index_format: Option<wgpu::IndexFormat>,
+    ) -> Self {
+        GeometryBuilder::new(device).interleaved(vertices, attributes, indices, index_format)
+    }
+
+    /// [`GeometryBuilder::separate`] without CPU copies.
+    pub fn from_separate(
+        device: &wgpu::Device,
+        buffers: &[(u32, &[u8], VertexLayout)],
+        indices: &[u32],
+    ) -> Result<Self, GeometryError> {
+        GeometryBuilder::new(device).separate(buffers, indices)
+    }
+
+    /// [`GeometryBuilder::unindexed`] without CPU copies.
+    pub fn from_unindexed<V: bytemuck::Pod + Hash + Eq>(
+        device: &wgpu::Device,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+    ) -> Self {
+        GeometryBuilder::new(device).unindexed(vertices, attributes)
+    }
+
+    /// [`GeometryBuilder::unindexed_with_tolerance`] without CPU copies.
+    pub fn from_unindexed_with_tolerance<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+        tolerance: f32,
+    ) -> Self {
+        GeometryBuilder::new(device).unindexed_with_tolerance(vertices, attributes, tolerance)
+    }
+
+    /// [`GeometryBuilder::heightmap`] without CPU copies.
+    pub fn from_heightmap(
+        device: &wgpu::Device,
+        heights: &[f32],
+        width: u32,
+        height: u32,
+        scale: Vec3,
+    ) -> Result<Self, GeometryError> {
+        GeometryBuilder::new(device).heightmap(heights, width, height, scale)
+    }
+
+    /// [`GeometryBuilder::torus`] without CPU copies.
+    pub fn torus(
+        device: &wgpu::Device,
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        GeometryBuilder::new(device).torus(
+            major_radius,
+            minor_radius,
+            major_segments,
+            minor_segments,
+        )
+    }
+
+    /// [`GeometryBuilder::cylinder`] without CPU copies.
+    pub fn cylinder(device: &wgpu::Device, radius: f32, height: f32, segments: u32) -> Self {
+        GeometryBuilder::new(device).cylinder(radius, height, segments)
+    }
+
+    /// [`GeometryBuilder::cone`] without CPU copies.
+    pub fn cone(device: &wgpu::Device, radius: f32, height: f32, segments: u32) -> Self {
+        GeometryBuilder::new(device).cone(radius, height, segments)
+    }
+
+    /// [`GeometryBuilder::capsule`] without CPU copies.
+    pub fn capsule(
+        device: &wgpu::Device,
+        radius: f32,
+        cylinder_height: f32,
+        segments: u32,
+        rings: u32,
+    ) -> Self {
+        GeometryBuilder::new(device).capsule(radius, cylinder_height, segments, rings)
+    }
+
+    /// [`GeometryBuilder::point_cloud`] without CPU copies.
+    pub fn point_cloud(device: &wgpu::Device, positions: &[Vec3]) -> Self {
+        GeometryBuilder::new(device).point_cloud(positions)
+    }
+
+    /// [`GeometryBuilder::line_list`] without CPU copies.
+    pub fn line_list(device: &wgpu::Device, segments: &[[Vec3; 2]]) -> Self {
+        GeometryBuilder::new(device).line_list(segments)
+    }
+
+    /// [`GeometryBuilder::load_bin`] without CPU copies.
+    pub fn load_bin(
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, GeometryFileError> {
+        GeometryBuilder::new(device).load_bin(path)
+    }
+
+    /// Geometry without vertex buffers drawing `vertex_count` vertices as a triangle list, for
+    /// shaders that derive positions from `@builtin(vertex_index)`. Needs no device since
+    /// nothing is uploaded.
+    pub fn procedural(vertex_count: u32) -> Self {
+        Self {
+            index_buffer: None,
+            index_buffer_range: None,
+            index_format: wgpu::IndexFormat::Uint32,
+            base_vertex: 0,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            buffers: vec![],
+            count: vertex_count,
+            indices: None,
+            instance_count: None,
+        }
+    }
+
+    /// Concatenates geometries sharing the same vertex layouts into a single indexed geometry,
+    /// offsetting indices by the running vertex count. Requires CPU copies of the vertex and
+    /// index data, which the result keeps too; [`Self::drop_cpu_data`] releases them.
+    pub fn merge(device: &wgpu::Device, geometries: &[&Geometry]) -> Result<Self, GeometryError> {
+        let Some(first) = geometries.first() else {
+            return Err(GeometryError::Empty);
+        };
+
+        let mut contents: Vec<Vec<u8>> = vec![vec![]; first.buffers.len()];
+        let mut indices = Vec::new();
+        let mut vertex_count = 0u32;
+
+        for geometry in geometries {
+            if geometry.topology != first.topology
+                || geometry.buffers.len() != first.buffers.len()
+                || geometry.buffers.iter().zip(&first.buffers).any(|(a, b)| a.layout != b.layout)
+            {
+                return Err(GeometryError::LayoutMismatch);
+            }
+
+            let count = geometry.vertex_count()?;
+            for (merged, vertex_buffer) in contents.iter_mut().zip(&geometry.buffers) {
+                let bytes = vertex_buffer.contents().ok_or(GeometryError::MissingCpuData)?;
+                merged.extend_from_slice(bytes);
+            }
+
+            let offset = vertex_count.wrapping_add_signed(geometry.base_vertex);
+            indices.extend(geometry.drawn_indices()?.iter().map(|i| i.wrapping_add(offset)));
+            vertex_count += count;
+        }
+
+        let index_format = wgpu::IndexFormat::auto(vertex_count);
+
+        let buffers = contents
+            .iter()
+            .zip(&first.buffers)
+            .map(|(bytes, vertex_buffer)| {
+                VertexBuffer::with_cpu_data(device, bytes, vertex_buffer.layout.clone())
+            })
+            .collect();
+
+        Ok(Self {
+            index_buffer: Some(create_index_buffer(device, &indices, index_format)),
+            index_buffer_range: None,
+            index_format,
+            base_vertex: 0,
+            topology: first.topology,
+            buffers,
+            count: indices.len() as u32,
+            indices: Some(Arc::from(indices)),
+            instance_count: None,
+        })
+    }
+
+    /// Line list drawing every edge of this triangle list once, for debug wireframes without
+    /// `PolygonMode::Line`. Vertex buffers are shared with `self`.
+    pub fn to_wireframe(&self, device: &wgpu::Device) -> Result<Self, GeometryError> {
+        if self.topology != wgpu::PrimitiveTopology::TriangleList {
+            return Err(GeometryError::UnsupportedTopology(self.topology));
+        }
+
+        let mut edges = FxHashSet::default();
+        let mut indices = Vec::new();
+        for triangle in self.drawn_indices()?.chunks_exact(3) {
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (triangle[a], triangle[b]);
+                if edges.insert((a.min(b), a.max(b))) {
+                    indices.extend_from_slice(&[a, b]);
+                }
+            }
+        }
+
+        let index_format = match self.index_buffer {
+            Some(_) => self.index_format,
+            None => wgpu::IndexFormat::auto(self.count),
+        };
+
+        Ok(Self {
+            index_buffer: Some(create_index_buffer(device, &indices, index_format)),
+            index_buffer_range: None,
+            index_format,
+            base_vertex: self.base_vertex,
+            topology: wgpu::PrimitiveTopology::LineList,
+            buffers: self.buffers.clone(),
+            count: indices.len() as u32,
+            indices: Some(Arc::from(indices)),
+            instance_count: self.instance_count,
+        })
+    }
+
+    /// Bakes `matrix` into the vertex data and re-uploads it. Positions are the `Float32x3`
+    /// attribute at shader location 0 and normals the one at location 1 (if any), as in
+    /// [`MeshVertex`]. This permanently changes the stored geometry. Clones share the GPU
+    /// buffers and see the new vertices too, but keep their old CPU copy. Nothing is changed
+    /// when any of the buffers can't be transformed.
+    pub fn transform_vertices(
+        &mut self,
+        queue: &wgpu::Queue,
+        matrix: Mat4,
+    ) -> Result<(), GeometryError> {
+        // Every buffer is checked before the first one is modified
+        let mut transformed = vec![];
+        for (slot, vertex_buffer) in self.buffers.iter().enumerate() {
+            if vertex_buffer.layout.step_mode != wgpu::VertexStepMode::Vertex {
+                continue;
+            }
+            let find = |location| {
+                vertex_buffer.layout.attributes.iter().find(|a| {
+                    a.shader_location == location && a.format == wgpu::VertexFormat::Float32x3
+                })
+            };
+            let position = find(0).map(|a| a.offset as usize);
+            let normal = find(1).map(|a| a.offset as usize);
+            if position.is_none() && normal.is_none() {
+                continue;
+            }
+            if vertex_buffer.contents.is_none() {
+                return Err(GeometryError::MissingCpuData);
+            }
+            if vertex_buffer.layout.array_stride == 0 {
+                return Err(GeometryError::ZeroStride);
+            }
+            transformed.push((slot, position, normal));
+        }
+        if transformed.iter().all(|(_, position, _)| position.is_none()) {
+            return Err(GeometryError::MissingAttribute(0));
+        }
+
+        let normal_matrix = Mat3::from_mat4(matrix).inverse().transpose();
+        for (slot, position, normal) in transformed {
+            let vertex_buffer = &mut self.buffers[slot];
+            let stride = vertex_buffer.layout.array_stride as usize;
+            let buffer = vertex_buffer.buffer.clone();
+            let Some(bytes) = vertex_buffer.contents_mut() else {
+                continue;
+            };
+            for vertex in bytes.chunks_exact_mut(stride) {
+                if let Some(offset) = position {
+                    let value = read_vec3(vertex, offset);
+                    write_vec3(vertex, offset, matrix.transform_point3(value));
+                }
+                if let Some(offset) = normal {
+                    let value = read_vec3(vertex, offset);
+                    write_vec3(vertex, offset, (normal_matrix * value).normalize_or_zero());
+                }
+            }
+            queue.write_buffer(&buffer, 0, bytes);
+        }
+
+        Ok(())
+    }
+
+    /// Linear subdivision: every level splits each triangle into four through its edge
+    /// midpoints. Float attributes of the new vertices are interpolated, the normal at shader
+    /// location 1 is renormalized. Shared edges get a single midpoint vertex. Requires CPU
+    /// copies of the vertex and index data, which are replaced by the subdivided ones.
+    pub fn subdivide(&mut self, device: &wgpu::Device, levels: u32) -> Result<(), GeometryError> {
+        if self.topology != wgpu::PrimitiveTopology::TriangleList {
+            return Err(GeometryError::UnsupportedTopology(self.topology));
+        }
+        if levels > MAX_SUBDIVISION_LEVELS {
+            return Err(GeometryError::SubdivisionLimit(levels));
+        }
+        if levels == 0 {
+            return Ok(());
+        }
+
+        let slots: Vec<usize> = (0..self.buffers.len())
+            .filter(|&i| self.buffers[i].layout.step_mode == wgpu::VertexStepMode::Vertex)
+            .collect();
+        let layouts: Vec<&VertexLayout> = slots.iter().map(|&i| &self.buffers[i].layout).collect();
+        let mut contents = slots
+            .iter()
+            .map(|&i| self.buffers[i].contents().map(<[u8]>::to_vec))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(GeometryError::MissingCpuData)?;
+
+        let mut vertex_count = self.vertex_count()?;
+        let mut indices: Vec<u32> =
+            self.drawn_indices()?.iter().map(|i| i.wrapping_add_signed(self.base_vertex)).collect();
+
+        for _ in 0..levels {
+            let mut midpoints = FxHashMap::default();
+            let mut subdivided = Vec::with_capacity(indices.len() * 4);
+
+            for triangle in indices.chunks_exact(3) {
+                let mut midpoint = |a: u32, b: u32| {
+                    *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        for (bytes, layout) in contents.iter_mut().zip(&layouts) {
+                            push_midpoint_vertex(bytes, layout, a, b);
+                        }
+                        vertex_count += 1;
+                        vertex_count - 1
+                    })
+                };
+
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                subdivided.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+            }
+            indices = subdivided;
+        }
+
+        let buffers: Vec<VertexBuffer> = contents
+            .iter()
+            .zip(&layouts)
+            .map(|(bytes, layout)| VertexBuffer::with_cpu_data(device, bytes, (*layout).clone()))
+            .collect();
+        for (slot, vertex_buffer) in slots.into_iter().zip(buffers) {
+            self.buffers[slot] = vertex_buffer;
+        }
+
+        self.index_format = wgpu::IndexFormat::auto(vertex_count);
+        self.index_buffer = Some(create_index_buffer(device, &indices, self.index_format));
+        self.index_buffer_range = None;
+        self.base_vertex = 0;
+        self.count = indices.len() as u32;
+        self.indices = Some(Arc::from(indices));
+
+        Ok(())
+    }
+
+    /// Attaches a per-instance vertex buffer at `slot`, either right after the existing buffers
+    /// or replacing a previous instance buffer, and makes draws of this geometry use
+    /// `instances.len()` instances. Slots holding per-vertex data can't be replaced.
+    pub fn set_instance_buffer<I: bytemuck::Pod>(
+        &mut self,
+        device: &wgpu::Device,
+        slot: u32,
+        instances: &[I],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+    ) -> Result<(), GeometryError> {
+        let slot_index = slot as usize;
+        if slot_index > self.buffers.len() {
+            return Err(GeometryError::InvalidSlot(slot));
+        }
+        if self
+            .buffers
+            .get(slot_index)
+            .is_some_and(|buffer| buffer.layout.step_mode == wgpu::VertexStepMode::Vertex)
+        {
+            return Err(GeometryError::VertexSlot(slot));
+        }
+
+        let layout = VertexLayout {
+            array_stride: size_of::<I>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: attributes.into(),
+        };
+
+        let collision = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != slot_index)
+            .flat_map(|(_, vertex_buffer)| vertex_buffer.layout.attributes.iter())
+            .find(|a| layout.attributes.iter().any(|b| a.shader_location == b.shader_location));
+        if let Some(attribute) = collision {
+            return Err(GeometryError::LocationCollision(attribute.shader_location));
+        }
+
+        let instance_buffer = VertexBuffer::new(device, bytemuck::cast_slice(instances), layout);
+        if slot_index == self.buffers.len() {
+            self.buffers.push(instance_buffer);
+        } else {
+            self.buffers[slot_index] = instance_buffer;
+        }
+        self.instance_count = Some(instances.len() as u32);
+
+        Ok(())
+    }
+
+    /// Axis-aligned `(min, max)` of the `Float32x3` positions at shader location 0, over every
+    /// vertex in the buffer rather than only the indexed ones. Cached until the position
+    /// buffer's CPU copy or range changes, e.g. through [`VertexBuffer::update`] or
+    /// [`Self::transform_vertices`], so static meshes are scanned once. Empty geometry has zero
+    /// bounds.
+    pub fn bounds(&mut self) -> Result<(Vec3, Vec3), GeometryError> {
+        let (vertex_buffer, offset) = self
+            .buffers
+            .iter_mut()
+            .filter(|buffer| buffer.layout.step_mode == wgpu::VertexStepMode::Vertex)
+            .find_map(|buffer| {
+                let attribute = buffer.layout.attributes.iter().find(|a| {
+                    a.shader_location == 0 && a.format == wgpu::VertexFormat::Float32x3
+                })?;
+                let offset = attribute.offset as usize;
+                Some((buffer, offset))
+            })
+            .ok_or(GeometryError::MissingAttribute(0))?;
+        let bytes = vertex_buffer.contents().ok_or(GeometryError::MissingCpuData)?;
+
+        let stride = vertex_buffer.layout.array_stride;
+        if stride == 0 {
+            return Err(GeometryError::ZeroStride);
+        }
+        if let Some(cache) = &vertex_buffer.bounds_cache
+            && cache.range == vertex_buffer.range
+            && (cache.offset, cache.stride) == (offset, stride)
+        {
+            return Ok(cache.bounds);
+        }
+
+        let mut min = Vec3::INFINITY;
+        let mut max = Vec3::NEG_INFINITY;
+        for vertex in bytes.chunks_exact(stride as usize) {
+            let position = read_vec3(vertex, offset);
+            min = min.min(position);
+            max = max.max(position);
+        }
+        let bounds = if min.cmple(max).all() {
+            (min, max)
+        } else {
+            (Vec3::ZERO, Vec3::ZERO)
+        };
+
+        vertex_buffer.bounds_cache = Some(BoundsCache {
+            range: vertex_buffer.range.clone(),
+            offset,
+            stride,
+            bounds,
+        });
+        Ok(bounds)
+    }
+
+    /// Number of vertices addressed by the per-vertex buffers, derived from the CPU copies.
+    pub fn vertex_count(&self) -> Result<u32, GeometryError> {
+        count_vertices(self.buffers.iter().map(|buffer| (buffer.contents(), &buffer.layout)))
+    }
+
+    /// Releases the CPU copies of the vertex and index data, e.g. after [`Self::merge`].
+    /// CPU-side operations fail with [`GeometryError::MissingCpuData`] afterwards.
+    pub fn drop_cpu_data(&mut self) {
+        self.indices = None;
+        for vertex_buffer in &mut self.buffers {
+            vertex_buffer.contents = None;
+        }
+    }
+
+    /// Indices actually consumed by a draw of this geometry. Non-indexed geometry yields a
+    /// sequential list.
+    pub fn drawn_indices(&self) -> Result<Cow<'_, [u32]>, GeometryError> {
+        if self.index_buffer.is_none() {
+            return Ok(Cow::Owned((0..self.count).collect()));
+        }
+
+        let indices = self.indices.as_deref().ok_or(GeometryError::MissingCpuData)?;
+        let index_size = match self.index_format {
+            wgpu::IndexFormat::Uint16 => 2,
+            wgpu::IndexFormat::Uint32 => 4,
+        };
+        let start = self.index_buffer_range.as_ref().map_or(0, |r| r.start as usize / index_size);
+
+        Ok(Cow::Borrowed(&indices[start..start + self.count as usize]))
+    }
+
+    /// Writes the drawn vertex and index data to `path` in a small versioned binary format
+    /// that [`Geometry::load_bin`] uploads without any parsing beyond the header: magic,
+    /// version, topology, index format, counts and vertex layouts, followed by the raw bytes of
+    /// every buffer and the indices. Needs the CPU copies of every buffer; buffer and index
+    /// ranges are baked in.
+    pub fn save_bin(&self, path: impl AsRef<Path>) -> Result<(), GeometryFileError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(BIN_MAGIC);
+        bytes.extend_from_slice(&BIN_VERSION.to_le_bytes());
+
+        let topology = TOPOLOGIES.iter().position(|&t| t == self.topology).unwrap_or(0);
+        let index_format = match (&self.index_buffer, self.index_format) {
+            (None, _) => 0u32,
+            (Some(_), wgpu::IndexFormat::Uint16) => 1,
+            (Some(_), wgpu::IndexFormat::Uint32) => 2,
+        };
+        for value in [
+            topology as u32,
+            index_format,
+            self.base_vertex as u32,
+            self.count,
+            self.instance_count.unwrap_or(u32::MAX),
+            self.buffers.len() as u32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for vertex_buffer in &self.buffers {
+            let contents = vertex_buffer
+                .contents()
+                .ok_or(GeometryFileError::Geometry(GeometryError::MissingCpuData))?;
+            let layout = &vertex_buffer.layout;
+            bytes.extend_from_slice(&layout.array_stride.to_le_bytes());
+            let step_mode = match layout.step_mode {
+                wgpu::VertexStepMode::Vertex => 0u32,
+                wgpu::VertexStepMode::Instance => 1,
+            };
+            bytes.extend_from_slice(&step_mode.to_le_bytes());
+            bytes.extend_from_slice(&(layout.attributes.len() as u32).to_le_bytes());
+            for attribute in layout.attributes.iter() {
+                let format = VERTEX_FORMATS.iter().position(|&f| f == attribute.format);
+                bytes.extend_from_slice(&(format.unwrap_or(0) as u32).to_le_bytes());
+                bytes.extend_from_slice(&attribute.offset.to_le_bytes());
+                bytes.extend_from_slice(&attribute.shader_location.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(contents);
+        }
+
+        if self.index_buffer.is_some() {
+            let indices = self.drawn_indices().map_err(GeometryFileError::Geometry)?;
+            for index in indices.iter() {
+                bytes.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+
+        std::fs::write(path, bytes).map_err(GeometryFileError::Io)
+    }
+}
+
+/// Uploads geometry to a device. CPU copies of the vertex and index data are only kept with
+/// [`Self::keep_cpu_data`], since they double the memory of a mesh and most meshes are only
+/// drawn. The CPU-side operations of [`Geometry`], e.g. [`Geometry::merge`],
+/// [`Geometry::bounds`] or [`Geometry::save_bin`], fail with [`GeometryError::MissingCpuData`]
+/// without them. The constructors on `Geometry` are shorthands keeping no copies.
+#[derive(Debug, Clone, Copy)]
+pub struct GeometryBuilder<'a> {
+    device: &'a wgpu::Device,
+    keep_cpu_data: bool,
+}
+
+impl<'a> GeometryBuilder<'a> {
+    pub fn new(device: &'a wgpu::Device) -> Self {
+        Self {
+            device,
+            keep_cpu_data: false,
+        }
+    }
+
+    /// Keeps CPU copies of the uploaded vertex and index data in the built geometry.
+    pub fn keep_cpu_data(mut self) -> Self {
+        self.keep_cpu_data = true;
+        self
+    }
+
+    /// Uploads interleaved `vertices` and `indices` into a single-buffer indexed geometry.
+    /// When `index_format` is `None` it is picked from the vertex count.
+    pub fn interleaved<V: bytemuck::Pod>(
+        self,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+        indices: &[u32],
+        index_format: Option<wgpu::IndexFormat>,
+    ) -> Geometry {
+        let index_format =
+            index_format.unwrap_or_else(|| wgpu::IndexFormat::auto(vertices.len() as u32));
+        let layout = VertexLayout {
+            array_stride: size_of::<V>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: attributes.into(),
+        };
+        let vertex_buffer = self.vertex_buffer(bytemuck::cast_slice(vertices), layout);
+
+        let index_buffer = create_index_buffer(self.device, indices, index_format);
+
+        Geometry {
+            index_buffer: Some(index_buffer),
+            index_buffer_range: None,
+            index_format,
+            base_vertex: 0,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            buffers: vec![vertex_buffer],
+            count: indices.len() as u32,
+            indices: self.cpu_indices(indices),
+            instance_count: None,
+        }
+    }
+
+    /// Uploads non-interleaved vertex data, one [`VertexBuffer`] per `(slot, bytes, layout)`
+    /// entry, e.g. positions and normals read from separate glTF accessors. Strides come from
+    /// the layouts, so padded elements and buffers with unused attributes work. Slots have to
+    /// cover `0..buffers.len()` and no shader location may repeat. Like
+    /// [`Geometry::set_instance_buffer`], an instance-stepped buffer sets `instance_count`.
+    pub fn separate(
+        self,
+        buffers: &[(u32, &[u8], VertexLayout)],
+        indices: &[u32],
+    ) -> Result<Geometry, GeometryError> {
+        if buffers.is_empty() {
+            return Err(GeometryError::Empty);
+        }
+
+        let mut sorted: Vec<_> = buffers.iter().collect();
+        sorted.sort_by_key(|(slot, ..)| *slot);
+        for (i, (slot, ..)) in sorted.iter().enumerate() {
+            if *slot as usize != i {
+                return Err(GeometryError::InvalidSlot(*slot));
+            }
+        }
+
+        let mut locations = FxHashSet::default();
+        if let Some(attribute) = sorted
+            .iter()
+            .flat_map(|(_, _, layout)| layout.attributes.iter())
+            .find(|attribute| !locations.insert(attribute.shader_location))
+        {
+            return Err(GeometryError::LocationCollision(attribute.shader_location));
+        }
+
+        let layouts: Vec<_> = sorted.iter().map(|(_, _, layout)| layout.clone()).collect();
+        let vertex_count =
+            count_vertices(sorted.iter().map(|(_, contents, ..)| Some(*contents)).zip(&layouts))?;
+        let instance_count = sorted
+            .iter()
+            .zip(&layouts)
+            .find(|(_, layout)| {
+                layout.step_mode == wgpu::VertexStepMode::Instance && layout.array_stride > 0
+            })
+            .map(|((_, contents, ..), layout)| {
+                (contents.len() as u64 / layout.array_stride) as u32
+            });
+
+        let buffers = sorted
+            .iter()
+            .zip(layouts)
+            .map(|((_, contents, ..), layout)| self.vertex_buffer(contents, layout))
+            .collect();
+
+        let index_format = wgpu::IndexFormat::auto(vertex_count);
+        Ok(Geometry {
+            index_buffer: Some(create_index_buffer(self.device, indices, index_format)),
+            index_buffer_range: None,
+            index_format,
+            base_vertex: 0,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            buffers,
+            count: indices.len() as u32,
+            indices: self.cpu_indices(indices),
+            instance_count,
+        })
+    }
+
+    /// Builds an indexed geometry out of a flat triangle list, merging bitwise-equal vertices.
+    pub fn unindexed<V: bytemuck::Pod + Hash + Eq>(
+        self,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+    ) -> Geometry {
+        let (unique, indices) = deduplicate_vertices(vertices, |v| *v);
+        self.interleaved(&unique, attributes, &indices, Some(wgpu::IndexFormat::Uint32))
+    }
+
+    /// Same as [`Self::unindexed`], but float attributes are snapped to a grid of
+    /// `tolerance` before comparison, so near-equal vertices collapse into the first one seen.
+    /// Values straddling a grid cell boundary are not merged.
+    pub fn unindexed_with_tolerance<V: bytemuck::Pod>(
+        self,
+        vertices: &[V],
+        attributes: impl Into<Cow<'static, [wgpu::VertexAttribute]>>,
+        tolerance: f32,
+    ) -> Geometry {
+        let attributes = attributes.into();
+        let (unique, indices) = deduplicate_vertices(vertices, |v| {
+            quantized_vertex_key(bytemuck::bytes_of(v), &attributes, tolerance)
+        });
+        self.interleaved(&unique, attributes, &indices, Some(wgpu::IndexFormat::Uint32))
+    }
+
+    /// Terrain grid of `width * height` samples, spread over `scale.x * scale.z` around the
+    /// origin with heights multiplied by `scale.y`. Normals come from the height differences to
+    /// the neighbouring samples, clamped at the edges.
+    pub fn heightmap(
+        self,
+        heights: &[f32],
+        width: u32,
+        height: u32,
+        scale: Vec3,
+    ) -> Result<Geometry, GeometryError> {
+        let samples = width.checked_mul(height).map(|samples| samples as usize);
+        if width < 2 || height < 2 || samples != Some(heights.len()) {
+            return Err(GeometryError::InvalidHeightmap);
+        }
+
+        let step_x = scale.x / (width - 1) as f32;
+        let step_z = scale.z / (height - 1) as f32;
+        let sample = |x: u32, z: u32| heights[(z * width + x) as usize] * scale.y;
+
+        let mut vertices = Vec::with_capacity(heights.len());
+        for z in 0..height {
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (back, front) = (z.saturating_sub(1), (z + 1).min(height - 1));
+                let slope_x =
+                    (sample(right, z) - sample(left, z)) / ((right - left) as f32 * step_x);
+                let slope_z =
+                    (sample(x, front) - sample(x, back)) / ((front - back) as f32 * step_z);
+
+                let u = x as f32 / (width - 1) as f32;
+                let v = z as f32 / (height - 1) as f32;
+                vertices.push(MeshVertex {
+                    position: [(u - 0.5) * scale.x, sample(x, z), (v - 0.5) * scale.z],
+                    normal: Vec3::new(-slope_x, 1.0, -slope_z).normalize().to_array(),
+                    uv: [u, v],
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity((width - 1) as usize * (height - 1) as usize * 6);
+        for z in 0..height - 1 {
+            for x in 0..width - 1 {
+                let a = z * width + x;
+                let b = a + width;
+                indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+
+        Ok(self.interleaved(&vertices, &MeshVertex::ATTRIBUTES[..], &indices, None))
+    }
+
+    /// Ring torus around the y axis with `major_segments` around the axis and `minor_segments`
+    /// around the tube, each at least 3. The seam vertices are duplicated so texture
+    /// coordinates wrap cleanly.
+    pub fn torus(
+        self,
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Geometry {
+        let (major, minor) = (major_segments.max(3), minor_segments.max(3));
+        let (vertex_count, index_count) = torus_counts(major, minor);
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for row in 0..=minor {
+            let v = row as f32 / minor as f32;
+            let (sin_phi, cos_phi) = (v * TAU).sin_cos();
+            let ring = major_radius + minor_radius * cos_phi;
+            for column in 0..=major {
+                let u = column as f32 / major as f32;
+                let (sin_theta, cos_theta) = (u * TAU).sin_cos();
+                vertices.push(MeshVertex {
+                    position: [ring * cos_theta, minor_radius * sin_phi, ring * sin_theta],
+                    normal: [cos_phi * cos_theta, sin_phi, cos_phi * sin_theta],
+                    uv: [u, v],
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity(index_count);
+        push_grid_indices(&mut indices, 0, minor, major, false, false);
+
+        self.mesh_vertices(&vertices, &indices)
+    }
+
+    /// Capped cylinder along the y axis, centered on the origin, with at least 3 `segments`.
+    pub fn cylinder(self, radius: f32, height: f32, segments: u32) -> Geometry {
+        let segments = segments.max(3);
+        let (vertex_count, index_count) = cylinder_counts(segments);
+        let half_height = height * 0.5;
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for row in 0..=1 {
+            for column in 0..=segments {
+                let u = column as f32 / segments as f32;
+                let (sin_theta, cos_theta) = (u * TAU).sin_cos();
+                vertices.push(MeshVertex {
+                    position: [
+                        radius * cos_theta,
+                        if row == 0 {
+                            -half_height
+                        } else {
+                            half_height
+                        },
+                        radius * sin_theta,
+                    ],
+                    normal: [cos_theta, 0.0, sin_theta],
+                    uv: [u, 1.0 - row as f32],
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity(index_count);
+        push_grid_indices(&mut indices, 0, 1, segments, false, false);
+        push_cap(&mut vertices, &mut indices, radius, -half_height, segments);
+        push_cap(&mut vertices, &mut indices, radius, half_height, segments);
+
+        self.mesh_vertices(&vertices, &indices)
+    }
+
+    /// Cone along the y axis with its base at `-height / 2` and the apex at `height / 2`, with
+    /// at least 3 `segments`. The apex is split per segment so each side face gets its own
+    /// normal.
+    pub fn cone(self, radius: f32, height: f32, segments: u32) -> Geometry {
+        let segments = segments.max(3);
+        let (vertex_count, index_count) = cone_counts(segments);
+        let half_height = height * 0.5;
+        let side_normal = |angle: f32| {
+            let (sin_theta, cos_theta) = angle.sin_cos();
+            Vec3::new(height * cos_theta, radius, height * sin_theta).normalize().to_array()
+        };
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for column in 0..=segments {
+            let u = column as f32 / segments as f32;
+            let (sin_theta, cos_theta) = (u * TAU).sin_cos();
+            vertices.push(MeshVertex {
+                position: [radius * cos_theta, -half_height, radius * sin_theta],
+                normal: side_normal(u * TAU),
+                uv: [u, 1.0],
+            });
+        }
+        for column in 0..=segments {
+            // Halfway around the face the apex vertex closes
+            let u = (column as f32 + 0.5) / segments as f32;
+            vertices.push(MeshVertex {
+                position: [0.0, half_height, 0.0],
+                normal: side_normal(u * TAU),
+                uv: [u, 0.0],
+            });
+        }
+
+        let mut indices = Vec::with_capacity(index_count);
+        push_grid_indices(&mut indices, 0, 1, segments, false, true);
+        push_cap(&mut vertices, &mut indices, radius, -half_height, segments);
+
+        self.mesh_vertices(&vertices, &indices)
+    }
+
+    /// Capsule along the y axis: a cylinder of `cylinder_height` closed by two hemispheres of
+    /// `radius`, so the total height is `cylinder_height + 2 * radius`. Each hemisphere has
+    /// `rings` latitude bands (at least 1) and there are at least 3 `segments` around.
+    pub fn capsule(self, radius: f32, cylinder_height: f32, segments: u32, rings: u32) -> Geometry {
+        let (segments, rings) = (segments.max(3), rings.max(1));
+        let (vertex_count, index_count) = capsule_counts(segments, rings);
+        let half_height = cylinder_height * 0.5;
+        let total_height = cylinder_height + 2.0 * radius;
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        // Latitudes from the bottom pole to the top one, the equator of each hemisphere is
+        // a separate row so the straight section between them stays a single band
+        for row in 0..2 * (rings + 1) {
+            let (band, center_y) = if row <= rings {
+                (row as f32 / rings as f32 - 1.0, -half_height)
+            } else {
+                ((row - rings - 1) as f32 / rings as f32, half_height)
+            };
+            let (sin_lat, cos_lat) = (band * FRAC_PI_2).sin_cos();
+            let y = center_y + radius * sin_lat;
+            for column in 0..=segments {
+                let u = column as f32 / segments as f32;
+                let (sin_theta, cos_theta) = (u * TAU).sin_cos();
+                let normal = [cos_lat * cos_theta, sin_lat, cos_lat * sin_theta];
+                vertices.push(MeshVertex {
+                    position: [radius * normal[0], y, radius * normal[2]],
+                    normal,
+                    uv: [u, 0.5 - y / total_height],
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity(index_count);
+        push_grid_indices(&mut indices, 0, 2 * rings + 1, segments, true, true);
+
+        self.mesh_vertices(&vertices, &indices)
+    }
+
+    fn mesh_vertices(self, vertices: &[MeshVertex], indices: &[u32]) -> Geometry {
+        self.interleaved(
+            vertices,
+            &MeshVertex::ATTRIBUTES[..],
+            indices,
+            Some(wgpu::IndexFormat::Uint32),
+        )
+    }
+
+    /// Non-indexed `PointList` of `positions` at shader location 0, e.g. for
+    /// [`crate::shader::presets::unlit`].
+    pub fn point_cloud(self, positions: &[Vec3]) -> Geometry {
+        self.positions(positions, wgpu::PrimitiveTopology::PointList)
+    }
+
+    /// Non-indexed `LineList` drawing one line per `[start, end]` segment, positions at shader
+    /// location 0.
+    pub fn line_list(self, segments: &[[Vec3; 2]]) -> Geometry {
+        self.positions(segments.as_flattened(), wgpu::PrimitiveTopology::LineList)
+    }
+
+    fn positions(self, positions: &[Vec3], topology: wgpu::PrimitiveTopology) -> Geometry {
+        let layout = VertexLayout {
+            array_stride: size_of::<Vec3>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Cow::Borrowed(&wgpu::vertex_attr_array![0 => Float32x3]),
+        };
+
+        Geometry {
+            index_buffer: None,
+            index_buffer_range: None,
+            index_format: wgpu::IndexFormat::Uint32,
+            base_vertex: 0,
+            topology,
+            buffers: vec![self.vertex_buffer(bytemuck::cast_slice(positions), layout)],
+            count: positions.len() as u32,
+            indices: None,
+            instance_count: None,
+        }
+    }
+
+    /// Reads a geometry written by [`Geometry::save_bin`] and uploads it.
+    pub fn load_bin(self, path: impl AsRef<Path>) -> Result<Geometry, GeometryFileError> {
+        let bytes = std::fs::read(path).map_err(GeometryFileError::Io)?;
+        let mut reader = BinReader {
+            bytes: &bytes,
+        };
+
+        if reader.take(BIN_MAGIC.len())? != BIN_MAGIC {
+            return Err(GeometryFileError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != BIN_VERSION {
+            return Err(GeometryFileError::UnsupportedVersion(version));
+        }
+
+        let topology = *TOPOLOGIES.get(reader.u32()? as usize).ok_or(GeometryFileError::Corrupt)?;
+        let index_format = match reader.u32()? {
+            0 => None,
+            1 => Some(wgpu::IndexFormat::Uint16),
+            2 => Some(wgpu::IndexFormat::Uint32),
+            _ => return Err(GeometryFileError::Corrupt),
+        };
+        let base_vertex = reader.u32()? as i32;
+        let count = reader.u32()?;
+        let instance_count = Some(reader.u32()?).filter(|&count| count != u32::MAX);
+        let buffer_count = reader.u32()?;
+
+        let mut buffers = Vec::new();
+        for _ in 0..buffer_count {
+            let array_stride = reader.u64()?;
+            let step_mode = match reader.u32()? {
+                0 => wgpu::VertexStepMode::Vertex,
+                1 => wgpu::VertexStepMode::Instance,
+                _ => return Err(GeometryFileError::Corrupt),
+            };
+            let mut attributes = Vec::new();
+            for _ in 0..reader.u32()? {
+                attributes.push(wgpu::VertexAttribute {
+                    format: *VERTEX_FORMATS
+                        .get(reader.u32()? as usize)
+                        .ok_or(GeometryFileError::Corrupt)?,
+                    offset: reader.u64()?,
+                    shader_location: reader.u32()?,
+                });
+            }
+            let len = usize::try_from(reader.u64()?).map_err(|_| GeometryFileError::Corrupt)?;
+            let layout = VertexLayout {
+                array_stride,
+                step_mode,
+                attributes: Cow::Owned(attributes),
+            };
+            buffers.push(self.vertex_buffer(reader.take(len)?, layout));
+        }
+
+        let (index_buffer, indices) = match index_format {
+            Some(format) => {
+                let indices: Vec<u32> = reader
+                    .take(count as usize * size_of::<u32>())?
+                    .chunks_exact(size_of::<u32>())
+                    .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                (
+                    Some(create_index_buffer(self.device, &indices, format)),
+                    self.cpu_indices(&indices),
+                )
+            }
+            None => (None, None),
+        };
+        if !reader.bytes.is_empty() {
+            return Err(GeometryFileError::Corrupt);
+        }
+
+        Ok(Geometry {
+            index_buffer,
+            index_buffer_range: None,
+            index_format: index_format.unwrap_or(wgpu::IndexFormat::Uint32),
+            base_vertex,
+            topology,
+            buffers,
+            count,
+            indices,
+            instance_count,
+        })
+    }
+
+    fn vertex_buffer(self, contents: &[u8], layout: VertexLayout) -> VertexBuffer {
+        if self.keep_cpu_data {
+            VertexBuffer::with_cpu_data(self.device, contents, layout)
+        } else {
+            VertexBuffer::new(self.device, contents, layout)
+        }
+    }
+
+    fn cpu_indices(self, indices: &[u32]) -> Option<Arc<[u32]>> {
+        self.keep_cpu_data.then(|| Arc::from(indices))
+    }
+}
+
+/// Position, normal and texture coordinate at shader locations 0, 1 and 2. Used by the built-in
+/// mesh generators.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+impl MeshVertex {
+    pub const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+}
+
+pub trait IndexFormatExt {
+    /// Smallest index format able to address `vertex_count` vertices.
+    fn auto(vertex_count: u32) -> Self;
+}
+
+impl IndexFormatExt for wgpu::IndexFormat {
+    fn auto(vertex_count: u32) -> Self {
+        if vertex_count <= u16::MAX as u32 {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryError {
+    Empty,
+    LayoutMismatch,
+    MissingCpuData,
+    InvalidSlot(u32),
+    /// The slot holds a per-vertex buffer, which an instance buffer can't replace.
+    VertexSlot(u32),
+    LocationCollision(u32),
+    InvalidHeightmap,
+    UnsupportedTopology(wgpu::PrimitiveTopology),
+    MissingAttribute(u32),
+    SubdivisionLimit(u32),
+    /// A per-vertex buffer has an `array_stride` of 0, so its vertices can't be told apart.
+    ZeroStride,
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no geometry was provided"),
+            Self::LayoutMismatch => write!(f, "vertex layouts of the geometries differ"),
+            Self::MissingCpuData => write!(f, "geometry has no CPU copy of its vertex data"),
+            Self::InvalidSlot(slot) => write!(f, "vertex buffer slot {slot} leaves a gap"),
+            Self::VertexSlot(slot) => {
+                write!(f, "vertex buffer slot {slot} already holds per-vertex data")
+            }
+            Self::LocationCollision(location) => {
+                write!(f, "shader location {location} is already used by another buffer")
+            }
+            Self::InvalidHeightmap => {
+                write!(f, "heightmap needs at least 2x2 samples matching its dimensions")
+            }
+            Self::UnsupportedTopology(topology) => {
+                write!(f, "operation doesn't support {topology:?} geometry")
+            }
+            Self::MissingAttribute(location) => {
+                write!(f, "no Float32x3 vertex attribute at shader location {location}")
+            }
+            Self::SubdivisionLimit(levels) => {
+                write!(
+                    f,
+                    "{levels} subdivision levels exceed the limit of {MAX_SUBDIVISION_LEVELS}"
+                )
+            }
+            Self::ZeroStride => write!(f, "per-vertex buffer has an array stride of 0"),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+#[derive(Debug)]
+pub enum GeometryFileError {
+    Io(std::io::Error),
+    /// The file doesn't start with the geometry magic bytes.
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    /// Truncated data or an out of range enum value.
+    Corrupt,
+    Geometry(GeometryError),
+}
+
+impl fmt::Display for GeometryFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to access geometry file: {e}"),
+            Self::InvalidMagic => write!(f, "not a geometry file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "geometry file version {version}, expected {BIN_VERSION}")
+            }
+            Self::Corrupt => write!(f, "geometry file is truncated or corrupt"),
+            Self::Geometry(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for GeometryFileError {}
+
+#[derive(Debug, Clone)]
+pub struct VertexBuffer {
+    pub buffer: wgpu::Buffer,
+    pub range: Option<Range<u32>>,
+    pub layout: VertexLayout,
+    /// CPU copy of the bytes written so far, only kept when created [`Self::with_cpu_data`].
+    contents: Option<Arc<Vec<u8>>>,
+    /// Result of the last [`Geometry::bounds`] over this buffer, reset by every change of
+    /// `contents`.
+    bounds_cache: Option<BoundsCache>,
+}
+
+impl VertexBuffer {
+    /// Uploads `contents` without keeping a CPU copy.
+    pub fn new(device: &wgpu::Device, contents: &[u8], layout: VertexLayout) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents,
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+        Self::from_buffer(buffer, layout)
+    }
+
+    /// Uploads `contents` and keeps a CPU copy of it, updated along with the buffer, for the
+    /// CPU-side operations of [`Geometry`].
+    pub fn with_cpu_data(device: &wgpu::Device, contents: &[u8], layout: VertexLayout) -> Self {
+        let mut vertex_buffer = Self::new(device, contents, layout);
+        vertex_buffer.contents = Some(Arc::new(contents.to_vec()));
+        vertex_buffer
+    }
+
+    /// Wraps a buffer created elsewhere, without a CPU copy.
+    pub fn from_buffer(buffer: wgpu::Buffer, layout: VertexLayout) -> Self {
+        Self {
+            buffer,
+            range: None,
+            layout,
+            contents: None,
+            bounds_cache: None,
+        }
+    }
+
+    /// Allocated size of the GPU buffer in bytes, independent of how much of it holds vertices.
+    pub fn capacity(&self) -> wgpu::BufferAddress {
+        self.buffer.size()
+    }
+
+    /// Writes `data` at `offset` bytes. The write has to fit into the current capacity.
+    pub fn update(&mut self, queue: &wgpu::Queue, offset: wgpu::BufferAddress, data: &[u8]) {
+        if offset + data.len() as wgpu::BufferAddress > self.capacity() {
+            utils::cold_panic("Vertex buffer update out of bounds");
+        }
+
+        queue.write_buffer(&self.buffer, offset, data);
+        self.update_contents(offset as usize, data);
+    }
+
+    /// Like [`VertexBuffer::update`], but reallocates the buffer to the next power of two when
+    /// the write doesn't fit, keeping the old contents. Clones of the owning `Geometry` made
+    /// before the call keep referencing the old buffer.
+    pub fn update_growable(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        let required = offset + data.len() as wgpu::BufferAddress;
+
+        if required > self.capacity() {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: required.next_power_of_two().max(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: self.buffer.usage(),
+                mapped_at_creation: false,
+            });
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: None,
+            });
+            encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.capacity());
+            queue.submit(Some(encoder.finish()));
+
+            self.buffer = buffer;
+        }
+
+        self.update(queue, offset, data);
+    }
+
+    fn update_contents(&mut self, offset: usize, data: &[u8]) {
+        if let Some(bytes) = self.contents_mut() {
+            if offset + data.len() > bytes.len() {
+                bytes.resize(offset + data.len(), 0);
+            }
+            bytes[offset..offset + data.len()].copy_from_slice(data);
+        }
+    }
+
+    /// Whole CPU copy for patching in place. It is only cloned while a clone of the geometry
+    /// still shares it.
+    fn contents_mut(&mut self) -> Option<&mut Vec<u8>> {
+        self.bounds_cache = None;
+        self.contents.as_mut().map(Arc::make_mut)
+    }
+
+    /// CPU copy of the bytes bound for drawing, honoring `range`.
+    pub fn contents(&self) -> Option<&[u8]> {
+        let contents = self.contents.as_deref()?;
+        Some(match &self.range {
+            Some(range) => &contents[range.start as usize..range.end as usize],
+            None => contents.as_slice(),
+        })
+    }
+}
+
+/// Bounds computed by [`Geometry::bounds`] and the range and position layout they were
+/// computed for, which are public fields of [`VertexBuffer`] and may change without it knowing.
+#[derive(Debug, Clone)]
+struct BoundsCache {
+    range: Option<Range<u32>>,
+    offset: usize,
+    stride: wgpu::BufferAddress,
+    bounds: (Vec3, Vec3),
+}
+
+/// Owned counterpart of [`wgpu::VertexBufferLayout`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub array_stride: wgpu::BufferAddress,
+    pub step_mode: wgpu::VertexStepMode,
+    pub attributes: Cow<'static, [wgpu::VertexAttribute]>,
+}
+
+impl VertexLayout {
+    pub fn wgpu_layout(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+impl From<wgpu::VertexBufferLayout<'static>> for VertexLayout {
+    fn from(layout: wgpu::VertexBufferLayout<'static>) -> Self {
+        Self {
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: Cow::Borrowed(layout.attributes),
+        }
+    }
+}
+
+/// Leading bytes of a [`Geometry::save_bin`] file.
+const BIN_MAGIC: &[u8; 4] = b"WGRG";
+/// Bumped on every layout change, older files are rejected rather than misread.
+const BIN_VERSION: u32 = 1;
+
+/// Encoding order of topologies in geometry files, never reorder.
+const TOPOLOGIES: [wgpu::PrimitiveTopology; 5] = [
+    wgpu::PrimitiveTopology::TriangleList,
+    wgpu::PrimitiveTopology::TriangleStrip,
+    wgpu::PrimitiveTopology::LineList,
+    wgpu::PrimitiveTopology::LineStrip,
+    wgpu::PrimitiveTopology::PointList,
+];
+
+/// Encoding order of vertex formats in geometry files, only ever append.
+const VERTEX_FORMATS: [wgpu::VertexFormat; 45] = {
+    use wgpu::VertexFormat as Vf;
+    [
+        Vf::Uint8,
+        Vf::Uint8x2,
+        Vf::Uint8x4,
+        Vf::Sint8,
+        Vf::Sint8x2,
+        Vf::Sint8x4,
+        Vf::Unorm8,
+        Vf::Unorm8x2,
+        Vf::Unorm8x4,
+        Vf::Snorm8,
+        Vf::Snorm8x2,
+        Vf::Snorm8x4,
+        Vf::Uint16,
+        Vf::Uint16x2,
+        Vf::Uint16x4,
+        Vf::Sint16,
+        Vf::Sint16x2,
+        Vf::Sint16x4,
+        Vf::Unorm16,
+        Vf::Unorm16x2,
+        Vf::Unorm16x4,
+        Vf::Snorm16,
+        Vf::Snorm16x2,
+        Vf::Snorm16x4,
+        Vf::Float16,
+        Vf::Float16x2,
+        Vf::Float16x4,
+        Vf::Float32,
+        Vf::Float32x2,
+        Vf::Float32x3,
+        Vf::Float32x4,
+        Vf::Uint32,
+        Vf::Uint32x2,
+        Vf::Uint32x3,
+        Vf::Uint32x4,
+        Vf::Sint32,
+        Vf::Sint32x2,
+        Vf::Sint32x3,
+        Vf::Sint32x4,
+        Vf::Float64,
+        Vf::Float64x2,
+        Vf::Float64x3,
+        Vf::Float64x4,
+        Vf::Unorm10_10_10_2,
+        Vf::Unorm8x4Bgra,
+    ]
+};
+
+/// Little-endian cursor over a geometry file.
+struct BinReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], GeometryFileError> {
+        if len > self.bytes.len() {
+            return Err(GeometryFileError::Corrupt);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, GeometryFileError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, GeometryFileError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Vertices addressed by the per-vertex buffers among `buffers`, which all have to agree.
+fn count_vertices<'b>(
+    buffers: impl IntoIterator<Item = (Option<&'b [u8]>, &'b VertexLayout)>,
+) -> Result<u32, GeometryError> {
+    let mut count = None;
+
+    for (bytes, layout) in buffers {
+        if layout.step_mode != wgpu::VertexStepMode::Vertex {
+            continue;
+        }
+        let bytes = bytes.ok_or(GeometryError::MissingCpuData)?;
+        let buffer_count = bytes
+            .len()
+            .checked_div(layout.array_stride as usize)
+            .ok_or(GeometryError::ZeroStride)? as u32;
+        if count.is_some_and(|c| c != buffer_count) {
+            return Err(GeometryError::LayoutMismatch);
+        }
+        count = Some(buffer_count);
+    }
+
+    Ok(count.unwrap_or(0))
+}
+
+fn create_index_buffer(
+    device: &wgpu::Device,
+    indices: &[u32],
+    index_format: wgpu::IndexFormat,
+) -> wgpu::Buffer {
+    let narrowed: Vec<u16>;
+    let contents = match index_format {
+        wgpu::IndexFormat::Uint16 => {
+            debug_assert!(
+                indices.iter().all(|&i| i <= u16::MAX as u32),
+                "Index exceeds u16::MAX, use IndexFormat::Uint32"
+            );
+            narrowed = indices.iter().map(|&i| i as u16).collect();
+            bytemuck::cast_slice(&narrowed)
+        }
+        wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices),
+    };
+
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents,
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+/// Vertex and index counts of [`Geometry::torus`].
+fn torus_counts(major_segments: u32, minor_segments: u32) -> (usize, usize) {
+    let (major, minor) = (major_segments as usize, minor_segments as usize);
+    ((major + 1) * (minor + 1), major * minor * 6)
+}
+
+/// Vertex and index counts of [`Geometry::cylinder`]: the side band plus two caps.
+fn cylinder_counts(segments: u32) -> (usize, usize) {
+    let segments = segments as usize;
+    (2 * (segments + 1) + 2 * (segments + 1), segments * 12)
+}
+
+/// Vertex and index counts of [`Geometry::cone`]: the side with a split apex plus the base.
+fn cone_counts(segments: u32) -> (usize, usize) {
+    let segments = segments as usize;
+    (2 * (segments + 1) + segments + 1, segments * 6)
+}
+
+/// Vertex and index counts of [`Geometry::capsule`]. The pole bands only have one triangle
+/// per segment.
+fn capsule_counts(segments: u32, rings: u32) -> (usize, usize) {
+    let (segments, rings) = (segments as usize, rings as usize);
+    (2 * (rings + 1) * (segments + 1), rings * segments * 12)
+}
+
+/// Triangulates `rows * columns` quads of a vertex grid starting at `base`, with
+/// `columns + 1` vertices per row. Rows advance across the surface so that, seen from the
+/// front, columns run to the right and rows upwards. A collapsed first or last row (a pole)
+/// only gets the triangle that isn't degenerate.
+fn push_grid_indices(
+    indices: &mut Vec<u32>,
+    base: u32,
+    rows: u32,
+    columns: u32,
+    collapsed_first: bool,
+    collapsed_last: bool,
+) {
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = base + row * stride + column;
+            let b = a + stride;
+            if !(collapsed_first && row == 0) {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if !(collapsed_last && row == rows - 1) {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+}
+
+/// Appends a disc of `radius` at height `y` facing away from the origin, as a center vertex
+/// and a fan of `segments` rim vertices with planar texture coordinates.
+fn push_cap(
+    vertices: &mut Vec<MeshVertex>,
+    indices: &mut Vec<u32>,
+    radius: f32,
+    y: f32,
+    segments: u32,
+) {
+    let normal_y = if y < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let center = vertices.len() as u32;
+    vertices.push(MeshVertex {
+        position: [0.0, y, 0.0],
+        normal: [0.0, normal_y, 0.0],
+        uv: [0.5, 0.5],
+    });
+
+    for column in 0..segments {
+        let (sin_theta, cos_theta) = (column as f32 / segments as f32 * TAU).sin_cos();
+        vertices.push(MeshVertex {
+            position: [radius * cos_theta, y, radius * sin_theta],
+            normal: [0.0, normal_y, 0.0],
+            uv: [0.5 + 0.5 * cos_theta, 0.5 + 0.5 * sin_theta],
+        });
+
+        let (current, next) = (center + 1 + column, center + 1 + (column + 1) % segments);
+        if normal_y > 0.0 {
+            indices.extend_from_slice(&[center, next, current]);
+        } else {
+            indices.extend_from_slice(&[center, current, next]);
+        }
+    }
+}
+
+/// Appends the vertex halfway between vertices `a` and `b`. Float attributes are averaged,
+/// everything else is copied from `a`.
+fn push_midpoint_vertex(bytes: &mut Vec<u8>, layout: &VertexLayout, a: u32, b: u32) {
+    let stride = layout.array_stride as usize;
+    let (a, b) = (a as usize * stride, b as usize * stride);
+    let mut vertex = bytes[a..a + stride].to_vec();
+
+    for attribute in layout.attributes.iter() {
+        let components = match attribute.format {
+            wgpu::VertexFormat::Float32 => 1,
+            wgpu::VertexFormat::Float32x2 => 2,
+            wgpu::VertexFormat::Float32x3 => 3,
+            wgpu::VertexFormat::Float32x4 => 4,
+            _ => continue,
+        };
+
+        let offset = attribute.offset as usize;
+        for component in 0..components {
+            let at = offset + component * 4;
+            let x: f32 = bytemuck::pod_read_unaligned(&bytes[a + at..a + at + 4]);
+            let y: f32 = bytemuck::pod_read_unaligned(&bytes[b + at..b + at + 4]);
+            vertex[at..at + 4].copy_from_slice(&((x + y) * 0.5).to_ne_bytes());
+        }
+
+        if attribute.shader_location == 1 && components == 3 {
+            let normal = read_vec3(&vertex, offset).normalize_or_zero();
+            write_vec3(&mut vertex, offset, normal);
+        }
+    }
+
+    bytes.extend_from_slice(&vertex);
+}
+
+fn read_vec3(vertex: &[u8], offset: usize) -> Vec3 {
+    Vec3::from_array(bytemuck::pod_read_unaligned(&vertex[offset..offset + 12]))
+}
+
+fn write_vec3(vertex: &mut [u8], offset: usize, value: Vec3) {
+    vertex[offset..offset + 12].copy_from_slice(bytemuck::bytes_of(&value.to_array()));
+}
+
+fn deduplicate_vertices<V: Copy, K: Hash + Eq>(
+    vertices: &[V],
+    key: impl Fn(&V) -> K,
+) -> (Vec<V>, Vec<u32>) {
+    let mut lookup = FxHashMap::default();
+    let mut unique = Vec::new();
+
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            *lookup.entry(key(vertex)).or_insert_with(|| {
+                unique.push(*vertex);
+                (unique.len() - 1) as u32
+            })
+        })
+        .collect();
+
+    (unique, indices)
+}
+
+fn quantized_vertex_key(
+    bytes: &[u8],
+    attributes: &[wgpu::VertexAttribute],
+    tolerance: f32,
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(bytes.len());
+
+    for attribute in attributes {
+        let start = attribute.offset as usize;
+        let end = start + attribute.format.size() as usize;
+        let components = match attribute.format {
+            wgpu::VertexFormat::Float32 => 1,
+            wgpu::VertexFormat::Float32x2 => 2,
+            wgpu::VertexFormat::Float32x3 => 3,
+            wgpu::VertexFormat::Float32x4 => 4,
+            _ => 0,
+        };
+
+        if components == 0 || tolerance <= 0.0 {
+            key.extend_from_slice(&bytes[start..end]);
+            continue;
+        }
+
+        for chunk in bytes[start..end].chunks_exact(4) {
+            let value = f32::from_ne_bytes(chunk.try_into().unwrap());
+            let quantized = (value / tolerance).round() as i64;
+            key.extend_from_slice(&quantized.to_ne_bytes());
+        }
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, tests};
+
+    const NORMAL_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: 12,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![1 => Float32x3],
+    };
+
+    #[test]
+    fn from_unindexed_collapses_quad() {
+        let context = Context::noop();
+        // Two triangles of a unit quad, the shared diagonal repeated
+        let corners: [[u32; 2]; 6] = [[0, 0], [1, 0], [1, 1], [0, 0], [1, 1], [0, 1]];
+        let attributes = wgpu::vertex_attr_array![0 => Uint32x2];
+
+        let geometry = Geometry::builder(context.device())
+            .keep_cpu_data()
+            .unindexed(&corners, attributes.to_vec());
+
+        assert_eq!(geometry.vertex_count(), Ok(4));
+        assert_eq!(geometry.count, 6);
+        assert_eq!(geometry.indices.as_deref(), Some(&[0, 1, 2, 0, 2, 3][..]));
+    }
+
+    #[test]
+    fn from_unindexed_with_tolerance_merges_near_equal() {
+        let context = Context::noop();
+        let jitter = 1e-5;
+        let corners: [[f32; 2]; 6] =
+            [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [jitter, 0.0], [1.0, 1.0 - jitter], [0.0, 1.0]];
+        let attributes = wgpu::vertex_attr_array![0 => Float32x2];
+
+        let builder = Geometry::builder(context.device()).keep_cpu_data();
+        let merged = builder.unindexed_with_tolerance(&corners, attributes.to_vec(), 1e-3);
+        let exact = builder.unindexed_with_tolerance(&corners, attributes.to_vec(), 0.0);
+
+        assert_eq!(merged.vertex_count(), Ok(4));
+        assert_eq!(exact.vertex_count(), Ok(6));
+    }
+
+    #[test]
+    fn cpu_data_is_opt_in() {
+        let context = Context::noop();
+        let drawn_only = Geometry::cylinder(context.device(), 1.0, 1.0, 8);
+        let kept = Geometry::builder(context.device()).keep_cpu_data().cylinder(1.0, 1.0, 8);
+
+        assert!(drawn_only.indices.is_none());
+        assert!(drawn_only.buffers.iter().all(|buffer| buffer.contents().is_none()));
+        assert_eq!(
+            Geometry::merge(context.device(), &[&drawn_only]).err(),
+            Some(GeometryError::MissingCpuData)
+        );
+
+        let mut merged = Geometry::merge(context.device(), &[&kept, &kept]).unwrap();
+        let (vertex_count, index_count) = cylinder_counts(8);
+        assert_eq!(merged.vertex_count(), Ok(2 * vertex_count as u32));
+        assert_eq!(merged.count, 2 * index_count as u32);
+        // Indices of the second copy are offset by the vertices of the first
+        let indices = merged.drawn_indices().unwrap();
+        assert_eq!(indices[index_count], kept.indices.as_deref().unwrap()[0] + vertex_count as u32);
+
+        merged.drop_cpu_data();
+        assert_eq!(merged.vertex_count(), Err(GeometryError::MissingCpuData));
+    }
+
+    #[test]
+    fn updates_patch_cpu_copy_in_place() {
+        let context = Context::noop();
+        let layout = VertexLayout {
+            array_stride: 4,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Cow::Borrowed(&[]),
+        };
+        let mut vertex_buffer =
+            VertexBuffer::with_cpu_data(context.device(), &[0; 16], layout.clone());
+        let address = vertex_buffer.contents().unwrap().as_ptr();
+
+        vertex_buffer.update(context.queue(), 4, &[1; 4]);
+        assert_eq!(vertex_buffer.contents().unwrap().as_ptr(), address);
+
+        let shared = vertex_buffer.clone();
+        vertex_buffer.update_growable(context.device(), context.queue(), 16, &[2; 8]);
+        assert_eq!(vertex_buffer.capacity(), 32);
+
+        let mut expected = [0; 24];
+        expected[4..8].fill(1);
+        expected[16..].fill(2);
+        assert_eq!(vertex_buffer.contents(), Some(&expected[..]));
+        // The clone keeps the data as it was
+        assert_eq!(shared.contents(), Some(&expected[..16]));
+    }
+
+    #[test]
+    fn instance_buffer_keeps_vertex_slots() {
+        let context = Context::noop();
+        let mut geometry = Geometry::cylinder(context.device(), 1.0, 1.0, 8);
+        let offsets = [[0.0f32; 3]; 4];
+        let attributes = wgpu::vertex_attr_array![3 => Float32x3];
+
+        assert_eq!(
+            geometry.set_instance_buffer(context.device(), 0, &offsets, attributes.to_vec()),
+            Err(GeometryError::VertexSlot(0))
+        );
+        assert_eq!(
+            geometry.set_instance_buffer(context.device(), 2, &offsets, attributes.to_vec()),
+            Err(GeometryError::InvalidSlot(2))
+        );
+
+        geometry.set_instance_buffer(context.device(), 1, &offsets, attributes.to_vec()).unwrap();
+        assert_eq!(geometry.instance_count, Some(4));
+        // A previous instance buffer can be replaced
+        geometry
+            .set_instance_buffer(context.device(), 1, &offsets[..2], attributes.to_vec())
+            .unwrap();
+        assert_eq!(geometry.buffers.len(), 2);
+        assert_eq!(geometry.instance_count, Some(2));
+    }
+
+    #[test]
+    fn heightmap_counts_and_winding() {
+        let context = Context::noop();
+        let (width, height) = (4, 3);
+        let geometry = Geometry::builder(context.device())
+            .keep_cpu_data()
+            .heightmap(&[0.0; 12], width, height, Vec3::new(3.0, 1.0, 2.0))
+            .unwrap();
+
+        assert_eq!(geometry.vertex_count(), Ok(width * height));
+        assert_eq!(geometry.count, (width - 1) * (height - 1) * 6);
+
+        let vertices: Vec<MeshVertex> =
+            bytemuck::pod_collect_to_vec(geometry.buffers[0].contents().unwrap());
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 1.0, 0.0]));
+        // Counter-clockwise seen from above, so the face normals agree with the vertex normals
+        for triangle in geometry.drawn_indices().unwrap().chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].position));
+            assert!((b - a).cross(c - a).normalize().abs_diff_eq(Vec3::Y, 1e-6));
+        }
+    }
+
+    #[test]
+    fn heightmap_rejects_mismatched_sizes() {
+        let context = Context::noop();
+        let heightmap = |heights: &[f32], width, height| {
+            Geometry::builder(context.device()).heightmap(heights, width, height, Vec3::ONE)
+        };
+
+        assert_eq!(heightmap(&[0.0; 4], 1, 4).err(), Some(GeometryError::InvalidHeightmap));
+        assert_eq!(heightmap(&[0.0; 6], 2, 2).err(), Some(GeometryError::InvalidHeightmap));
+        // 65536 * 65536 wraps to 0 in u32
+        assert_eq!(heightmap(&[], 1 << 16, 1 << 16).err(), Some(GeometryError::InvalidHeightmap));
+    }
+
+    #[test]
+    fn transform_vertices_moves_bounds() {
+        let context = Context::noop();
+        let corners: Vec<MeshVertex> = (0..8)
+            .map(|i| MeshVertex {
+                position: [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|bit| bit as f32 - 0.5),
+                normal: [0.0, 1.0, 0.0],
+                uv: [0.0; 2],
+            })
+            .collect();
+        let indices = [0, 1, 3, 0, 3, 2, 4, 6, 7, 4, 7, 5];
+        let mut cube = Geometry::builder(context.device()).keep_cpu_data().interleaved(
+            &corners,
+            &MeshVertex::ATTRIBUTES[..],
+            &indices,
+            None,
+        );
+        assert_eq!(cube.bounds(), Ok((Vec3::splat(-0.5), Vec3::splat(0.5))));
+
+        let offset = Vec3::new(1.0, 2.0, 3.0);
+        cube.transform_vertices(context.queue(), Mat4::from_translation(offset)).unwrap();
+
+        assert_eq!(cube.bounds(), Ok((offset - 0.5, offset + 0.5)));
+    }
+
+    #[test]
+    fn separate_keeps_one_layout_per_slot() {
+        let context = Context::noop();
+        // Positions padded to 16 bytes, so the stride isn't where the attribute ends
+        let positions: [[f32; 4]; 3] = [[0.0; 4], [1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]];
+        let normals: [[f32; 3]; 3] = [[0.0, 0.0, 1.0]; 3];
+        let padded = VertexLayout {
+            array_stride: 16,
+            ..tests::POSITION_LAYOUT.into()
+        };
+        let buffers = [
+            (1, bytemuck::cast_slice(&normals), NORMAL_LAYOUT.into()),
+            (0, bytemuck::cast_slice(&positions), padded.clone()),
+        ];
+
+        let geometry = Geometry::builder(context.device())
+            .keep_cpu_data()
+            .separate(&buffers, &[0, 1, 2])
+            .unwrap();
+
+        assert_eq!(geometry.buffers.len(), 2);
+        assert_eq!(geometry.buffers[0].layout, padded);
+        assert_eq!(geometry.buffers[1].layout, VertexLayout::from(NORMAL_LAYOUT));
+        assert_eq!(geometry.vertex_count(), Ok(3));
+    }
+
+    #[test]
+    fn procedural_counts_match_the_generated_meshes() {
+        let context = Context::noop();
+        let builder = || Geometry::builder(context.device()).keep_cpu_data();
+        for segments in [3, 4, 17] {
+            let rings = segments / 2;
+            let shapes = [
+                (
+                    builder().torus(1.0, 0.25, segments, rings + 3),
+                    torus_counts(segments, rings + 3),
+                ),
+                (builder().cylinder(1.0, 2.0, segments), cylinder_counts(segments)),
+                (builder().cone(1.0, 2.0, segments), cone_counts(segments)),
+                (builder().capsule(0.5, 1.0, segments, rings), capsule_counts(segments, rings)),
+            ];
+
+            for (geometry, (vertex_count, index_count)) in shapes {
+                assert_eq!(geometry.vertex_count(), Ok(vertex_count as u32));
+                assert_eq!(geometry.count as usize, index_count);
+                let indices = geometry.drawn_indices().unwrap();
+                assert!(indices.iter().all(|&index| (index as usize) < vertex_count));
+            }
+        }
+    }
+
+    #[test]
+    fn bin_files_round_trip() {
+        let context = Context::noop();
+        let builder = Geometry::builder(context.device()).keep_cpu_data();
+        // A capsule without the cylinder is a UV sphere
+        let mut sphere = builder.capsule(1.0, 0.0, 16, 8);
+        let path = std::env::temp_dir().join(format!("sphere-{}.bin", std::process::id()));
+
+        sphere.save_bin(&path).unwrap();
+        let loaded = builder.load_bin(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut loaded = loaded.unwrap();
+
+        assert_eq!(loaded.topology, sphere.topology);
+        assert_eq!(loaded.index_format, sphere.index_format);
+        assert_eq!(loaded.count, sphere.count);
+        assert_eq!(loaded.instance_count, sphere.instance_count);
+        assert_eq!(loaded.buffers.len(), 1);
+        assert_eq!(loaded.buffers[0].layout, sphere.buffers[0].layout);
+        assert_eq!(loaded.buffers[0].contents(), sphere.buffers[0].contents());
+        assert_eq!(loaded.drawn_indices(), sphere.drawn_indices());
+        assert_eq!(loaded.bounds(), sphere.bounds());
+    }
+
+    #[test]
+    fn vertex_updates_invalidate_bounds() {
+        let context = Context::noop();
+        let corners = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let mut triangle = Geometry::builder(context.device()).keep_cpu_data().interleaved(
+            &corners,
+            wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            &[0, 1, 2],
+            None,
+        );
+        assert_eq!(triangle.bounds(), Ok((Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0))));
+
+        let moved = [0.0f32, 0.0, 5.0];
+        triangle.buffers[0].update(context.queue(), 24, bytemuck::bytes_of(&moved));
+        assert_eq!(triangle.bounds(), Ok((Vec3::ZERO, Vec3::new(1.0, 0.0, 5.0))));
+
+        // Only the first two vertices
+        triangle.buffers[0].range = Some(0..24);
+        assert_eq!(triangle.bounds(), Ok((Vec3::ZERO, Vec3::X)));
+    }
+
+    #[test]
+    fn separate_rejects_zero_strides() {
+        let context = Context::noop();
+        let positions: [[f32; 3]; 3] = [[0.0; 3]; 3];
+        let unstrided = VertexLayout {
+            array_stride: 0,
+            ..tests::POSITION_LAYOUT.into()
+        };
+
+        let result = Geometry::from_separate(
+            context.device(),
+            &[(0, bytemuck::cast_slice(&positions), unstrided)],
+            &[0, 1, 2],
+        );
+
+        assert_eq!(result.err(), Some(GeometryError::ZeroStride));
+    }
+
+    #[test]
+    fn zero_strides_are_errors_on_the_cpu_side() {
+        let context = Context::noop();
+        let positions: [[f32; 3]; 3] = [[0.0; 3]; 3];
+        let mut geometry = Geometry::builder(context.device()).keep_cpu_data().interleaved(
+            &positions,
+            wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            &[0, 1, 2],
+            None,
+        );
+        geometry.buffers[0].layout.array_stride = 0;
+
+        assert_eq!(geometry.bounds(), Err(GeometryError::ZeroStride));
+        assert_eq!(
+            geometry.transform_vertices(context.queue(), Mat4::IDENTITY),
+            Err(GeometryError::ZeroStride)
+        );
+    }
+
+    #[test]
+    fn transform_vertices_checks_every_buffer_first() {
+        let context = Context::noop();
+        let positions: [[f32; 3]; 3] = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let normals: [[f32; 3]; 3] = [[0.0, 0.0, 1.0]; 3];
+        let buffers = [
+            (0, bytemuck::cast_slice(&positions), VertexLayout::from(tests::POSITION_LAYOUT)),
+            (1, bytemuck::cast_slice(&normals), NORMAL_LAYOUT.into()),
+        ];
+        let mut geometry = Geometry::builder(context.device())
+            .keep_cpu_data()
+            .separate(&buffers, &[0, 1, 2])
+            .unwrap();
+        // Only the normals lost their CPU copy
+        geometry.buffers[1].contents = None;
+
+        let result =
+            geometry.transform_vertices(context.queue(), Mat4::from_scale(Vec3::splat(2.0)));
+
+        assert_eq!(result, Err(GeometryError::MissingCpuData));
+        assert_eq!(geometry.buffers[0].contents(), Some(bytemuck::cast_slice(&positions)));
+    }
+
+    #[test]
+    fn subdivide_quadruples_triangles() {
+        let context = Context::noop();
+        let corners = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let mut triangle = Geometry::builder(context.device()).keep_cpu_data().interleaved(
+            &corners,
+            wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            &[0, 1, 2],
+            None,
+        );
+
+        triangle.subdivide(context.device(), 1).unwrap();
+        assert_eq!(triangle.count, 4 * 3);
+        assert_eq!(triangle.vertex_count(), Ok(3 + 3));
+
+        // Each of the 9 edges of the first level gets one shared midpoint
+        triangle.subdivide(context.device(), 1).unwrap();
+        assert_eq!(triangle.count, 16 * 3);
+        assert_eq!(triangle.vertex_count(), Ok(6 + 9));
+    }
+}
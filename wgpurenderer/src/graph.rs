@@ -0,0 +1,338 @@
+use crate::renderpass::RenderPass;
+use std::fmt::{self, Debug};
+
+/// Index of a pass in its [`RenderGraph`], returned when the pass is added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// Input `input` of the pass is neither written by a pass of the graph nor imported.
+    UnwrittenInput {
+        pass: PassId,
+        name: Option<String>,
+        input: usize,
+    },
+    /// Input `input` of the pass is written by `writer`, which was added after it. Passes only
+    /// see the writes of passes added before them.
+    ReadBeforeWrite {
+        pass: PassId,
+        name: Option<String>,
+        input: usize,
+        writer: PassId,
+    },
+    /// The passes depend on each other in a cycle, e.g. two passes each reading what the other
+    /// writes.
+    Cycle,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnwrittenInput {
+                pass,
+                name,
+                input,
+            } => {
+                let name = name.as_deref().unwrap_or("unnamed");
+                write!(
+                    f,
+                    "input {input} of pass {} ({name}) isn't written by any pass or imported",
+                    pass.0
+                )
+            }
+            Self::ReadBeforeWrite {
+                pass,
+                name,
+                input,
+                writer,
+            } => {
+                let name = name.as_deref().unwrap_or("unnamed");
+                write!(
+                    f,
+                    "input {input} of pass {} ({name}) is written by the later pass {}",
+                    pass.0, writer.0
+                )
+            }
+            Self::Cycle => write!(f, "render graph passes depend on each other in a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+pub trait ComputePassExecutor: Debug {
+    fn execute(&mut self, compute_pass: &mut wgpu::ComputePass);
+}
+
+/// Compute work of a [`RenderGraph`], encoded by its executor into a compute pass of its own.
+#[derive(Debug)]
+pub struct ComputePass {
+    pub name: Option<String>,
+    pub executor: Box<dyn ComputePassExecutor>,
+}
+
+impl ComputePass {
+    pub(crate) fn render(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: self.name.as_deref(),
+            timestamp_writes: None,
+        });
+        self.executor.execute(&mut compute_pass);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum GraphPass {
+    Render(Box<RenderPass>),
+    Compute(ComputePass),
+}
+
+#[derive(Debug)]
+struct Node {
+    pass: GraphPass,
+    inputs: Vec<wgpu::Texture>,
+    /// Textures written besides the attachments of a render pass.
+    outputs: Vec<wgpu::Texture>,
+}
+
+impl Node {
+    fn name(&self) -> Option<&str> {
+        match &self.pass {
+            GraphPass::Render(pass) => pass.name.as_deref(),
+            GraphPass::Compute(pass) => pass.name.as_deref(),
+        }
+    }
+
+    /// Declared outputs plus, for render passes, every attachment texture.
+    fn written_textures(&self) -> Vec<wgpu::Texture> {
+        let mut textures = self.outputs.clone();
+        if let GraphPass::Render(pass) = &self.pass {
+            let target = &pass.render_target;
+            for attachment in &target.color_attachments {
+                textures.push(attachment.view.texture().clone());
+                textures.extend(attachment.resolve_target.as_ref().map(|v| v.texture().clone()));
+            }
+            textures
+                .extend(target.depth_stencil_attachment.as_ref().map(|a| a.view.texture().clone()));
+        }
+        textures
+    }
+}
+
+/// Passes that read each other's textures, run by [`crate::Renderer::render_graph`] in
+/// dependency order into one encoder, e.g. shadow → G-buffer → lighting → post.
+///
+/// A render pass writes the textures of its attachments, a compute pass the outputs declared
+/// when adding it. A pass reading a texture runs after every pass writing it and has to be added
+/// after them, passes writing the same texture run in the order they were added. Otherwise
+/// passes keep the order they were added in. Textures written outside the graph, e.g. loaded
+/// from disk or by the previous frame, have to be [imported](Self::import). A texture can't be
+/// written again after it's read within one graph, [`RenderGraph::execution_order`] reports
+/// [`GraphError::ReadBeforeWrite`]; ping-pong between two textures instead.
+///
+/// Barriers are inserted by wgpu, the graph only orders and validates.
+#[derive(Debug, Default)]
+pub struct RenderGraph {
+    nodes: Vec<Node>,
+    imported: Vec<wgpu::Texture>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `texture` as written before the graph runs, so passes may read it.
+    pub fn import(&mut self, texture: &wgpu::Texture) {
+        self.imported.push(texture.clone());
+    }
+
+    /// Adds a pass reading `inputs`, e.g. the textures bound by its draw calls.
+    pub fn add_render_pass(&mut self, pass: RenderPass, inputs: &[&wgpu::Texture]) -> PassId {
+        self.add_node(GraphPass::Render(Box::new(pass)), inputs, &[])
+    }
+
+    pub fn add_compute_pass(
+        &mut self,
+        pass: ComputePass,
+        inputs: &[&wgpu::Texture],
+        outputs: &[&wgpu::Texture],
+    ) -> PassId {
+        self.add_node(GraphPass::Compute(pass), inputs, outputs)
+    }
+
+    /// The pass added as `id`, e.g. to update its draw calls or render target for the next
+    /// frame. `None` for compute passes.
+    pub fn render_pass_mut(&mut self, id: PassId) -> Option<&mut RenderPass> {
+        match &mut self.nodes.get_mut(id.0)?.pass {
+            GraphPass::Render(pass) => Some(&mut **pass),
+            GraphPass::Compute(_) => None,
+        }
+    }
+
+    pub fn compute_pass_mut(&mut self, id: PassId) -> Option<&mut ComputePass> {
+        match &mut self.nodes.get_mut(id.0)?.pass {
+            GraphPass::Compute(pass) => Some(pass),
+            GraphPass::Render(_) => None,
+        }
+    }
+
+    /// Order the passes run in. Recomputed on every call since render targets may have been
+    /// replaced since.
+    pub fn execution_order(&self) -> Result<Vec<PassId>, GraphError> {
+        // Graphs have a handful of textures, a linear search beats hashing
+        let mut writers: Vec<(wgpu::Texture, Vec<usize>)> = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for texture in node.written_textures() {
+                match writers.iter_mut().find(|(written, _)| *written == texture) {
+                    Some((_, texture_writers)) => {
+                        // A pass writing one texture through several attachments
+                        if texture_writers.last() != Some(&index) {
+                            texture_writers.push(index);
+                        }
+                    }
+                    None => writers.push((texture, vec![index])),
+                }
+            }
+        }
+
+        let mut dependencies = vec![vec![]; self.nodes.len()];
+        for (_, texture_writers) in &writers {
+            for pair in texture_writers.windows(2) {
+                dependencies[pair[1]].push(pair[0]);
+            }
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            for (input_index, input) in node.inputs.iter().enumerate() {
+                match writers.iter().find(|(written, _)| written == input) {
+                    Some((_, texture_writers)) => {
+                        if let Some(&writer) = texture_writers.iter().find(|&&w| w > index) {
+                            return Err(GraphError::ReadBeforeWrite {
+                                pass: PassId(index),
+                                name: node.name().map(str::to_owned),
+                                input: input_index,
+                                writer: PassId(writer),
+                            });
+                        }
+                        dependencies[index]
+                            .extend(texture_writers.iter().copied().filter(|&w| w != index))
+                    }
+                    None if self.imported.contains(input) => {}
+                    None => {
+                        return Err(GraphError::UnwrittenInput {
+                            pass: PassId(index),
+                            name: node.name().map(str::to_owned),
+                            input: input_index,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Always takes the first ready pass to keep the order passes were added in
+        let mut done = vec![false; self.nodes.len()];
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while order.len() < self.nodes.len() {
+            let next = (0..self.nodes.len())
+                .find(|&index| !done[index] && dependencies[index].iter().all(|&d| done[d]))
+                .ok_or(GraphError::Cycle)?;
+            done[next] = true;
+            order.push(PassId(next));
+        }
+        Ok(order)
+    }
+
+    /// The passes listed in `order`, e.g. from [`Self::execution_order`], each at most once.
+    pub(crate) fn passes_mut(&mut self, order: &[PassId]) -> Vec<&mut GraphPass> {
+        let mut passes: Vec<_> = self.nodes.iter_mut().map(|node| Some(&mut node.pass)).collect();
+        order.iter().filter_map(|id| passes.get_mut(id.0)?.take()).collect()
+    }
+
+    fn add_node(
+        &mut self,
+        pass: GraphPass,
+        inputs: &[&wgpu::Texture],
+        outputs: &[&wgpu::Texture],
+    ) -> PassId {
+        self.nodes.push(Node {
+            pass,
+            inputs: inputs.iter().map(|&texture| texture.clone()).collect(),
+            outputs: outputs.iter().map(|&texture| texture.clone()).collect(),
+        });
+        PassId(self.nodes.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Renderer};
+
+    #[derive(Debug)]
+    struct NoWork;
+
+    impl ComputePassExecutor for NoWork {
+        fn execute(&mut self, _compute_pass: &mut wgpu::ComputePass) {}
+    }
+
+    fn compute(name: &str) -> ComputePass {
+        ComputePass {
+            name: Some(name.to_string()),
+            executor: Box::new(NoWork),
+        }
+    }
+
+    fn texture(device: &wgpu::Device) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    #[test]
+    fn readers_run_after_writers() {
+        let context = Context::noop();
+        let (first, second) = (texture(context.device()), texture(context.device()));
+        let mut graph = RenderGraph::new();
+        graph.import(&first);
+
+        let blur = graph.add_compute_pass(compute("blur"), &[&first], &[&second]);
+        let tonemap = graph.add_compute_pass(compute("tonemap"), &[&second], &[]);
+
+        assert_eq!(graph.execution_order(), Ok(vec![blur, tonemap]));
+        let mut renderer = Renderer::with_context(context);
+        assert_eq!(renderer.render_graph(&mut graph), Ok(()));
+    }
+
+    #[test]
+    fn reads_before_a_later_write_are_rejected() {
+        let context = Context::noop();
+        let history = texture(context.device());
+        let mut graph = RenderGraph::new();
+        graph.import(&history);
+
+        let resolve = graph.add_compute_pass(compute("resolve"), &[&history], &[]);
+        let store = graph.add_compute_pass(compute("store"), &[], &[&history]);
+
+        assert_eq!(
+            graph.execution_order(),
+            Err(GraphError::ReadBeforeWrite {
+                pass: resolve,
+                name: Some("resolve".to_string()),
+                input: 0,
+                writer: store,
+            })
+        );
+    }
+}
@@ -1,118 +1,107 @@
+pub mod binding;
 pub mod camera;
+pub mod context;
+pub mod controllers;
+pub mod frame;
+pub mod geometry;
+pub mod graph;
+pub mod pipeline;
+pub mod pool;
+pub mod readback;
 pub mod renderpass;
+pub mod rendertarget;
+pub mod sampler;
+pub mod scene;
+pub mod shader;
+pub mod ssbo;
+pub mod surface;
+pub mod texture;
 pub mod transform;
 pub mod utils;
 
 use crate::{renderpass::RenderPass, utils::TypeId};
-pub use camera::Camera;
+pub use binding::{BindGroupCache, Binding, BufferBinding};
+pub use camera::{Camera, CameraShake, Perspective};
+pub use context::{Capabilities, Context, ContextBuilder, ContextError};
+pub use controllers::{FirstPersonController, FirstPersonInput, OrbitController, OrbitInput};
+pub use frame::{FrameClock, FrameGlobals};
+pub use geometry::{Geometry, GeometryBuilder};
+pub use graph::{ComputePass, ComputePassExecutor, GraphError, PassId, RenderGraph};
 use nohash_hasher::IntMap;
+pub use pipeline::{BlendMode, PipelineCache, PipelineError, PipelineVariants};
+pub use pool::{Handle, ResourcePool};
+pub use rendertarget::{
+    ColorAttachment, ColorAttachmentBuilder, DepthStencilAttachment, DepthStencilAttachmentBuilder,
+    RenderTarget, RenderTargetBuilder,
+};
 use rustc_hash::FxHashMap;
+pub use sampler::{Sampler, SamplerBuilder};
+pub use scene::{Node, Scene};
+pub use shader::ShaderSource;
 use smallvec::SmallVec;
 use sorted_vec::SortedVec;
 use std::{
-    borrow::Cow,
-    cell::RefCell,
     fmt::Debug,
     hash::{Hash, Hasher},
     num::NonZeroU32,
     ops::Range,
-    rc::Rc,
 };
-use transform::Transform;
+pub use surface::{SurfaceFrame, SurfaceState};
+pub use texture::Texture;
 pub use wgpurenderer_macros::immediate;
 
-pub struct Scene {
-    nodes: Vec<Node>,
-}
-
-pub struct Node {
-    transform: Transform,
-}
-
-#[derive(Debug, Clone)]
-pub struct Context {
-    instance: wgpu::Instance,
-    adapter: wgpu::Adapter,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-}
-
-impl Context {
-    pub fn new() -> Self {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
-            ..Default::default()
-        });
-
-        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: None,
-            force_fallback_adapter: false,
-        });
-        let adapter = pollster::block_on(adapter).unwrap();
-
-        let device_queue = adapter.request_device(&wgpu::DeviceDescriptor {
-            label: None,
-            required_features: wgpu::Features::IMMEDIATES,
-            required_limits: wgpu::Limits {
-                max_immediate_size: 128,
-                ..Default::default()
-            },
-            experimental_features: wgpu::ExperimentalFeatures::disabled(),
-            memory_hints: wgpu::MemoryHints::Performance,
-            trace: wgpu::Trace::Off,
-        });
-        let (device, queue) = pollster::block_on(device_queue).unwrap();
-
-        Self {
-            instance,
-            adapter,
-            device,
-            queue,
-        }
-    }
-
-    pub fn instance(&self) -> &wgpu::Instance {
-        &self.instance
-    }
-
-    pub fn adapter(&self) -> &wgpu::Adapter {
-        &self.adapter
-    }
-
-    pub fn device(&self) -> &wgpu::Device {
-        &self.device
-    }
-
-    pub fn queue(&self) -> &wgpu::Queue {
-        &self.queue
-    }
-}
-
 #[derive(Debug)]
 pub struct Renderer {
     context: Context,
-    shader_cache: FxHashMap<Cow<'static, str>, wgpu::ShaderModule>,
+    shader_cache: FxHashMap<shader::ShaderSource, wgpu::ShaderModule>,
     bind_group_layout_cache: Vec<(Vec<wgpu::BindGroupLayoutEntry>, wgpu::BindGroupLayout)>,
-    immeadiate_manager: Rc<RefCell<ImmediateManager>>,
-    pub surface: Option<wgpu::Surface<'static>>,
-    pub config: Option<wgpu::SurfaceConfiguration>,
-    pub surface_texture: Option<wgpu::SurfaceTexture>,
-    pub surface_view: Option<wgpu::TextureView>,
-    // TODO: determine duplicating render pipelines and return the existing one
+    immeadiate_manager: utils::Shared<ImmediateManager>,
+    /// Immediates dropped while the manager was borrowed, removed at the start of the next frame.
+    released_immediates: utils::Shared<Vec<utils::InstanceId>>,
+    render_pipelines: ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: ResourcePool<wgpu::BindGroup>,
+    geometries: ResourcePool<Geometry>,
+    ssbo_pool: ssbo::SsboPool,
+    pipeline_cache: PipelineCache,
+    bind_group_cache: BindGroupCache,
+    frame_globals: frame::FrameGlobalsState,
+    render_stats: renderpass::RenderStats,
+    /// Created by the first [`Renderer::generate_mipmaps`].
+    mipmap_generator: Option<texture::MipmapGenerator>,
 }
 
+// With `multithread` the renderer and the handles it gives out can move between threads
+#[cfg(feature = "multithread")]
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Renderer>();
+    assert_send_sync::<Immediate>();
+    assert_send_sync::<ssbo::ObjectData>();
+};
+
 impl Renderer {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, ContextError> {
+        Ok(Self::with_context(Context::new()?))
+    }
+
+    /// Renderer on an existing context, e.g. one built with [`ContextBuilder`] for a specific
+    /// adapter.
+    pub fn with_context(context: Context) -> Self {
         Self {
-            context: Context::new(),
+            frame_globals: frame::FrameGlobalsState::new(context.device()),
+            render_stats: renderpass::RenderStats::default(),
+            context,
             shader_cache: FxHashMap::default(),
             bind_group_layout_cache: Vec::new(),
-            immeadiate_manager: Rc::new(RefCell::new(ImmediateManager::new())),
-            surface: None,
-            config: None,
-            surface_texture: None,
-            surface_view: None,
+            immeadiate_manager: utils::Shared::new(ImmediateManager::new()),
+            released_immediates: utils::Shared::default(),
+            render_pipelines: ResourcePool::new(),
+            bind_groups: ResourcePool::new(),
+            geometries: ResourcePool::new(),
+            ssbo_pool: ssbo::SsboPool::new(),
+            pipeline_cache: PipelineCache::new(),
+            bind_group_cache: BindGroupCache::new(),
+            mipmap_generator: None,
         }
     }
 
@@ -120,68 +109,18 @@ impl Renderer {
         &self.context
     }
 
-    pub fn init_surface(&mut self, surface: wgpu::Surface<'static>, width: u32, height: u32) {
-        let surface_caps = surface.get_capabilities(self.context.adapter());
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width,
-            height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(self.context.device(), &config);
-
-        self.surface = Some(surface);
-        self.config = Some(config);
-    }
-
-    pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            if let (Some(surface), Some(config)) = (&mut self.surface, &mut self.config) {
-                config.width = width;
-                config.height = height;
-                surface.configure(self.context.device(), config);
-            }
-        }
-    }
-
-    pub fn surface_format(&self) -> wgpu::TextureFormat {
-        self.config.as_ref().map(|c| c.format).unwrap_or(wgpu::TextureFormat::Bgra8Unorm)
-    }
-
-    pub fn acquire(&mut self) -> Result<(), wgpu::SurfaceError> {
-        if let Some(surface) = &self.surface {
-            let output = surface.get_current_texture()?;
-            let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-            self.surface_texture = Some(output);
-            self.surface_view = Some(view);
+    /// Returns the existing pipeline when an identical material was already created, so
+    /// removing it affects every material that shares it.
+    pub fn create_render_pipeline(&mut self, material: &Material) -> Handle<wgpu::RenderPipeline> {
+        if let Some(handle) = self.pipeline_cache.get(material, &self.render_pipelines) {
+            return handle;
         }
-        Ok(())
-    }
-
-    pub fn present(&mut self) {
-        self.surface_view = None;
-        if let Some(texture) = self.surface_texture.take() {
-            texture.present();
-        }
-    }
 
-    pub fn create_render_pipeline(&mut self, material: &Material) -> wgpu::RenderPipeline {
         for item in &material.bind_groups {
             let found = self.bind_group_layout_cache.iter().find(|(entries, _)| *entries == *item);
 
             if found.is_none() {
-                let bind_group_layout = self.context.device.create_bind_group_layout(
+                let bind_group_layout = self.context.device().create_bind_group_layout(
                     &wgpu::BindGroupLayoutDescriptor {
                         label: None,
                         entries: item.as_slice(),
@@ -199,18 +138,17 @@ impl Renderer {
             .collect();
 
         // TODO: add caching
-        let layout = self.context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &bind_group_layouts,
-            immediate_size: 128,
-        });
-
-        let shader_module = self.shader_cache.entry(material.source.clone()).or_insert_with(|| {
-            self.context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        let layout =
+            self.context.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                source: wgpu::ShaderSource::Wgsl(material.source.clone()),
-            })
-        });
+                bind_group_layouts: &bind_group_layouts,
+                immediate_size: material.immediate_size,
+            });
+
+        let shader_module = self
+            .shader_cache
+            .entry(material.source.clone())
+            .or_insert_with(|| material.source.create_module(self.context.device()));
 
         let vertex = wgpu::VertexState {
             module: shader_module,
@@ -227,7 +165,7 @@ impl Renderer {
         });
 
         let desc = wgpu::RenderPipelineDescriptor {
-            label: None,
+            label: material.label.as_deref(),
             layout: Some(&layout),
             vertex,
             primitive: material.primitive,
@@ -235,85 +173,684 @@ impl Renderer {
             multisample: material.multisample,
             fragment,
             multiview_mask: None,
-            cache: None,
+            cache: self.pipeline_cache.driver_cache(),
+        };
+
+        let render_pipeline = self.context.device().create_render_pipeline(&desc);
+        let handle = self.render_pipelines.insert(render_pipeline);
+        self.pipeline_cache.insert(material.clone(), handle);
+        self.immeadiate_manager.borrow_mut().declare_pipeline(
+            handle,
+            material.immediate_size,
+            material.label.clone(),
+        );
+        handle
+    }
+
+    /// [`Self::create_render_pipeline`] after checking that the material's fragment targets
+    /// match the color attachments of `render_target` one to one, in `@location` order, and its
+    /// depth-stencil format the depth attachment, see [`Material::validate_targets`].
+    pub fn create_render_pipeline_for(
+        &mut self,
+        material: &Material,
+        render_target: &RenderTarget,
+    ) -> Result<Handle<wgpu::RenderPipeline>, PipelineError> {
+        material.validate_targets(render_target)?;
+        Ok(self.create_render_pipeline(material))
+    }
+
+    /// Enables the driver's pipeline cache when the device supports it (Vulkan only at the
+    /// moment). Returns whether it's enabled. Use [`Self::pipeline_cache_key`] to tell data of
+    /// different adapters apart.
+    ///
+    /// # Safety
+    ///
+    /// `data` must have been returned by [`Self::pipeline_cache_data`].
+    pub unsafe fn init_pipeline_cache(&mut self, data: Option<&[u8]>) -> bool {
+        let device = self.context.device();
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return false;
+        }
+
+        // SAFETY: upheld by the caller
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data,
+                fallback: true,
+            })
         };
+        self.pipeline_cache.set_driver_cache(cache);
+        true
+    }
+
+    /// Serialized driver pipeline cache, to be saved and passed to
+    /// [`Self::init_pipeline_cache`] on the next run.
+    pub fn pipeline_cache_data(&self) -> Option<Vec<u8>> {
+        self.pipeline_cache.driver_cache()?.get_data()
+    }
 
-        let render_pipeline = self.context.device.create_render_pipeline(&desc);
-        render_pipeline
+    pub fn pipeline_cache_key(&self) -> Option<String> {
+        wgpu::util::pipeline_cache_key(&self.context.adapter().get_info())
+    }
+
+    pub fn render_pipeline(
+        &self,
+        handle: Handle<wgpu::RenderPipeline>,
+    ) -> Option<&wgpu::RenderPipeline> {
+        self.render_pipelines.get(handle)
+    }
+
+    pub fn remove_render_pipeline(
+        &mut self,
+        handle: Handle<wgpu::RenderPipeline>,
+    ) -> Option<wgpu::RenderPipeline> {
+        self.immeadiate_manager.borrow_mut().forget_pipeline(handle);
+        self.render_pipelines.remove(handle)
     }
 
     pub fn create_bindgroup(
         &mut self,
         layout_entries: &[wgpu::BindGroupLayoutEntry],
         entries: &[wgpu::BindGroupEntry],
-    ) -> wgpu::BindGroup {
+    ) -> Handle<wgpu::BindGroup> {
+        let layout_index = self.bind_group_layout_index(layout_entries);
+        let bind_group = self.context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout_cache[layout_index].1,
+            entries,
+        });
+
+        self.bind_groups.insert(bind_group)
+    }
+
+    /// Creates a bind group with `bindings` at binding indices `0..`, or returns the existing
+    /// one when the same tracked resources were bound with the same layout before. Cached bind
+    /// groups are removed once one of their resources is dropped.
+    pub fn create_cached_bindgroup(
+        &mut self,
+        visibility: wgpu::ShaderStages,
+        bindings: &[&dyn Binding],
+    ) -> Handle<wgpu::BindGroup> {
+        let layout_entries: Vec<_> = bindings
+            .iter()
+            .zip(0..)
+            .map(|(binding, index)| binding.layout_entry(index, visibility))
+            .collect();
+        let layout_index = self.bind_group_layout_index(&layout_entries);
+
+        if let Some(handle) = self.bind_group_cache.get(layout_index, bindings, &self.bind_groups) {
+            return handle;
+        }
+
+        let entries: Vec<_> =
+            bindings.iter().zip(0..).map(|(binding, index)| binding.entry(index)).collect();
+        let bind_group = self.context.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout_cache[layout_index].1,
+            entries: &entries,
+        });
+
+        let handle = self.bind_groups.insert(bind_group);
+        self.bind_group_cache.insert(layout_index, bindings, handle);
+        handle
+    }
+
+    /// Index of the cached layout for `layout_entries`, stable for the renderer's lifetime.
+    fn bind_group_layout_index(&mut self, layout_entries: &[wgpu::BindGroupLayoutEntry]) -> usize {
         let found = self
             .bind_group_layout_cache
             .iter()
             .position(|(cached_entries, _)| cached_entries.as_slice() == layout_entries);
 
-        let layout = if let Some(index) = found {
-            &self.bind_group_layout_cache[index].1
+        if let Some(index) = found {
+            index
         } else {
             let bind_group_layout =
-                self.context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                self.context.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: None,
                     entries: layout_entries,
                 });
             self.bind_group_layout_cache.push((layout_entries.to_vec(), bind_group_layout));
-            &self.bind_group_layout_cache.last().unwrap().1
-        };
+            self.bind_group_layout_cache.len() - 1
+        }
+    }
 
-        let bind_group = self.context.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout,
-            entries,
-        });
+    pub fn bind_group(&self, handle: Handle<wgpu::BindGroup>) -> Option<&wgpu::BindGroup> {
+        self.bind_groups.get(handle)
+    }
+
+    pub fn remove_bind_group(
+        &mut self,
+        handle: Handle<wgpu::BindGroup>,
+    ) -> Option<wgpu::BindGroup> {
+        self.bind_groups.remove(handle)
+    }
+
+    pub fn add_geometry(&mut self, geometry: Geometry) -> Handle<Geometry> {
+        self.geometries.insert(geometry)
+    }
+
+    pub fn geometry(&self, handle: Handle<Geometry>) -> Option<&Geometry> {
+        self.geometries.get(handle)
+    }
+
+    pub fn geometry_mut(&mut self, handle: Handle<Geometry>) -> Option<&mut Geometry> {
+        self.geometries.get_mut(handle)
+    }
 
-        bind_group
+    pub fn remove_geometry(&mut self, handle: Handle<Geometry>) -> Option<Geometry> {
+        self.geometries.remove(handle)
     }
 
+    /// # Panics
+    ///
+    /// When called while the renderer is encoding, e.g. from a [`renderpass::RenderPassExecutor`].
+    /// With the `multithread` feature this blocks until encoding is done instead, so it deadlocks
+    /// when called from the encoding thread.
     pub fn create_immediate(&self, size: usize) -> Immediate {
-        let id = self.immeadiate_manager.borrow_mut().allocate(size);
+        let mut manager = self
+            .immeadiate_manager
+            .borrow_mut_or_panic("Immediate created while the renderer is encoding a frame");
+        let id = manager.allocate(size);
 
         Immediate {
             id,
             counter: utils::InstanceCounter::new(),
             manager: self.immeadiate_manager.clone(),
+            released: self.released_immediates.clone(),
+        }
+    }
+
+    /// [`texture::Texture::generate_mipmaps`] with the renderer's generator, so the mipmap
+    /// pipeline is only built once per texture format.
+    pub fn generate_mipmaps(
+        &mut self,
+        texture: &texture::Texture,
+    ) -> Result<(), texture::TextureError> {
+        let device = self.context.device();
+        let generator =
+            self.mipmap_generator.get_or_insert_with(|| texture::MipmapGenerator::new(device));
+        texture.generate_mipmaps(device, self.context.queue(), generator)
+    }
+
+    /// Per-object SSBO data, uploaded to the GPU at the start of every [`Renderer::render`].
+    pub fn create_object_data(&self) -> ssbo::ObjectData {
+        self.ssbo_pool.create_object()
+    }
+
+    pub fn ssbo_buffer<T: bytemuck::NoUninit>(&self) -> Option<wgpu::Buffer> {
+        self.ssbo_pool.buffer::<T>()
+    }
+
+    /// Pre-sizes the SSBO of type `T` for `count` objects, avoiding repeated reallocations
+    /// when many objects are registered at once.
+    pub fn reserve_ssbo<T: bytemuck::NoUninit>(&self, count: usize) {
+        self.ssbo_pool.reserve::<T>(count);
+    }
+
+    pub fn ssbo_entry_count<T: bytemuck::NoUninit>(&self) -> usize {
+        self.ssbo_pool.entry_count::<T>()
+    }
+
+    /// Whether the object `id` (see [`ssbo::ObjectData::id`]) has data of type `T`.
+    pub fn ssbo_contains<T: bytemuck::NoUninit>(&self, id: utils::InstanceId) -> bool {
+        self.ssbo_pool.contains::<T>(id)
+    }
+
+    /// Index of the object `id` in the SSBO of type `T`, for `instances[instance_index]` in
+    /// [`shader::modules::storage::INSTANCES`].
+    pub fn ssbo_index<T: bytemuck::NoUninit>(&self, id: utils::InstanceId) -> Option<u32> {
+        self.ssbo_pool.index_of::<T>(id)
+    }
+
+    /// Ids of every object with data of type `T`, sorted.
+    pub fn ssbo_ids<T: bytemuck::NoUninit>(&self) -> Vec<utils::InstanceId> {
+        self.ssbo_pool.ids::<T>()
+    }
+
+    /// Values of the [`FrameGlobals`] uploaded for the last rendered frame.
+    pub fn frame_globals(&self) -> FrameGlobals {
+        self.frame_globals.globals()
+    }
+
+    /// Draw calls encoded and skipped, pipeline and bind group switches summed over the passes
+    /// of the last [`Self::render`], [`Self::render_parallel`] or [`Self::render_graph`].
+    pub fn render_stats(&self) -> renderpass::RenderStats {
+        self.render_stats
+    }
+
+    /// Uniform buffer holding [`FrameGlobals`], updated at the start of every
+    /// [`Self::render`] and [`Self::render_parallel`]. Bind it, e.g. through
+    /// [`BufferBinding::uniform`], where the shader declares
+    /// [`shader::modules::uniforms::FRAME_GLOBALS`], conventionally `@group(0) @binding(1)`.
+    pub fn frame_globals_buffer(&self) -> &wgpu::Buffer {
+        self.frame_globals.buffer()
+    }
+
+    /// Drops bind groups over released resources and packs the immediate ranges of dropped
+    /// [`Immediate`]s together, so their space is reused by the next allocations. Advances the
+    /// frame globals, taking the resolution from the last pass with attachments.
+    fn prepare_frame<'a>(
+        &mut self,
+        render_passes: impl DoubleEndedIterator<Item = &'a RenderPass>,
+    ) {
+        let resolution = render_passes.rev().find_map(|pass| pass.render_target.size());
+        self.frame_globals.advance(self.context.queue(), resolution);
+        self.bind_group_cache.evict_dropped(&mut self.bind_groups);
+
+        self.render_stats = renderpass::RenderStats::default();
+
+        let mut manager = self.immeadiate_manager.borrow_mut();
+        for id in self.released_immediates.borrow_mut().drain(..) {
+            manager.remove(id);
         }
+        manager.defragment();
+    }
+
+    /// Flushes pending SSBO data, then records all passes into one encoder and submits it.
+    pub fn render(&mut self, render_passes: &mut [&mut RenderPass]) {
+        self.render_frame(render_passes.iter_mut().map(|pass| FramePass::Render(pass)).collect());
+    }
+
+    /// [`Self::render`] for the passes of `graph`, in [`RenderGraph::execution_order`]. Nothing
+    /// is rendered when the graph is invalid.
+    pub fn render_graph(&mut self, graph: &mut RenderGraph) -> Result<(), GraphError> {
+        let order = graph.execution_order()?;
+        let passes = graph
+            .passes_mut(&order)
+            .into_iter()
+            .map(|pass| match pass {
+                graph::GraphPass::Render(pass) => FramePass::Render(pass),
+                graph::GraphPass::Compute(pass) => FramePass::Compute(pass),
+            })
+            .collect();
+        self.render_frame(passes);
+        Ok(())
     }
 
-    pub fn render(&self, render_passes: &mut [&mut RenderPass]) {
+    /// Flushes pending SSBO data, then records `passes` in order into one encoder and submits
+    /// it.
+    fn render_frame(&mut self, mut passes: Vec<FramePass<'_>>) {
+        self.prepare_frame(passes.iter().filter_map(|pass| match pass {
+            FramePass::Render(pass) => Some(&**pass),
+            FramePass::Compute(_) => None,
+        }));
+
         let mut encoder =
             self.context.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: None,
             });
+        self.ssbo_pool.flush(&mut encoder, self.context.device());
         let immediate_manager = self.immeadiate_manager.borrow();
-        for render_pass in render_passes.iter_mut() {
-            render_pass.render(&mut encoder, &immediate_manager);
+        for pass in &mut passes {
+            match pass {
+                FramePass::Render(pass) => {
+                    self.render_stats += pass.render(
+                        &mut encoder,
+                        &immediate_manager,
+                        &self.render_pipelines,
+                        &self.bind_groups,
+                    )
+                }
+                FramePass::Compute(pass) => pass.render(&mut encoder),
+            }
         }
+        drop(immediate_manager);
 
         self.context.queue().submit(Some(encoder.finish()));
+        self.ssbo_pool.recall();
+    }
+
+    /// Same result as [`Self::render`], with the passes encoded on several threads.
+    ///
+    /// Every pass is first resolved on the calling thread into an owned command list holding
+    /// clones of the wgpu objects it uses, since the resource pools and managers aren't `Sync`.
+    /// The lists are then split into contiguous runs of passes, one per available core, and
+    /// each run is encoded into its own `CommandEncoder`. Passes with an executor are encoded
+    /// on the calling thread.
+    ///
+    /// All command buffers go into a single `queue.submit` in pass order, after the SSBO
+    /// uploads. Submission order is execution order, so every pass sees the results of the
+    /// passes before it exactly as with one encoder. Only the CPU-side recording runs in
+    /// parallel, which pays off for passes with many draw calls.
+    pub fn render_parallel(&mut self, render_passes: &mut [&mut RenderPass]) {
+        enum Job {
+            Recorded(Box<renderpass::RecordedPass>),
+            Encoded(wgpu::CommandBuffer),
+        }
+
+        self.prepare_frame(render_passes.iter().map(|pass| &**pass));
+
+        let device = self.context.device();
+        let mut upload_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: None,
+        });
+        self.ssbo_pool.flush(&mut upload_encoder, device);
+
+        let immediate_manager = self.immeadiate_manager.borrow();
+        let jobs: Vec<_> = render_passes
+            .iter_mut()
+            .map(|render_pass| {
+                match render_pass.record(
+                    &immediate_manager,
+                    &self.render_pipelines,
+                    &self.bind_groups,
+                ) {
+                    Some((pass, stats)) => {
+                        self.render_stats += stats;
+                        Job::Recorded(Box::new(pass))
+                    }
+                    None => {
+                        let mut encoder =
+                            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                label: None,
+                            });
+                        self.render_stats += render_pass.render(
+                            &mut encoder,
+                            &immediate_manager,
+                            &self.render_pipelines,
+                            &self.bind_groups,
+                        );
+                        Job::Encoded(encoder.finish())
+                    }
+                }
+            })
+            .collect();
+        drop(immediate_manager);
+
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = jobs.len().div_ceil(threads).max(1);
+        let mut jobs = jobs.into_iter().peekable();
+        let mut chunks = vec![];
+        while jobs.peek().is_some() {
+            chunks.push(jobs.by_ref().take(chunk_size).collect::<Vec<_>>());
+        }
+
+        let command_buffers: Vec<Vec<wgpu::CommandBuffer>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut command_buffers = vec![];
+                        let mut encoder = None;
+                        for job in chunk {
+                            match job {
+                                Job::Recorded(pass) => {
+                                    pass.encode(encoder.get_or_insert_with(|| {
+                                        device.create_command_encoder(
+                                            &wgpu::CommandEncoderDescriptor {
+                                                label: None,
+                                            },
+                                        )
+                                    }))
+                                }
+                                Job::Encoded(command_buffer) => {
+                                    command_buffers.extend(encoder.take().map(|e| e.finish()));
+                                    command_buffers.push(command_buffer);
+                                }
+                            }
+                        }
+                        command_buffers.extend(encoder.map(|e| e.finish()));
+                        command_buffers
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        });
+
+        self.context.queue().submit(
+            std::iter::once(upload_encoder.finish()).chain(command_buffers.into_iter().flatten()),
+        );
+        self.ssbo_pool.recall();
+    }
+
+    /// Records the static draw calls of `render_pass` into a render bundle that it replays
+    /// until they change.
+    pub fn cache_bundle(&self, render_pass: &mut RenderPass) {
+        render_pass.cache_bundle(
+            self.context.device(),
+            &self.immeadiate_manager.borrow(),
+            &self.render_pipelines,
+            &self.bind_groups,
+        );
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Pass recorded by [`Renderer::render_frame`].
+enum FramePass<'a> {
+    Render(&'a mut RenderPass),
+    Compute(&'a mut graph::ComputePass),
+}
+
+pub(crate) fn select_present_mode(
+    surface_caps: &wgpu::SurfaceCapabilities,
+    requested: wgpu::PresentMode,
+) -> wgpu::PresentMode {
+    let is_auto =
+        matches!(requested, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+
+    if is_auto || surface_caps.present_modes.contains(&requested) {
+        requested
+    } else {
+        log::warn!("Present mode {requested:?} is not supported by the surface, using Fifo");
+        wgpu::PresentMode::Fifo
+    }
+}
+
+/// Materials compare and hash without their [`Material::label`], so the pipeline cache shares
+/// one pipeline between materials that only differ in name.
+#[derive(Debug, Clone)]
 pub struct Material {
+    /// Label of the created pipeline, shown by GPU debuggers and used for its debug groups with
+    /// the `debug-markers` feature. A pipeline shared through the cache keeps the label of the
+    /// material it was created for.
+    pub label: Option<String>,
     pub bind_groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
     pub vertex: Vertex,
     pub fragment: Option<Fragment>,
     pub depth_stencil: Option<wgpu::DepthStencilState>,
     pub primitive: wgpu::PrimitiveState,
     pub multisample: wgpu::MultisampleState,
-    pub source: Cow<'static, str>,
+    /// Bytes of immediate data available to the shaders, at least the size of the shader's
+    /// `var<immediate>` block and of every immediate bound with it. See
+    /// [`shader::ShaderLayout::immediate_size`].
+    pub immediate_size: u32,
+    pub source: shader::ShaderSource,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl PartialEq for Material {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            label: _,
+            bind_groups,
+            vertex,
+            fragment,
+            depth_stencil,
+            primitive,
+            multisample,
+            immediate_size,
+            source,
+        } = self;
+        *bind_groups == other.bind_groups
+            && *vertex == other.vertex
+            && *fragment == other.fragment
+            && *depth_stencil == other.depth_stencil
+            && *primitive == other.primitive
+            && *multisample == other.multisample
+            && *immediate_size == other.immediate_size
+            && *source == other.source
+    }
+}
+
+impl Eq for Material {}
+
+impl Hash for Material {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            label: _,
+            bind_groups,
+            vertex,
+            fragment,
+            depth_stencil,
+            primitive,
+            multisample,
+            immediate_size,
+            source,
+        } = self;
+        bind_groups.hash(state);
+        vertex.hash(state);
+        fragment.hash(state);
+        depth_stencil.hash(state);
+        primitive.hash(state);
+        multisample.hash(state);
+        immediate_size.hash(state);
+        source.hash(state);
+    }
+}
+
+impl Material {
+    /// Enables stencil testing. Switches the depth format to `Depth24PlusStencil8` when the
+    /// current one has no stencil aspect; without depth-stencil state a `Less` depth test
+    /// with depth writes is added.
+    pub fn with_stencil(mut self, stencil: wgpu::StencilState) -> Self {
+        let depth_stencil = self.depth_stencil.get_or_insert(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24PlusStencil8,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+
+        if !depth_stencil.format.has_stencil_aspect() {
+            log::warn!(
+                "Depth format {:?} has no stencil aspect, using Depth24PlusStencil8",
+                depth_stencil.format
+            );
+            depth_stencil.format = wgpu::TextureFormat::Depth24PlusStencil8;
+        }
+        depth_stencil.stencil = stencil;
+        self
+    }
+
+    /// Turns the alpha written to color target 0 into an MSAA coverage mask, so cutout
+    /// materials like foliage get antialiased edges without sorting. The fragment shader has to
+    /// output meaningful alpha, and the targets usually stay opaque: combining it with alpha
+    /// blending applies the alpha twice. Only allowed with a sample count above 1.
+    pub fn with_alpha_to_coverage(mut self, enabled: bool) -> Result<Self, PipelineError> {
+        if enabled && self.multisample.count <= 1 {
+            return Err(PipelineError::AlphaToCoverageWithoutMsaa);
+        }
+        self.multisample.alpha_to_coverage_enabled = enabled;
+        Ok(self)
+    }
+
+    /// Checks that fragment output `@location(i)` has a target with the format of color
+    /// attachment `i`. A material without a fragment stage matches any color attachments.
+    /// The depth-stencil format has to match the depth attachment, both being absent included.
+    /// Alpha-to-coverage is rejected without multisampling.
+    pub fn validate_targets(&self, render_target: &RenderTarget) -> Result<(), PipelineError> {
+        if self.multisample.alpha_to_coverage_enabled && self.multisample.count <= 1 {
+            return Err(PipelineError::AlphaToCoverageWithoutMsaa);
+        }
+
+        let depth_format = self.depth_stencil.as_ref().map(|depth_stencil| depth_stencil.format);
+        if depth_format != render_target.depth_format() {
+            return Err(PipelineError::DepthFormatMismatch {
+                material: depth_format,
+                render_target: render_target.depth_format(),
+            });
+        }
+
+        let Some(fragment) = &self.fragment else {
+            return Ok(());
+        };
+
+        let formats = render_target.color_formats();
+        if fragment.targets.len() != formats.len() {
+            return Err(PipelineError::TargetCountMismatch {
+                material: fragment.targets.len(),
+                render_target: formats.len(),
+            });
+        }
+
+        for (location, (target, &format)) in fragment.targets.iter().zip(&formats).enumerate() {
+            if let Some(target) = target
+                && target.format != format
+            {
+                return Err(PipelineError::TargetFormatMismatch {
+                    location,
+                    material: target.format,
+                    render_target: format,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that [`Self::immediate_size`] covers the shader's immediate block reflected into
+    /// `layout`. The pipeline layout only declares a size, immediate data is visible to every
+    /// stage regardless of [`shader::ImmediateRange::visibility`].
+    pub fn validate_immediates(&self, layout: &shader::ShaderLayout) -> Result<(), PipelineError> {
+        let shader = layout.immediate_size();
+        if self.immediate_size < shader {
+            return Err(PipelineError::ImmediateSize {
+                material: self.immediate_size,
+                shader,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the material's vertex buffer layouts against the vertex inputs reflected into
+    /// `layout`, see [`shader::ShaderLayout::validate_vertex_format`]. Meant to be called before
+    /// [`Renderer::create_render_pipeline`], where a mismatch would otherwise surface as a wgpu
+    /// validation error or garbage vertices.
+    pub fn validate_vertex_layout(
+        &self,
+        layout: &shader::ShaderLayout,
+    ) -> Result<(), PipelineError> {
+        layout
+            .validate_vertex_attributes(
+                self.vertex.buffers.iter().flat_map(|buffer| buffer.attributes.iter()),
+            )
+            .map_err(PipelineError::VertexLayout)
+    }
+
+    /// Variant for a depth pre-pass: no fragment stage, depth test `Less` with writes.
+    pub fn depth_only(&self) -> Self {
+        let mut material = self.clone();
+        material.fragment = None;
+        if let Some(depth_stencil) = &mut material.depth_stencil {
+            depth_stencil.depth_write_enabled = true;
+            depth_stencil.depth_compare = wgpu::CompareFunction::Less;
+        }
+        material
+    }
+
+    /// Variant for the pass after a depth pre-pass: only fragments matching the stored depth
+    /// are shaded and depth isn't written again.
+    pub fn depth_equal(&self) -> Self {
+        let mut material = self.clone();
+        if let Some(depth_stencil) = &mut material.depth_stencil {
+            depth_stencil.depth_write_enabled = false;
+            depth_stencil.depth_compare = wgpu::CompareFunction::Equal;
+        }
+        material
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Vertex {
     pub buffers: Vec<wgpu::VertexBufferLayout<'static>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Fragment {
     pub targets: Vec<Option<wgpu::ColorTargetState>>,
 }
@@ -342,43 +879,37 @@ pub struct DrawCall {
     pub geometry: Geometry,
     pub shader_data: ShaderData,
     pub instance_count: NonZeroU32,
-    pub render_pipeline_handle: wgpu::RenderPipeline,
+    pub render_pipeline_handle: Handle<wgpu::RenderPipeline>,
+    /// Scissor rectangle as `(x, y, width, height)`, clamped to the render target. `None`
+    /// covers the whole target.
+    pub scissor: Option<(u32, u32, u32, u32)>,
+    /// Reference value for stencil tests, `None` uses 0.
+    pub stencil_reference: Option<u32>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Geometry {
-    pub index_buffer: Option<wgpu::Buffer>,
-    pub index_buffer_range: Option<Range<u32>>,
-    pub index_format: wgpu::IndexFormat,
-    pub buffers: Vec<(wgpu::Buffer, Option<Range<u32>>)>,
-    pub count: u32,
+impl DrawCall {
+    /// Material identity of the draw, ordered like [`renderpass::compare_draw_calls`] sorts.
+    pub fn sort_key(&self) -> MaterialKey {
+        MaterialKey {
+            render_pipeline_handle: self.render_pipeline_handle,
+            bind_groups: self.shader_data.bind_groups.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ShaderData {
     pub immediates: Option<Immediate>,
-    pub bind_groups: SmallVec<[wgpu::BindGroup; 3]>,
-}
-
-#[derive(Debug, Clone)]
-pub struct ColorAttachment {
-    pub view: wgpu::TextureView,
-    pub depth_slice: Option<u32>,
-    pub resolve_target: Option<wgpu::TextureView>,
-    pub ops: wgpu::Operations<wgpu::Color>,
-}
-
-#[derive(Debug, Clone)]
-pub struct DepthStencilAttachment {
-    pub view: wgpu::TextureView,
-    pub depth_ops: Option<wgpu::Operations<f32>>,
-    pub stencil_ops: Option<wgpu::Operations<u32>>,
+    pub bind_groups: SmallVec<[Handle<wgpu::BindGroup>; 3]>,
 }
 
-#[derive(Debug, Clone)]
-pub struct RenderTarget {
-    pub color_attachments: SmallVec<[ColorAttachment; 1]>,
-    pub depth_stencil_attachment: Option<DepthStencilAttachment>,
+/// Pipeline and bind groups of a draw call, for sorting or bucketing draws before handing them
+/// to [`renderpass::execute_ordered_draw_calls`]. Orders by pipeline, then bind groups in slot
+/// order, the order the executor sorts by, so draws with equal keys share all of that state.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialKey {
+    pub render_pipeline_handle: Handle<wgpu::RenderPipeline>,
+    pub bind_groups: SmallVec<[Handle<wgpu::BindGroup>; 3]>,
 }
 
 ////////////////////////////////////////
@@ -390,6 +921,10 @@ pub struct ImmediateManager {
     bytes: Vec<u8>,
     is_compact: bool,
     top_pos: usize,
+    /// Immediate size of every pipeline layout created by the renderer, checked at draw time.
+    pipeline_sizes: FxHashMap<Handle<wgpu::RenderPipeline>, u32>,
+    /// Material labels of those pipelines, naming their debug groups.
+    pipeline_labels: FxHashMap<Handle<wgpu::RenderPipeline>, String>,
 }
 
 impl ImmediateManager {
@@ -400,7 +935,57 @@ impl ImmediateManager {
             bytes: vec![],
             is_compact: true,
             top_pos: 0,
+            pipeline_sizes: FxHashMap::default(),
+            pipeline_labels: FxHashMap::default(),
+        }
+    }
+
+    /// Records the immediate size of the layout of `pipeline`, see [`Self::validate_draw`], and
+    /// its label, see [`Self::pipeline_label`].
+    pub fn declare_pipeline(
+        &mut self,
+        pipeline: Handle<wgpu::RenderPipeline>,
+        size: u32,
+        label: Option<String>,
+    ) {
+        self.pipeline_sizes.insert(pipeline, size);
+        match label {
+            Some(label) => self.pipeline_labels.insert(pipeline, label),
+            None => self.pipeline_labels.remove(&pipeline),
+        };
+    }
+
+    pub fn forget_pipeline(&mut self, pipeline: Handle<wgpu::RenderPipeline>) {
+        self.pipeline_sizes.remove(&pipeline);
+        self.pipeline_labels.remove(&pipeline);
+    }
+
+    /// Label of the material `pipeline` was created from.
+    pub fn pipeline_label(&self, pipeline: Handle<wgpu::RenderPipeline>) -> Option<&str> {
+        self.pipeline_labels.get(&pipeline).map(String::as_str)
+    }
+
+    /// Checks that `size` bytes of immediate data set at offset 0 fit into the immediate size
+    /// declared for `pipeline` and are a multiple of `wgpu::IMMEDIATE_DATA_ALIGNMENT`.
+    /// Pipelines that weren't declared pass.
+    pub fn validate_draw(
+        &self,
+        pipeline: Handle<wgpu::RenderPipeline>,
+        size: usize,
+    ) -> Result<(), shader::ShaderError> {
+        let Some(&declared) = self.pipeline_sizes.get(&pipeline) else {
+            return Ok(());
+        };
+        if size > declared as usize {
+            return Err(shader::ShaderError::ImmediateTooLarge {
+                size,
+                declared,
+            });
+        }
+        if !size.is_multiple_of(wgpu::IMMEDIATE_DATA_ALIGNMENT as usize) {
+            return Err(shader::ShaderError::ImmediateMisaligned(size));
         }
+        Ok(())
     }
 
     pub fn allocate(&mut self, size: usize) -> utils::InstanceId {
@@ -438,6 +1023,8 @@ impl ImmediateManager {
             let new_size = (self.bytes.len() * 2).max(range.end + size).max(64);
             self.bytes.resize(new_size, 0);
         }
+        // A recycled range still holds the bytes of the immediate that was removed
+        self.bytes[range.clone()].fill(0);
 
         let id = self.id_pool.get_next();
         let idx = id.as_usize();
@@ -451,6 +1038,9 @@ impl ImmediateManager {
         id
     }
 
+    /// Moves the live ranges to the front of the buffer, closing the gaps left by removed
+    /// entries. Ids stay valid. Called by the renderer once per frame; a no-op while nothing was
+    /// removed out of order.
     pub fn defragment(&mut self) {
         if self.is_compact {
             return;
@@ -506,16 +1096,23 @@ impl ImmediateManager {
     }
 }
 
+/// Reading, writing and dropping go through the renderer's shared [`ImmediateManager`], which is
+/// borrowed while a frame is encoded. Reads and writes from inside the encoding, e.g. in a
+/// [`renderpass::RenderPassExecutor`], panic, or block under the `multithread` feature, where a
+/// held lock usually means another thread. A drop there is deferred to the next frame.
 #[derive(Debug)]
 pub struct Immediate {
     id: utils::InstanceId,
     counter: utils::InstanceCounter,
-    manager: Rc<RefCell<ImmediateManager>>,
+    manager: utils::Shared<ImmediateManager>,
+    released: utils::Shared<Vec<utils::InstanceId>>,
 }
 
 impl Immediate {
     pub fn write(&mut self, offset: usize, data: &[u8]) {
-        let mut manager = self.manager.borrow_mut();
+        let mut manager = self
+            .manager
+            .borrow_mut_or_panic("Immediate written while the renderer is encoding a frame");
         let slice = match manager.get_mut(self.id) {
             Some(s) => s,
             None => utils::cold_panic("Immediate buffer invalid ID"),
@@ -529,7 +1126,8 @@ impl Immediate {
     }
 
     pub fn read<const S: usize>(&self, offset: usize) -> [u8; S] {
-        let manager = self.manager.borrow();
+        let manager =
+            self.manager.borrow_or_panic("Immediate read while the renderer is encoding a frame");
         let slice = match manager.get(self.id) {
             Some(s) => s,
             None => utils::cold_panic("Immediate buffer invalid ID"),
@@ -552,15 +1150,18 @@ impl Clone for Immediate {
             id: self.id,
             counter: self.counter.clone(),
             manager: self.manager.clone(),
+            released: self.released.clone(),
         }
     }
 }
 
 impl Drop for Immediate {
     fn drop(&mut self) {
-        self.counter.decrement();
-        if self.counter.value() == 0 {
-            self.manager.borrow_mut().remove(self.id);
+        if self.counter.decrement() {
+            match self.manager.try_borrow_mut() {
+                Some(mut manager) => manager.remove(self.id),
+                None => self.released.borrow_mut().push(self.id),
+            }
         }
     }
 }
@@ -655,3 +1256,129 @@ impl Hash for Entry {
         self.id.hash(state);
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Draws position-only vertices transformed by a world matrix in the immediate data.
+    pub(crate) const SHADER: &str = r#"
+var<immediate> world: mat4x4<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return world * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+"#;
+
+    pub(crate) const POSITION_LAYOUT: wgpu::VertexBufferLayout<'static> =
+        wgpu::VertexBufferLayout {
+            array_stride: 12,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+        };
+
+    /// Material for [`SHADER`] with one `format` color target and no depth.
+    pub(crate) fn material(format: wgpu::TextureFormat) -> Material {
+        Material {
+            label: Some("Test".to_string()),
+            bind_groups: vec![],
+            vertex: Vertex {
+                buffers: vec![POSITION_LAYOUT],
+            },
+            fragment: Some(Fragment {
+                targets: vec![Some(format.into())],
+            }),
+            depth_stencil: None,
+            primitive: wgpu::PrimitiveState::default(),
+            multisample: wgpu::MultisampleState::default(),
+            immediate_size: size_of::<glam::Mat4>() as u32,
+            source: SHADER.into(),
+        }
+    }
+
+    #[test]
+    fn labels_are_not_part_of_the_pipeline_key() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let material = material(wgpu::TextureFormat::Rgba8Unorm);
+        let renamed = Material {
+            label: Some("Renamed".to_string()),
+            ..material.clone()
+        };
+
+        let pipeline = renderer.create_render_pipeline(&material);
+
+        assert_eq!(renderer.create_render_pipeline(&renamed), pipeline);
+        assert_eq!(renderer.immeadiate_manager.borrow().pipeline_label(pipeline), Some("Test"));
+    }
+
+    #[immediate]
+    struct TestData {
+        tint: [f32; 4],
+        count: u32,
+    }
+
+    #[test]
+    fn init_default_resets_every_field() {
+        let renderer = Renderer::with_context(Context::noop());
+        let mut data = TestData::new(renderer.create_immediate(TestData::SIZE), 0);
+
+        data.set_tint([1.0, 0.5, 0.25, 1.0]);
+        data.set_count(7);
+        data.init_default();
+
+        assert_eq!(data.get_tint(), [0.0; 4]);
+        assert_eq!(data.get_count(), 0);
+    }
+
+    #[test]
+    fn set_all_round_trips_through_get_all() {
+        let renderer = Renderer::with_context(Context::noop());
+        let immediate = renderer.create_immediate(16 + TestData::SIZE);
+        let mut data = TestData::new(immediate, 16);
+
+        data.set_all(TestDataValues {
+            tint: [1.0, 0.5, 0.25, 1.0],
+            count: 7,
+        });
+        let values = data.get_all();
+
+        assert_eq!(values.tint, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(values.count, 7);
+        assert_eq!(data.get_count(), 7);
+    }
+
+    #[cfg(not(feature = "multithread"))]
+    #[test]
+    #[should_panic(expected = "Immediate written while the renderer is encoding a frame")]
+    fn writes_while_encoding_panic() {
+        let renderer = Renderer::with_context(Context::noop());
+        let mut immediate = renderer.create_immediate(16);
+
+        // What `Renderer::render` holds while it runs executors
+        let _encoding = renderer.immeadiate_manager.borrow();
+        immediate.write(0, &[0; 4]);
+    }
+
+    #[test]
+    fn drops_while_encoding_are_deferred() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let immediate = renderer.create_immediate(16);
+        let id = immediate.id;
+
+        let encoding = renderer.immeadiate_manager.borrow();
+        drop(immediate);
+        assert!(encoding.get(id).is_some());
+        drop(encoding);
+        assert_eq!(*renderer.released_immediates.borrow(), [id]);
+
+        renderer.prepare_frame(std::iter::empty());
+        assert!(renderer.released_immediates.borrow().is_empty());
+        assert!(renderer.immeadiate_manager.borrow().get(id).is_none());
+    }
+}
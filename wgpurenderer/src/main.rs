@@ -1,7 +1,6 @@
 use bytemuck::{Pod, Zeroable};
 use glam;
 use glfw::{Action, Key};
-use std::borrow::Cow;
 use std::num::NonZeroU32;
 use wgpu::util::DeviceExt;
 use wgpurenderer::{Immediate, immediate};
@@ -168,9 +167,7 @@ fn create_cube_mesh() -> (Vec<Vertex>, Vec<u16>) {
 struct State {
     size: (u32, u32),
     renderer: wgpurenderer::Renderer,
-    msaa_texture: wgpu::Texture,
-    depth_texture: wgpu::Texture,
-    sample_count: u32,
+    surface: wgpurenderer::SurfaceState,
     uniform_buffer: wgpu::Buffer,
     render_pass: wgpurenderer::renderpass::RenderPass,
     rotation: f32,
@@ -181,10 +178,27 @@ impl State {
     async fn new(context: glfw::PRenderContext) -> Self {
         let size = (800, 600);
 
-        let mut renderer = wgpurenderer::Renderer::new();
-        let surface = renderer.context().instance().create_surface(context).unwrap();
+        let mut renderer = wgpurenderer::Renderer::new()
+            .unwrap_or_else(|e| panic!("Failed to initialize renderer: {e}"));
+        let surface = renderer
+            .context()
+            .create_surface(context)
+            .unwrap_or_else(|e| panic!("Failed to initialize renderer: {e}"));
 
-        renderer.init_surface(surface, size.0, size.1);
+        let mut surface = wgpurenderer::SurfaceState::new(
+            renderer.context(),
+            surface,
+            size.0,
+            size.1,
+            4,
+            Some(wgpu::TextureFormat::Depth32Float),
+        );
+        surface.clear_color = wgpu::Color {
+            r: 0.1,
+            g: 0.2,
+            b: 0.3,
+            a: 1.0,
+        };
 
         let (vertices, indices) = create_cube_mesh();
         let num_indices = indices.len() as u32;
@@ -222,14 +236,20 @@ impl State {
             count: None,
         }];
 
+        let source = include_str!("../shaders/shader.wgsl");
+        let shader_layout =
+            wgpurenderer::shader::ShaderLayout::from_wgsl(source).expect("Invalid shader");
+        shader_layout.validate_immediate(LightData::SIZE).expect("Immediate data doesn't fit");
+
         let material = wgpurenderer::Material {
+            label: Some("Cube".to_string()),
             bind_groups: vec![bind_group_layout.clone()],
             vertex: wgpurenderer::Vertex {
                 buffers: vec![Vertex::desc()],
             },
             fragment: Some(wgpurenderer::Fragment {
                 targets: vec![Some(wgpu::ColorTargetState {
-                    format: renderer.surface_format(),
+                    format: surface.format(),
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -243,21 +263,24 @@ impl State {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
+            depth_stencil: surface.depth_format().map(|format| wgpu::DepthStencilState {
+                format,
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::Less,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
-                count: 4,
+                count: surface.sample_count(),
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
-            source: Cow::Borrowed(include_str!("../shaders/shader.wgsl")),
+            immediate_size: shader_layout.immediate_size(),
+            source: source.into(),
         };
 
+        material.validate_vertex_layout(&shader_layout).expect("Vertex layout doesn't match");
+        material.validate_immediates(&shader_layout).expect("Immediate size doesn't match");
         let pipeline_handle = renderer.create_render_pipeline(&material);
 
         let bindgroup_handle = renderer.create_bindgroup(
@@ -273,8 +296,15 @@ impl State {
             index_buffer: Some(index_buffer),
             index_buffer_range: Some(0..index_buffer_size),
             index_format: wgpu::IndexFormat::Uint16,
-            buffers: vec![(vertex_buffer, None)],
+            base_vertex: 0,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            buffers: vec![wgpurenderer::geometry::VertexBuffer::from_buffer(
+                vertex_buffer,
+                Vertex::desc().into(),
+            )],
             count: num_indices,
+            indices: None,
+            instance_count: None,
         };
 
         let shader_data = wgpurenderer::ShaderData {
@@ -287,58 +317,28 @@ impl State {
             shader_data,
             instance_count: NonZeroU32::new(1).unwrap(),
             render_pipeline_handle: pipeline_handle,
+            scissor: None,
+            stencil_reference: None,
         };
 
-        let sample_count = 4;
-        let msaa_texture = Self::create_msaa_texture(
-            renderer.context().device(),
-            size.0,
-            size.1,
-            renderer.surface_format(),
-            sample_count,
-        );
-        let depth_texture =
-            Self::create_depth_texture(renderer.context().device(), size.0, size.1, sample_count);
-
-        let msaa_view = msaa_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
         let render_pass = wgpurenderer::renderpass::RenderPass {
+            name: Some("Main".to_string()),
+            // Replaced by the surface frame's target every frame
             render_target: wgpurenderer::RenderTarget {
-                color_attachments: smallvec::smallvec![wgpurenderer::ColorAttachment {
-                    view: msaa_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                }],
-                depth_stencil_attachment: Some(wgpurenderer::DepthStencilAttachment {
-                    view: depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
+                color_attachments: Default::default(),
+                depth_stencil_attachment: None,
             },
             multiview_mask: None,
+            viewport: None,
             draw_calls: vec![draw_call],
+            bundle: None,
             executor: None,
         };
 
         Self {
             size,
             renderer,
-            msaa_texture,
-            depth_texture,
-            sample_count,
+            surface,
             uniform_buffer,
             render_pass,
             rotation: 0.0,
@@ -346,73 +346,10 @@ impl State {
         }
     }
 
-    fn create_msaa_texture(
-        device: &wgpu::Device,
-        width: u32,
-        height: u32,
-        format: wgpu::TextureFormat,
-        sample_count: u32,
-    ) -> wgpu::Texture {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("MSAA Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        })
-    }
-
-    fn create_depth_texture(
-        device: &wgpu::Device,
-        width: u32,
-        height: u32,
-        sample_count: u32,
-    ) -> wgpu::Texture {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        })
-    }
-
     fn resize(&mut self, new_size: (u32, u32)) {
         if new_size.0 > 0 && new_size.1 > 0 {
             self.size = new_size;
-            self.renderer.resize(self.size.0, self.size.1);
-
-            self.msaa_texture = Self::create_msaa_texture(
-                self.renderer.context().device(),
-                self.size.0,
-                self.size.1,
-                self.renderer.surface_format(),
-                self.sample_count,
-            );
-            self.depth_texture = Self::create_depth_texture(
-                self.renderer.context().device(),
-                self.size.0,
-                self.size.1,
-                self.sample_count,
-            );
-            self.render_pass.render_target.color_attachments[0].view =
-                self.msaa_texture.create_view(&wgpu::TextureViewDescriptor::default());
-            self.render_pass.render_target.depth_stencil_attachment.as_mut().unwrap().view =
-                self.depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.surface.resize(self.size.0, self.size.1);
         }
     }
 
@@ -458,13 +395,15 @@ impl State {
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.renderer.acquire()?;
-
-        self.render_pass.render_target.color_attachments[0].resolve_target =
-            Some(self.renderer.surface_view.as_ref().unwrap().clone());
+        let frame = self.surface.acquire()?;
 
+        self.render_pass.render_target = frame.render_target.clone();
         self.renderer.render(&mut [&mut self.render_pass]);
-        self.renderer.present();
+
+        // The surface view is only valid for this frame
+        self.render_pass.render_target.color_attachments.clear();
+        self.render_pass.render_target.depth_stencil_attachment = None;
+        frame.present();
 
         Ok(())
     }
@@ -504,7 +443,6 @@ fn main() {
 
         match state.render() {
             Ok(_) => {}
-            Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
             Err(wgpu::SurfaceError::OutOfMemory) => {
                 eprintln!("Out of memory!");
                 break;
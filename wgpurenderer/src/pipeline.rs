@@ -0,0 +1,224 @@
+use crate::{Handle, Material, Renderer, ResourcePool, shader::LayoutMismatch};
+use rustc_hash::FxHashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    Premultiplied,
+    Additive,
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> wgpu::BlendState {
+        match self {
+            Self::Opaque => wgpu::BlendState::REPLACE,
+            Self::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            Self::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            Self::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VariantKey {
+    pub blend: BlendMode,
+    pub depth_write: bool,
+}
+
+impl VariantKey {
+    pub const OPAQUE: Self = Self {
+        blend: BlendMode::Opaque,
+        depth_write: true,
+    };
+    pub const TRANSPARENT: Self = Self {
+        blend: BlendMode::Alpha,
+        depth_write: false,
+    };
+}
+
+/// Pipelines that only differ from a base material by blend state and depth writes. Variants
+/// are created on first request and live in the renderer's pipeline pool.
+#[derive(Debug)]
+pub struct PipelineVariants {
+    base: Material,
+    variants: FxHashMap<VariantKey, Handle<wgpu::RenderPipeline>>,
+}
+
+impl PipelineVariants {
+    pub fn new(base: Material) -> Self {
+        Self {
+            base,
+            variants: FxHashMap::default(),
+        }
+    }
+
+    pub fn base(&self) -> &Material {
+        &self.base
+    }
+
+    /// Returns the pipeline for `key`, creating it if it doesn't exist or was removed from the
+    /// renderer.
+    pub fn get(
+        &mut self,
+        renderer: &mut Renderer,
+        key: VariantKey,
+    ) -> Handle<wgpu::RenderPipeline> {
+        if let Some(&handle) = self.variants.get(&key)
+            && renderer.render_pipeline(handle).is_some()
+        {
+            return handle;
+        }
+
+        let mut material = self.base.clone();
+        if let Some(fragment) = &mut material.fragment {
+            for target in fragment.targets.iter_mut().flatten() {
+                target.blend = Some(key.blend.blend_state());
+            }
+        }
+        if let Some(depth_stencil) = &mut material.depth_stencil {
+            depth_stencil.depth_write_enabled = key.depth_write;
+        }
+
+        let handle = renderer.create_render_pipeline(&material);
+        self.variants.insert(key, handle);
+        handle
+    }
+
+    pub fn opaque(&mut self, renderer: &mut Renderer) -> Handle<wgpu::RenderPipeline> {
+        self.get(renderer, VariantKey::OPAQUE)
+    }
+
+    pub fn transparent(&mut self, renderer: &mut Renderer) -> Handle<wgpu::RenderPipeline> {
+        self.get(renderer, VariantKey::TRANSPARENT)
+    }
+
+    /// Removes every created variant from the renderer.
+    pub fn clear(&mut self, renderer: &mut Renderer) {
+        for (_, handle) in self.variants.drain() {
+            renderer.remove_render_pipeline(handle);
+        }
+    }
+}
+
+/// Deduplicates render pipelines by the material they were created from, optionally backed by
+/// the driver's on-disk pipeline cache.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    pipelines: FxHashMap<Material, Handle<wgpu::RenderPipeline>>,
+    driver_cache: Option<wgpu::PipelineCache>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached pipeline for `material`, if it's still alive in `pool`.
+    pub fn get(
+        &self,
+        material: &Material,
+        pool: &ResourcePool<wgpu::RenderPipeline>,
+    ) -> Option<Handle<wgpu::RenderPipeline>> {
+        self.pipelines.get(material).copied().filter(|&handle| pool.is_alive(handle))
+    }
+
+    pub fn insert(&mut self, material: Material, handle: Handle<wgpu::RenderPipeline>) {
+        self.pipelines.insert(material, handle);
+    }
+
+    pub fn driver_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.driver_cache.as_ref()
+    }
+
+    pub fn set_driver_cache(&mut self, cache: wgpu::PipelineCache) {
+        self.driver_cache = Some(cache);
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineError {
+    /// The material has a different number of fragment targets than the render target has
+    /// color attachments.
+    TargetCountMismatch {
+        material: usize,
+        render_target: usize,
+    },
+    TargetFormatMismatch {
+        location: usize,
+        material: wgpu::TextureFormat,
+        render_target: wgpu::TextureFormat,
+    },
+    /// The material's vertex buffers don't feed the shader's vertex inputs.
+    VertexLayout(LayoutMismatch),
+    /// Alpha-to-coverage was enabled on a material with a sample count of 1.
+    AlphaToCoverageWithoutMsaa,
+    /// The material's depth-stencil format differs from the render target's depth attachment,
+    /// `None` standing for no depth-stencil state or no attachment.
+    DepthFormatMismatch {
+        material: Option<wgpu::TextureFormat>,
+        render_target: Option<wgpu::TextureFormat>,
+    },
+    /// The material's immediate size is smaller than the shader's `var<immediate>` block.
+    ImmediateSize {
+        material: u32,
+        shader: u32,
+    },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TargetCountMismatch {
+                material,
+                render_target,
+            } => {
+                write!(f, "{material} fragment targets for {render_target} color attachments")
+            }
+            Self::TargetFormatMismatch {
+                location,
+                material,
+                render_target,
+            } => {
+                write!(
+                    f,
+                    "fragment target {location} is {material:?}, attachment {render_target:?}"
+                )
+            }
+            Self::VertexLayout(mismatch) => write!(f, "{mismatch}"),
+            Self::AlphaToCoverageWithoutMsaa => {
+                write!(f, "alpha-to-coverage needs a sample count above 1")
+            }
+            Self::DepthFormatMismatch {
+                material,
+                render_target,
+            } => {
+                write!(f, "depth format is {material:?}, attachment {render_target:?}")
+            }
+            Self::ImmediateSize {
+                material,
+                shader,
+            } => {
+                write!(f, "{material} bytes of immediate data for a {shader} byte shader block")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
@@ -0,0 +1,147 @@
+use crate::utils::{IdPool, InstanceId};
+use nohash_hasher::IntMap;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+/// Generational reference into a [`ResourcePool`]. Ids are recycled, the generation tells a
+/// handle to a removed resource apart from one to its replacement.
+pub struct Handle<T> {
+    id: InstanceId,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> InstanceId {
+        self.id
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("id", &self.id)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.id, self.generation).cmp(&(other.id, other.generation))
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourcePool<T> {
+    resources: IntMap<InstanceId, T>,
+    generations: Vec<u32>,
+    ids: IdPool,
+}
+
+impl<T> ResourcePool<T> {
+    pub fn new() -> Self {
+        Self {
+            resources: IntMap::default(),
+            generations: vec![],
+            ids: IdPool::new(),
+        }
+    }
+
+    pub fn insert(&mut self, resource: T) -> Handle<T> {
+        let id = self.ids.get_next();
+        let idx = id.as_usize();
+
+        if idx >= self.generations.len() {
+            self.generations.resize(idx + 1, 0);
+        }
+
+        self.resources.insert(id, resource);
+        Handle {
+            id,
+            generation: self.generations[idx],
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        if !self.is_alive(handle) {
+            return None;
+        }
+        self.resources.get(&handle.id)
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        if !self.is_alive(handle) {
+            return None;
+        }
+        self.resources.get_mut(&handle.id)
+    }
+
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        if !self.is_alive(handle) {
+            return None;
+        }
+
+        let resource = self.resources.remove(&handle.id)?;
+        self.generations[handle.id.as_usize()] += 1;
+        self.ids.free(handle.id);
+        Some(resource)
+    }
+
+    pub fn is_alive(&self, handle: Handle<T>) -> bool {
+        self.generations.get(handle.id.as_usize()) == Some(&handle.generation)
+            && self.resources.contains_key(&handle.id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+}
+
+impl<T> Default for ResourcePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
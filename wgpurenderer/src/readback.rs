@@ -0,0 +1,206 @@
+use crate::{camera::Camera, transform::Transform};
+use glam::{UVec2, Vec3, Vec4};
+use std::{fmt, sync::mpsc};
+
+#[derive(Debug)]
+pub enum ReadbackError {
+    UnsupportedFormat(wgpu::TextureFormat),
+    MissingAttachment,
+    Map(wgpu::BufferAsyncError),
+    Poll(wgpu::PollError),
+    #[cfg(feature = "image")]
+    Image(image::ImageError),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => {
+                write!(f, "texture format {format:?} can't be read back")
+            }
+            Self::MissingAttachment => write!(f, "render target has no attachment to read"),
+            Self::Map(e) => write!(f, "failed to map readback buffer: {e}"),
+            Self::Poll(e) => write!(f, "failed to wait for readback: {e}"),
+            #[cfg(feature = "image")]
+            Self::Image(e) => write!(f, "failed to encode image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+/// Copies a region of `texture` into CPU memory and blocks until it arrives. Rows in the
+/// returned bytes are tightly packed, the 256-byte copy padding is stripped.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    origin: wgpu::Origin3d,
+    size: wgpu::Extent3d,
+    aspect: wgpu::TextureAspect,
+) -> Result<Vec<u8>, ReadbackError> {
+    let format = texture.format();
+    let block_size =
+        format.block_copy_size(Some(aspect)).ok_or(ReadbackError::UnsupportedFormat(format))?;
+
+    let unpadded_bytes_per_row = size.width * block_size;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let rows = size.height * size.depth_or_array_layers;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: padded_bytes_per_row as u64 * rows as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: None,
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin,
+            aspect,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = mpsc::channel();
+    buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::wait_indefinitely()).map_err(ReadbackError::Poll)?;
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError)).map_err(ReadbackError::Map)?;
+
+    let mut bytes = Vec::with_capacity((unpadded_bytes_per_row * rows) as usize);
+    {
+        let mapped = buffer.get_mapped_range(..);
+        for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+            bytes.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    Ok(bytes)
+}
+
+/// Depth at `pixel` of a depth texture created with `COPY_SRC`, in `0..=1`. Only
+/// single-sampled `Depth32Float`, `Depth32FloatStencil8` and `Depth16Unorm` textures can be
+/// copied, `Depth24Plus*` formats have no defined memory layout.
+pub fn read_depth(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    pixel: UVec2,
+) -> Result<f32, ReadbackError> {
+    let format = texture.format();
+    let bytes = read_texture(
+        device,
+        queue,
+        texture,
+        wgpu::Origin3d {
+            x: pixel.x,
+            y: pixel.y,
+            z: 0,
+        },
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        wgpu::TextureAspect::DepthOnly,
+    )?;
+
+    match format {
+        wgpu::TextureFormat::Depth32Float | wgpu::TextureFormat::Depth32FloatStencil8 => {
+            Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        wgpu::TextureFormat::Depth16Unorm => {
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32)
+        }
+        _ => Err(ReadbackError::UnsupportedFormat(format)),
+    }
+}
+
+/// World position of the surface under `pixel` (in pixels, top-left origin), reconstructed from
+/// `depth_texture` through the inverse view-projection of `camera` at `transform`. `None` when
+/// `pixel` is outside the texture, nothing was drawn there or the depth can't be read, see
+/// [`read_depth`].
+///
+/// Blocks until the GPU has finished all submitted work, so it's meant for clicks rather than
+/// every frame. The depth is that of the last submitted frame, one frame behind the cursor;
+/// `camera` and `transform` have to be the ones that frame was rendered with, otherwise the
+/// position is off by however much the camera moved since.
+pub fn pick_world_position(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    depth_texture: &wgpu::Texture,
+    camera: &mut Camera,
+    transform: &Transform,
+    pixel: UVec2,
+) -> Option<Vec3> {
+    let size = depth_texture.size();
+    if pixel.x >= size.width || pixel.y >= size.height {
+        return None;
+    }
+
+    let depth = read_depth(device, queue, depth_texture, pixel)
+        .inspect_err(|e| log::warn!("Depth picking failed: {e}"))
+        .ok()?;
+    // Cleared to the far plane, nothing was drawn
+    if depth >= 1.0 {
+        return None;
+    }
+
+    let ndc = Vec4::new(
+        (pixel.x as f32 + 0.5) / size.width as f32 * 2.0 - 1.0,
+        1.0 - (pixel.y as f32 + 0.5) / size.height as f32 * 2.0,
+        depth,
+        1.0,
+    );
+    let world = camera.get_inverse_view_projection(transform) * ndc;
+    Some(world.truncate() / world.w)
+}
+
+/// Reads mip 0 of an 8-bit RGBA/BGRA texture into an image.
+#[cfg(feature = "image")]
+pub fn read_rgba_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<image::RgbaImage, ReadbackError> {
+    let format = texture.format();
+    let swap_red_blue = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => return Err(ReadbackError::UnsupportedFormat(format)),
+    };
+
+    let size = wgpu::Extent3d {
+        depth_or_array_layers: 1,
+        ..texture.size()
+    };
+    let mut bytes =
+        read_texture(device, queue, texture, wgpu::Origin3d::ZERO, size, wgpu::TextureAspect::All)?;
+
+    if swap_red_blue {
+        for pixel in bytes.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(image::RgbaImage::from_raw(size.width, size.height, bytes)
+        .expect("readback size matches the texture extent"))
+}
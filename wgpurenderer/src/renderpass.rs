@@ -1,62 +1,402 @@
-use crate::{DrawCall, ImmediateManager, RenderTarget};
+use crate::{DrawCall, Handle, ImmediateManager, RenderTarget, ResourcePool, utils};
+use rustc_hash::FxHasher;
 use smallvec::SmallVec;
-use std::{fmt::Debug, num::NonZeroU32};
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    num::NonZeroU32,
+    ops::{AddAssign, Range},
+};
+use wgpu::util::RenderEncoder;
 
 #[derive(Debug)]
 pub struct RenderPass {
+    /// Label of the pass. With the `debug-markers` feature its draws are also wrapped in a debug
+    /// group of this name, with a nested group per pipeline.
+    pub name: Option<String>,
     pub render_target: RenderTarget,
     // TODO: pub timestamp_writes: Option<RenderPassTimestampWrites<'a>>,
     // TODO: pub occlusion_query_set: Option<&'a QuerySet>,
     pub multiview_mask: Option<NonZeroU32>,
+    /// Applied once after the pass begins, `None` keeps the whole target. Not applied when an
+    /// `executor` runs the pass.
+    pub viewport: Option<Viewport>,
     pub draw_calls: Vec<DrawCall>,
+    /// Replayed instead of encoding the bundleable draw calls while they match what was
+    /// recorded. See [`RenderPass::cache_bundle`].
+    pub bundle: Option<RecordedBundle>,
     pub executor: Option<Box<dyn RenderPassExecutor>>,
 }
 
+#[derive(Debug)]
+pub struct RecordedBundle {
+    bundle: wgpu::RenderBundle,
+    fingerprint: u64,
+    draws: u32,
+    draws_skipped: u32,
+}
+
+impl RecordedBundle {
+    pub fn bundle(&self) -> &wgpu::RenderBundle {
+        &self.bundle
+    }
+
+    /// Draws encoded into the bundle, reported as [`RenderStats::bundled_draws`] on replay.
+    pub fn draws(&self) -> u32 {
+        self.draws
+    }
+
+    /// Bundleable draws left out while recording, see [`RenderStats::draws_skipped`].
+    pub fn draws_skipped(&self) -> u32 {
+        self.draws_skipped
+    }
+}
+
+/// Viewport rectangle in pixels. `min_depth`/`max_depth` remap the NDC depth range `0..1` and
+/// must stay within `0..=1`. For reversed-Z keep them at `0.0` and `1.0` and flip the depth
+/// compare function instead; swapping them is not allowed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    /// Covers `width`x`height` pixels at `(x, y)` with the full depth range.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
 impl RenderPass {
+    /// Depth pre-pass into the depth attachment of `render_target`, which must have one. The
+    /// pass has no color attachments, clears depth and stores it for the main pass.
+    ///
+    /// Draw calls should use a pipeline from [`crate::Material::depth_only`], the main pass one
+    /// from [`crate::Material::depth_equal`] with its depth attachment loaded instead of
+    /// cleared. Both pipelines must compute the vertex position identically (same module and
+    /// entry point, ideally `@invariant`), otherwise the `Equal` test rejects fragments.
+    pub fn depth_only(render_target: &RenderTarget) -> Self {
+        let Some(attachment) = &render_target.depth_stencil_attachment else {
+            utils::cold_panic("Depth pre-pass needs a depth attachment");
+        };
+
+        Self {
+            name: None,
+            render_target: RenderTarget {
+                color_attachments: SmallVec::new(),
+                depth_stencil_attachment: Some(crate::DepthStencilAttachment {
+                    view: attachment.view.clone(),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+            },
+            multiview_mask: None,
+            viewport: None,
+            draw_calls: vec![],
+            bundle: None,
+            executor: None,
+        }
+    }
+
+    /// Pass without draw calls that only clears every attachment of `render_target`: color to
+    /// `color`, depth to `depth` and stencil to 0.
+    pub fn clear(render_target: &RenderTarget, color: wgpu::Color, depth: f32) -> Self {
+        let mut render_target = render_target.clone();
+
+        for attachment in &mut render_target.color_attachments {
+            attachment.ops = wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color),
+                store: wgpu::StoreOp::Store,
+            };
+        }
+
+        if let Some(attachment) = &mut render_target.depth_stencil_attachment {
+            let format = attachment.view.texture().format();
+            attachment.depth_ops = format.has_depth_aspect().then_some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(depth),
+                store: wgpu::StoreOp::Store,
+            });
+            attachment.stencil_ops = format.has_stencil_aspect().then_some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Store,
+            });
+        }
+
+        Self {
+            name: None,
+            render_target,
+            multiview_mask: None,
+            viewport: None,
+            draw_calls: vec![],
+            bundle: None,
+            executor: None,
+        }
+    }
+
+    /// Pass drawing a single fullscreen triangle into `render_target` with `render_pipeline`,
+    /// e.g. built from [`crate::shader::presets::fullscreen`], reading its input through
+    /// `bind_group` at group 0. The draw has no vertex buffers, see
+    /// [`crate::Geometry::procedural`]. Attachments keep the load operations of
+    /// `render_target`.
+    pub fn fullscreen(
+        render_target: &RenderTarget,
+        render_pipeline: Handle<wgpu::RenderPipeline>,
+        bind_group: Handle<wgpu::BindGroup>,
+    ) -> Self {
+        Self {
+            name: None,
+            render_target: render_target.clone(),
+            multiview_mask: None,
+            viewport: None,
+            draw_calls: vec![DrawCall {
+                geometry: crate::Geometry::procedural(3),
+                shader_data: crate::ShaderData {
+                    immediates: None,
+                    bind_groups: SmallVec::from_slice(&[bind_group]),
+                },
+                instance_count: NonZeroU32::MIN,
+                render_pipeline_handle: render_pipeline,
+                scissor: None,
+                stencil_reference: None,
+            }],
+            bundle: None,
+            executor: None,
+        }
+    }
+
+    /// Inserts `draw_call` at its place in [`compare_draw_calls`] order, after the draws with an
+    /// equal key. Passes built only through this method are never re-sorted when rendered.
+    pub fn push_draw_call(&mut self, draw_call: DrawCall) {
+        let index =
+            self.draw_calls.partition_point(|other| compare_draw_calls(other, &draw_call).is_le());
+        self.draw_calls.insert(index, draw_call);
+    }
+
+    /// Encodes the pass into `encoder`. Passes run by an `executor` report empty stats.
     pub fn render(
         &mut self,
         encoder: &mut wgpu::CommandEncoder,
         immediate_manager: &ImmediateManager,
-    ) {
-        let color_attachments: SmallVec<[_; 1]> = self
+        render_pipelines: &ResourcePool<wgpu::RenderPipeline>,
+        bind_groups: &ResourcePool<wgpu::BindGroup>,
+    ) -> RenderStats {
+        if let Some(executor) = &mut self.executor {
+            with_descriptor(
+                &self.render_target,
+                self.name.as_deref(),
+                self.multiview_mask,
+                |descriptor| executor.execute(encoder, descriptor),
+            );
+            return RenderStats::default();
+        }
+
+        let mut render_pass = with_descriptor(
+            &self.render_target,
+            self.name.as_deref(),
+            self.multiview_mask,
+            |descriptor| encoder.begin_render_pass(descriptor),
+        );
+        self.encode_contents(&mut render_pass, immediate_manager, render_pipelines, bind_groups)
+    }
+
+    /// Resolves everything [`Self::render`] would encode into an owned command list that can be
+    /// encoded on another thread. Passes with an executor can't be recorded and return `None`.
+    pub(crate) fn record(
+        &mut self,
+        immediate_manager: &ImmediateManager,
+        render_pipelines: &ResourcePool<wgpu::RenderPipeline>,
+        bind_groups: &ResourcePool<wgpu::BindGroup>,
+    ) -> Option<(RecordedPass, RenderStats)> {
+        if self.executor.is_some() {
+            return None;
+        }
+
+        let mut recorder = CommandRecorder {
+            commands: vec![],
+        };
+        let stats =
+            self.encode_contents(&mut recorder, immediate_manager, render_pipelines, bind_groups);
+        let pass = RecordedPass {
+            name: self.name.clone(),
+            render_target: self.render_target.clone(),
+            multiview_mask: self.multiview_mask,
+            commands: recorder.commands,
+        };
+        Some((pass, stats))
+    }
+
+    fn encode_contents<'a>(
+        &'a mut self,
+        render_pass: &mut impl PassEncoder<'a>,
+        immediate_manager: &ImmediateManager,
+        render_pipelines: &'a ResourcePool<wgpu::RenderPipeline>,
+        bind_groups: &'a ResourcePool<wgpu::BindGroup>,
+    ) -> RenderStats {
+        let target_size = self.render_target.size().unwrap_or((0, 0));
+        if self
+            .bundle
+            .as_ref()
+            .is_some_and(|bundle| bundle.fingerprint != bundle_fingerprint(&self.draw_calls))
+        {
+            log::debug!("Draw calls changed, dropping the recorded render bundle");
+            self.bundle = None;
+        }
+        // The bundle holds on to removed pipelines and bind groups, while the regular path skips
+        // draws referencing them
+        if self.bundle.is_some()
+            && !self
+                .draw_calls
+                .iter()
+                .filter(|draw_call| is_bundleable(draw_call))
+                .all(|draw_call| handles_alive(draw_call, render_pipelines, bind_groups))
+        {
+            log::debug!(
+                "Draw calls reference removed resources, dropping the recorded render bundle"
+            );
+            self.bundle = None;
+        }
+
+        if let Some(viewport) = self.viewport {
+            render_pass.set_viewport(viewport);
+        }
+
+        #[cfg(feature = "debug-markers")]
+        if let Some(name) = &self.name {
+            render_pass.push_debug_group(name);
+        }
+
+        sort_draw_calls(&mut self.draw_calls);
+        let draw_calls = &self.draw_calls;
+        let stats = if let Some(bundle) = &self.bundle {
+            render_pass.execute_bundle(&bundle.bundle);
+            // The rest keeps its sorted order, so the list isn't re-sorted next frame
+            let mut stats = encode_sorted_draw_calls(
+                render_pass,
+                target_size,
+                draw_calls.iter().filter(|draw_call| !is_bundleable(draw_call)),
+                immediate_manager,
+                render_pipelines,
+                bind_groups,
+            );
+            stats.bundled_draws = bundle.draws;
+            stats.draws_skipped += bundle.draws_skipped;
+            stats
+        } else {
+            encode_sorted_draw_calls(
+                render_pass,
+                target_size,
+                draw_calls,
+                immediate_manager,
+                render_pipelines,
+                bind_groups,
+            )
+        };
+
+        #[cfg(feature = "debug-markers")]
+        if self.name.is_some() {
+            render_pass.pop_debug_group();
+        }
+        stats
+    }
+
+    /// Records the draw calls into a render bundle, in the same order
+    /// [`execute_ordered_draw_calls`] would issue them. Bundles can't change the scissor,
+    /// viewport or stencil reference, so draws setting those are left out and have to go
+    /// through the regular path.
+    /// Immediate data is captured as it is at record time, and draws the regular path would
+    /// skip are left out and counted.
+    pub fn record_bundle(
+        &self,
+        device: &wgpu::Device,
+        immediate_manager: &ImmediateManager,
+        render_pipelines: &ResourcePool<wgpu::RenderPipeline>,
+        bind_groups: &ResourcePool<wgpu::BindGroup>,
+    ) -> RecordedBundle {
+        let color_formats: SmallVec<[_; 1]> = self
             .render_target
             .color_attachments
             .iter()
-            .map(|attachment| {
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &attachment.view,
-                    resolve_target: attachment.resolve_target.as_ref(),
-                    ops: attachment.ops,
-                    depth_slice: attachment.depth_slice,
-                })
-            })
+            .map(|attachment| Some(attachment.view.texture().format()))
             .collect();
-
-        let depth_stencil_attachment =
+        let depth_stencil =
             self.render_target.depth_stencil_attachment.as_ref().map(|attachment| {
-                wgpu::RenderPassDepthStencilAttachment {
-                    view: &attachment.view,
-                    depth_ops: attachment.depth_ops,
-                    stencil_ops: attachment.stencil_ops,
+                wgpu::RenderBundleDepthStencil {
+                    format: attachment.view.texture().format(),
+                    depth_read_only: false,
+                    stencil_read_only: false,
                 }
             });
+        let sample_count = self
+            .render_target
+            .color_attachments
+            .first()
+            .map(|attachment| &attachment.view)
+            .or(self.render_target.depth_stencil_attachment.as_ref().map(|a| &a.view))
+            .map_or(1, |view| view.texture().sample_count());
 
-        let render_pass_descriptor = wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &color_attachments,
-            depth_stencil_attachment,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-            multiview_mask: self.multiview_mask,
-        };
+        let mut bundle_encoder =
+            device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: None,
+                color_formats: &color_formats,
+                depth_stencil,
+                sample_count,
+                multiview: self.multiview_mask,
+            });
 
-        if let Some(executor) = &mut self.executor {
-            executor.execute(encoder, &render_pass_descriptor);
-        } else {
-            let mut render_pass = encoder.begin_render_pass(&render_pass_descriptor);
-            execute_ordered_draw_calls(&mut render_pass, &mut self.draw_calls, immediate_manager);
+        let mut draw_calls: Vec<_> =
+            self.draw_calls.iter().filter(|draw_call| is_bundleable(draw_call)).collect();
+        draw_calls.sort_by(|a, b| compare_draw_calls(a, b));
+
+        let mut state = EncoderState::new();
+        for draw_call in draw_calls {
+            encode_draw_call(
+                &mut bundle_encoder,
+                &mut state,
+                draw_call,
+                immediate_manager,
+                render_pipelines,
+                bind_groups,
+            );
+        }
+
+        RecordedBundle {
+            bundle: bundle_encoder.finish(&wgpu::RenderBundleDescriptor {
+                label: None,
+            }),
+            fingerprint: bundle_fingerprint(&self.draw_calls),
+            draws: state.stats.draws_submitted,
+            draws_skipped: state.stats.draws_skipped,
         }
     }
+
+    /// Records a bundle and keeps it in [`Self::bundle`]. It is dropped automatically once the
+    /// bundleable draw calls change or reference a removed pipeline or bind group.
+    pub fn cache_bundle(
+        &mut self,
+        device: &wgpu::Device,
+        immediate_manager: &ImmediateManager,
+        render_pipelines: &ResourcePool<wgpu::RenderPipeline>,
+        bind_groups: &ResourcePool<wgpu::BindGroup>,
+    ) {
+        self.bundle =
+            Some(self.record_bundle(device, immediate_manager, render_pipelines, bind_groups));
+    }
 }
 
 pub trait RenderPassExecutor: Debug {
@@ -67,72 +407,887 @@ pub trait RenderPassExecutor: Debug {
     );
 }
 
-pub fn execute_ordered_draw_calls(
-    render_pass: &mut wgpu::RenderPass,
-    draw_calls: &mut [DrawCall],
-    immediate_manager: &ImmediateManager,
-) {
-    // Sort draw calls to minimize state changes: Pipeline -> BindGroups
-    draw_calls.sort_by(|a, b| match a.render_pipeline_handle.cmp(&b.render_pipeline_handle) {
-        std::cmp::Ordering::Equal => {
-            a.shader_data.bind_groups.iter().cmp(b.shader_data.bind_groups.iter())
+/// Calls `f` with the descriptor of a pass rendering into `render_target`.
+fn with_descriptor<R>(
+    render_target: &RenderTarget,
+    label: Option<&str>,
+    multiview_mask: Option<NonZeroU32>,
+    f: impl FnOnce(&wgpu::RenderPassDescriptor) -> R,
+) -> R {
+    let color_attachments: SmallVec<[_; 1]> = render_target
+        .color_attachments
+        .iter()
+        .map(|attachment| {
+            Some(wgpu::RenderPassColorAttachment {
+                view: &attachment.view,
+                resolve_target: attachment.resolve_target.as_ref(),
+                ops: attachment.ops,
+                depth_slice: attachment.depth_slice,
+            })
+        })
+        .collect();
+
+    let depth_stencil_attachment =
+        render_target.depth_stencil_attachment.as_ref().map(|attachment| {
+            wgpu::RenderPassDepthStencilAttachment {
+                view: &attachment.view,
+                depth_ops: attachment.depth_ops,
+                stencil_ops: attachment.stencil_ops,
+            }
+        });
+
+    f(&wgpu::RenderPassDescriptor {
+        label,
+        color_attachments: &color_attachments,
+        depth_stencil_attachment,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+        multiview_mask,
+    })
+}
+
+/// Pass-level state on top of what [`RenderEncoder`] covers, implemented by `wgpu::RenderPass`
+/// and by [`CommandRecorder`] for deferred encoding.
+trait PassEncoder<'a>: RenderEncoder<'a> {
+    fn set_viewport(&mut self, viewport: Viewport);
+    fn set_scissor_rect(&mut self, x: u32, y: u32, width: u32, height: u32);
+    fn set_stencil_reference(&mut self, reference: u32);
+    fn execute_bundle(&mut self, bundle: &'a wgpu::RenderBundle);
+    #[cfg(feature = "debug-markers")]
+    fn push_debug_group(&mut self, label: &str);
+    #[cfg(feature = "debug-markers")]
+    fn pop_debug_group(&mut self);
+}
+
+impl<'a> PassEncoder<'a> for wgpu::RenderPass<'a> {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        wgpu::RenderPass::set_viewport(
+            self,
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            viewport.min_depth,
+            viewport.max_depth,
+        );
+    }
+
+    fn set_scissor_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        wgpu::RenderPass::set_scissor_rect(self, x, y, width, height);
+    }
+
+    fn set_stencil_reference(&mut self, reference: u32) {
+        wgpu::RenderPass::set_stencil_reference(self, reference);
+    }
+
+    fn execute_bundle(&mut self, bundle: &'a wgpu::RenderBundle) {
+        self.execute_bundles([bundle]);
+    }
+
+    #[cfg(feature = "debug-markers")]
+    fn push_debug_group(&mut self, label: &str) {
+        wgpu::RenderPass::push_debug_group(self, label);
+    }
+
+    #[cfg(feature = "debug-markers")]
+    fn pop_debug_group(&mut self) {
+        wgpu::RenderPass::pop_debug_group(self);
+    }
+}
+
+/// A pass resolved by [`RenderPass::record`]. Holds clones of every wgpu object it uses, so it
+/// is `Send` and independent of the pools and managers it was recorded from.
+#[derive(Debug)]
+pub(crate) struct RecordedPass {
+    name: Option<String>,
+    render_target: RenderTarget,
+    multiview_mask: Option<NonZeroU32>,
+    commands: Vec<RenderCommand>,
+}
+
+impl RecordedPass {
+    /// Begins the pass on `encoder` and replays the recorded commands into it.
+    pub(crate) fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = with_descriptor(
+            &self.render_target,
+            self.name.as_deref(),
+            self.multiview_mask,
+            |descriptor| encoder.begin_render_pass(descriptor),
+        );
+
+        for command in &self.commands {
+            match command {
+                RenderCommand::SetViewport(viewport) => {
+                    PassEncoder::set_viewport(&mut render_pass, *viewport)
+                }
+                RenderCommand::SetScissorRect(x, y, width, height) => {
+                    render_pass.set_scissor_rect(*x, *y, *width, *height)
+                }
+                RenderCommand::SetStencilReference(reference) => {
+                    render_pass.set_stencil_reference(*reference)
+                }
+                RenderCommand::ExecuteBundle(bundle) => render_pass.execute_bundles([bundle]),
+                RenderCommand::SetPipeline(pipeline) => render_pass.set_pipeline(pipeline),
+                RenderCommand::SetBindGroup(index, bind_group, offsets) => {
+                    render_pass.set_bind_group(*index, bind_group.as_ref(), offsets)
+                }
+                RenderCommand::SetVertexBuffer(slot, buffer, range) => {
+                    render_pass.set_vertex_buffer(*slot, buffer.slice(range.clone()))
+                }
+                RenderCommand::SetIndexBuffer(buffer, range, format) => {
+                    render_pass.set_index_buffer(buffer.slice(range.clone()), *format)
+                }
+                RenderCommand::SetImmediates(offset, data) => {
+                    render_pass.set_immediates(*offset, data)
+                }
+                RenderCommand::Draw(vertices, instances) => {
+                    render_pass.draw(vertices.clone(), instances.clone())
+                }
+                RenderCommand::DrawIndexed(indices, base_vertex, instances) => {
+                    render_pass.draw_indexed(indices.clone(), *base_vertex, instances.clone())
+                }
+                RenderCommand::DrawIndirect(buffer, offset) => {
+                    render_pass.draw_indirect(buffer, *offset)
+                }
+                RenderCommand::DrawIndexedIndirect(buffer, offset) => {
+                    render_pass.draw_indexed_indirect(buffer, *offset)
+                }
+                #[cfg(feature = "debug-markers")]
+                RenderCommand::PushDebugGroup(label) => render_pass.push_debug_group(label),
+                #[cfg(feature = "debug-markers")]
+                RenderCommand::PopDebugGroup => render_pass.pop_debug_group(),
+            }
         }
-        ord => ord,
-    });
+    }
+}
+
+#[derive(Debug)]
+enum RenderCommand {
+    SetViewport(Viewport),
+    SetScissorRect(u32, u32, u32, u32),
+    SetStencilReference(u32),
+    ExecuteBundle(wgpu::RenderBundle),
+    SetPipeline(wgpu::RenderPipeline),
+    SetBindGroup(u32, Option<wgpu::BindGroup>, SmallVec<[wgpu::DynamicOffset; 2]>),
+    SetVertexBuffer(u32, wgpu::Buffer, Range<wgpu::BufferAddress>),
+    SetIndexBuffer(wgpu::Buffer, Range<wgpu::BufferAddress>, wgpu::IndexFormat),
+    SetImmediates(u32, Box<[u8]>),
+    Draw(Range<u32>, Range<u32>),
+    DrawIndexed(Range<u32>, i32, Range<u32>),
+    DrawIndirect(wgpu::Buffer, wgpu::BufferAddress),
+    DrawIndexedIndirect(wgpu::Buffer, wgpu::BufferAddress),
+    #[cfg(feature = "debug-markers")]
+    PushDebugGroup(String),
+    #[cfg(feature = "debug-markers")]
+    PopDebugGroup,
+}
+
+/// Encoder that stores commands instead of encoding them, see [`RecordedPass`].
+struct CommandRecorder {
+    commands: Vec<RenderCommand>,
+}
+
+fn slice_range(slice: &wgpu::BufferSlice) -> Range<wgpu::BufferAddress> {
+    slice.offset()..slice.offset() + slice.size().get()
+}
 
-    let mut current_pipeline_id = None;
-    let mut current_bind_groups: SmallVec<[Option<&wgpu::BindGroup>; 3]> =
-        SmallVec::from_elem(None, 3);
+impl<'a> RenderEncoder<'a> for CommandRecorder {
+    fn set_bind_group(
+        &mut self,
+        index: u32,
+        bind_group: Option<&'a wgpu::BindGroup>,
+        offsets: &[wgpu::DynamicOffset],
+    ) {
+        self.commands.push(RenderCommand::SetBindGroup(
+            index,
+            bind_group.cloned(),
+            SmallVec::from_slice(offsets),
+        ));
+    }
+
+    fn set_pipeline(&mut self, pipeline: &'a wgpu::RenderPipeline) {
+        self.commands.push(RenderCommand::SetPipeline(pipeline.clone()));
+    }
+
+    fn set_index_buffer(&mut self, buffer_slice: wgpu::BufferSlice<'a>, format: wgpu::IndexFormat) {
+        let buffer = buffer_slice.buffer().clone();
+        self.commands.push(RenderCommand::SetIndexBuffer(
+            buffer,
+            slice_range(&buffer_slice),
+            format,
+        ));
+    }
+
+    fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: wgpu::BufferSlice<'a>) {
+        let buffer = buffer_slice.buffer().clone();
+        self.commands.push(RenderCommand::SetVertexBuffer(
+            slot,
+            buffer,
+            slice_range(&buffer_slice),
+        ));
+    }
+
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.commands.push(RenderCommand::Draw(vertices, instances));
+    }
+
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.commands.push(RenderCommand::DrawIndexed(indices, base_vertex, instances));
+    }
+
+    fn draw_indirect(&mut self, indirect_buffer: &'a wgpu::Buffer, offset: wgpu::BufferAddress) {
+        self.commands.push(RenderCommand::DrawIndirect(indirect_buffer.clone(), offset));
+    }
+
+    fn draw_indexed_indirect(
+        &mut self,
+        indirect_buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        self.commands.push(RenderCommand::DrawIndexedIndirect(indirect_buffer.clone(), offset));
+    }
+
+    fn set_immediates(&mut self, offset: u32, data: &[u8]) {
+        self.commands.push(RenderCommand::SetImmediates(offset, data.into()));
+    }
+}
+
+impl<'a> PassEncoder<'a> for CommandRecorder {
+    fn set_viewport(&mut self, viewport: Viewport) {
+        self.commands.push(RenderCommand::SetViewport(viewport));
+    }
+
+    fn set_scissor_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.commands.push(RenderCommand::SetScissorRect(x, y, width, height));
+    }
+
+    fn set_stencil_reference(&mut self, reference: u32) {
+        self.commands.push(RenderCommand::SetStencilReference(reference));
+    }
+
+    fn execute_bundle(&mut self, bundle: &'a wgpu::RenderBundle) {
+        self.commands.push(RenderCommand::ExecuteBundle(bundle.clone()));
+    }
+
+    #[cfg(feature = "debug-markers")]
+    fn push_debug_group(&mut self, label: &str) {
+        self.commands.push(RenderCommand::PushDebugGroup(label.to_string()));
+    }
+
+    #[cfg(feature = "debug-markers")]
+    fn pop_debug_group(&mut self) {
+        self.commands.push(RenderCommand::PopDebugGroup);
+    }
+}
+
+/// What the draw call executor encoded, e.g. for finding out why an object isn't rendered.
+/// Summed over passes with `+=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    /// Draws encoded into the pass, not counting [`Self::bundled_draws`].
+    pub draws_submitted: u32,
+    /// Draws replayed from the pass's recorded render bundle.
+    pub bundled_draws: u32,
+    /// Draws skipped because their pipeline or a bind group was removed, or their immediate
+    /// data doesn't fit the pipeline layout. Skips over removed resources are only logged at
+    /// debug level, since they repeat every frame until the draw call is dropped.
+    pub draws_skipped: u32,
+    pub pipeline_switches: u32,
+    pub bind_group_switches: u32,
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draws_submitted += other.draws_submitted;
+        self.bundled_draws += other.bundled_draws;
+        self.draws_skipped += other.draws_skipped;
+        self.pipeline_switches += other.pipeline_switches;
+        self.bind_group_switches += other.bind_group_switches;
+    }
+}
+
+/// `target_size` is what draws without a scissor rectangle are clipped to and what scissor
+/// rectangles are clamped to. Draws without a stencil reference use 0. Draws referencing a
+/// removed pipeline or bind group are skipped and counted in [`RenderStats::draws_skipped`].
+/// With the `debug-markers` feature every run of draws sharing a pipeline is wrapped in a debug
+/// group named after the pipeline's [`crate::Material::label`], or its handle without one.
+/// Point and line geometry is drawn like triangles; the topology comes from each draw's
+/// pipeline, which has to match [`crate::Geometry::topology`]. Returns counters of what was
+/// encoded and skipped.
+pub fn execute_ordered_draw_calls<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    target_size: (u32, u32),
+    draw_calls: &'a mut [DrawCall],
+    immediate_manager: &ImmediateManager,
+    render_pipelines: &'a ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: &'a ResourcePool<wgpu::BindGroup>,
+) -> RenderStats {
+    encode_ordered_draw_calls(
+        render_pass,
+        target_size,
+        draw_calls,
+        immediate_manager,
+        render_pipelines,
+        bind_groups,
+    )
+}
+
+fn encode_ordered_draw_calls<'a>(
+    render_pass: &mut impl PassEncoder<'a>,
+    target_size: (u32, u32),
+    draw_calls: &'a mut [DrawCall],
+    immediate_manager: &ImmediateManager,
+    render_pipelines: &'a ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: &'a ResourcePool<wgpu::BindGroup>,
+) -> RenderStats {
+    sort_draw_calls(draw_calls);
+    encode_sorted_draw_calls(
+        render_pass,
+        target_size,
+        &*draw_calls,
+        immediate_manager,
+        render_pipelines,
+        bind_groups,
+    )
+}
+
+/// Sorts in [`compare_draw_calls`] order. Stable, so draws with equal keys keep their submission
+/// order from frame to frame. Draw lists kept in order through [`RenderPass::push_draw_call`]
+/// skip the sort entirely.
+fn sort_draw_calls(draw_calls: &mut [DrawCall]) {
+    if !draw_calls.is_sorted_by(|a, b| compare_draw_calls(a, b).is_le()) {
+        draw_calls.sort_by(compare_draw_calls);
+    }
+}
+
+fn encode_sorted_draw_calls<'a>(
+    render_pass: &mut impl PassEncoder<'a>,
+    target_size: (u32, u32),
+    draw_calls: impl IntoIterator<Item = &'a DrawCall>,
+    immediate_manager: &ImmediateManager,
+    render_pipelines: &'a ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: &'a ResourcePool<wgpu::BindGroup>,
+) -> RenderStats {
+    let mut state = EncoderState::new();
+    // A pass starts with the scissor covering the whole target
+    let full_scissor = (0, 0, target_size.0, target_size.1);
+    let mut current_scissor = full_scissor;
+    let mut current_stencil_reference = 0;
+    #[cfg(feature = "debug-markers")]
+    let mut current_pipeline = None;
 
     for draw_call in draw_calls {
-        // 1. Set pipeline
-        if current_pipeline_id != Some(&draw_call.render_pipeline_handle) {
-            render_pass.set_pipeline(&draw_call.render_pipeline_handle);
-            current_pipeline_id = Some(&draw_call.render_pipeline_handle);
-            // Reset bind groups cache because new pipeline might have different layouts
-            current_bind_groups.fill(None);
-        }
-
-        // 2. Set bind groups
-        for (i, bind_group) in draw_call.shader_data.bind_groups.iter().enumerate() {
-            if i >= current_bind_groups.len()
-                || current_bind_groups[i].is_none_or(|b| *b != *bind_group)
-            {
-                render_pass.set_bind_group(i as u32, bind_group, &[]);
-
-                if i < current_bind_groups.len() {
-                    current_bind_groups[i] = Some(bind_group);
+        #[cfg(feature = "debug-markers")]
+        if current_pipeline != Some(draw_call.render_pipeline_handle) {
+            if current_pipeline.is_some() {
+                render_pass.pop_debug_group();
+            }
+            let handle = draw_call.render_pipeline_handle;
+            match immediate_manager.pipeline_label(handle) {
+                Some(label) => render_pass.push_debug_group(label),
+                None => {
+                    render_pass.push_debug_group(&format!("pipeline {}", handle.id().as_usize()))
                 }
             }
+            current_pipeline = Some(handle);
+        }
+
+        let scissor =
+            draw_call.scissor.map_or(full_scissor, |rect| clamp_scissor(rect, target_size));
+        if current_scissor != scissor {
+            let (x, y, width, height) = scissor;
+            render_pass.set_scissor_rect(x, y, width, height);
+            current_scissor = scissor;
+        }
+
+        let stencil_reference = draw_call.stencil_reference.unwrap_or(0);
+        if current_stencil_reference != stencil_reference {
+            render_pass.set_stencil_reference(stencil_reference);
+            current_stencil_reference = stencil_reference;
+        }
+
+        encode_draw_call(
+            render_pass,
+            &mut state,
+            draw_call,
+            immediate_manager,
+            render_pipelines,
+            bind_groups,
+        );
+    }
+
+    #[cfg(feature = "debug-markers")]
+    if current_pipeline.is_some() {
+        render_pass.pop_debug_group();
+    }
+    state.stats
+}
+
+/// Clips `(x, y, width, height)` to the target, since wgpu rejects rectangles reaching past it.
+fn clamp_scissor(
+    (x, y, width, height): (u32, u32, u32, u32),
+    (target_width, target_height): (u32, u32),
+) -> (u32, u32, u32, u32) {
+    let x = x.min(target_width);
+    let y = y.min(target_height);
+    (x, y, width.min(target_width - x), height.min(target_height - y))
+}
+
+/// Order of draw calls within a pass, grouping them to minimize state changes: pipeline, then
+/// bind groups. Used by every path that sorts draw calls. Same as comparing
+/// [`DrawCall::sort_key`]s, without cloning the bind groups.
+pub fn compare_draw_calls(a: &DrawCall, b: &DrawCall) -> Ordering {
+    match a.render_pipeline_handle.cmp(&b.render_pipeline_handle) {
+        Ordering::Equal => a.shader_data.bind_groups.iter().cmp(b.shader_data.bind_groups.iter()),
+        ord => ord,
+    }
+}
+
+/// State already set on the encoder, used to skip redundant calls.
+struct EncoderState {
+    pipeline: Option<Handle<wgpu::RenderPipeline>>,
+    /// One slot per bind group index, grown to the most bind groups a draw has used.
+    bind_groups: SmallVec<[Option<Handle<wgpu::BindGroup>>; 3]>,
+    stats: RenderStats,
+}
+
+impl EncoderState {
+    fn new() -> Self {
+        Self {
+            pipeline: None,
+            bind_groups: SmallVec::new(),
+            stats: RenderStats::default(),
         }
+    }
+}
 
-        // 3. Set vertex/index buffers
-        for (i, (buffer, range)) in draw_call.geometry.buffers.iter().enumerate() {
-            let r = range.as_ref().map_or(0..buffer.size(), |r| (r.start as u64)..(r.end as u64));
-            render_pass.set_vertex_buffer(i as u32, buffer.slice(r));
+fn encode_draw_call<'a>(
+    encoder: &mut impl RenderEncoder<'a>,
+    state: &mut EncoderState,
+    draw_call: &'a DrawCall,
+    immediate_manager: &ImmediateManager,
+    render_pipelines: &'a ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: &'a ResourcePool<wgpu::BindGroup>,
+) {
+    // Handles outlive the resources they point to, such draws are skipped as a whole
+    let Some(pipeline) = render_pipelines.get(draw_call.render_pipeline_handle) else {
+        log::debug!("Skipping draw call with a stale render pipeline handle");
+        state.stats.draws_skipped += 1;
+        return;
+    };
+    let Some(resolved_bind_groups) = draw_call
+        .shader_data
+        .bind_groups
+        .iter()
+        .map(|&handle| bind_groups.get(handle))
+        .collect::<Option<SmallVec<[_; 3]>>>()
+    else {
+        log::debug!("Skipping draw call with a stale bind group handle");
+        state.stats.draws_skipped += 1;
+        return;
+    };
+    let immediates = draw_call
+        .shader_data
+        .immediates
+        .as_ref()
+        .and_then(|immediate| immediate_manager.get(immediate.id));
+    if let Some(bytes) = immediates
+        && let Err(e) =
+            immediate_manager.validate_draw(draw_call.render_pipeline_handle, bytes.len())
+    {
+        log::error!("Skipping draw call: {e}");
+        state.stats.draws_skipped += 1;
+        return;
+    }
+
+    // 1. Set pipeline
+    if state.pipeline != Some(draw_call.render_pipeline_handle) {
+        encoder.set_pipeline(pipeline);
+        state.pipeline = Some(draw_call.render_pipeline_handle);
+        state.stats.pipeline_switches += 1;
+        // Reset bind groups cache because new pipeline might have different layouts
+        state.bind_groups.fill(None);
+    }
+
+    // 2. Set bind groups
+    let handles = &draw_call.shader_data.bind_groups;
+    if state.bind_groups.len() < handles.len() {
+        state.bind_groups.resize(handles.len(), None);
+    }
+    for (i, (&handle, bind_group)) in handles.iter().zip(resolved_bind_groups).enumerate() {
+        if state.bind_groups[i] != Some(handle) {
+            encoder.set_bind_group(i as u32, Some(bind_group), &[]);
+            state.bind_groups[i] = Some(handle);
+            state.stats.bind_group_switches += 1;
         }
+    }
+
+    // 3. Set vertex/index buffers
+    for (i, vertex_buffer) in draw_call.geometry.buffers.iter().enumerate() {
+        let buffer = &vertex_buffer.buffer;
+        let r = vertex_buffer
+            .range
+            .as_ref()
+            .map_or(0..buffer.size(), |r| (r.start as u64)..(r.end as u64));
+        encoder.set_vertex_buffer(i as u32, buffer.slice(r));
+    }
+
+    if let Some(bytes) = immediates {
+        // The pipeline layout declares a single block starting at offset 0
+        encoder.set_immediates(0, bytes);
+    }
+
+    let instance_count =
+        draw_call.geometry.instance_count.unwrap_or(draw_call.instance_count.get());
+
+    if let Some(index_buffer) = &draw_call.geometry.index_buffer {
+        let r = draw_call
+            .geometry
+            .index_buffer_range
+            .as_ref()
+            .map_or(0..index_buffer.size(), |r| (r.start as u64)..(r.end as u64));
+
+        encoder.set_index_buffer(index_buffer.slice(r), draw_call.geometry.index_format);
+        encoder.draw_indexed(
+            0..draw_call.geometry.count,
+            draw_call.geometry.base_vertex,
+            0..instance_count,
+        );
+    } else {
+        encoder.draw(0..draw_call.geometry.count, 0..instance_count);
+    }
+    state.stats.draws_submitted += 1;
+}
+
+/// Whether the draw call only uses state that a render bundle can hold.
+fn is_bundleable(draw_call: &DrawCall) -> bool {
+    draw_call.scissor.is_none() && draw_call.stencil_reference.is_none()
+}
+
+fn handles_alive(
+    draw_call: &DrawCall,
+    render_pipelines: &ResourcePool<wgpu::RenderPipeline>,
+    bind_groups: &ResourcePool<wgpu::BindGroup>,
+) -> bool {
+    render_pipelines.is_alive(draw_call.render_pipeline_handle)
+        && draw_call.shader_data.bind_groups.iter().all(|&handle| bind_groups.is_alive(handle))
+}
 
-        if let Some(immediate) = &draw_call.shader_data.immediates {
-            if let Some(bytes) = immediate_manager.get(immediate.id) {
-                render_pass.set_immediates(0, bytes);
+/// Identifies the bundleable draw calls regardless of their order.
+fn bundle_fingerprint(draw_calls: &[DrawCall]) -> u64 {
+    draw_calls
+        .iter()
+        .filter(|draw_call| is_bundleable(draw_call))
+        .map(|draw_call| {
+            let mut hasher = FxHasher::default();
+            draw_call.render_pipeline_handle.hash(&mut hasher);
+            draw_call.shader_data.bind_groups.hash(&mut hasher);
+            draw_call.shader_data.immediates.as_ref().map(|i| i.id).hash(&mut hasher);
+            draw_call.instance_count.hash(&mut hasher);
+
+            let geometry = &draw_call.geometry;
+            geometry.index_buffer.hash(&mut hasher);
+            geometry.index_buffer_range.hash(&mut hasher);
+            geometry.index_format.hash(&mut hasher);
+            geometry.base_vertex.hash(&mut hasher);
+            geometry.count.hash(&mut hasher);
+            geometry.instance_count.hash(&mut hasher);
+            for vertex_buffer in &geometry.buffers {
+                vertex_buffer.buffer.hash(&mut hasher);
+                vertex_buffer.range.hash(&mut hasher);
             }
+            hasher.finish()
+        })
+        .fold(0, u64::wrapping_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Geometry, Material, Renderer, ShaderData, tests};
+
+    fn draw_call(pipeline: Handle<wgpu::RenderPipeline>) -> DrawCall {
+        DrawCall {
+            geometry: Geometry::procedural(3),
+            shader_data: ShaderData {
+                immediates: None,
+                bind_groups: SmallVec::new(),
+            },
+            instance_count: NonZeroU32::MIN,
+            render_pipeline_handle: pipeline,
+            scissor: None,
+            stencil_reference: None,
         }
+    }
 
-        if let Some(index_buffer) = &draw_call.geometry.index_buffer {
-            let r = draw_call
-                .geometry
-                .index_buffer_range
-                .as_ref()
-                .map_or(0..index_buffer.size(), |r| (r.start as u64)..(r.end as u64));
+    fn record(renderer: &Renderer, pass: &mut RenderPass) -> (RecordedPass, RenderStats) {
+        let immediate_manager = renderer.immeadiate_manager.borrow();
+        pass.record(&immediate_manager, &renderer.render_pipelines, &renderer.bind_groups).unwrap()
+    }
 
-            render_pass.set_index_buffer(index_buffer.slice(r), draw_call.geometry.index_format);
-            render_pass.draw_indexed(
-                0..draw_call.geometry.count,
-                0,
-                0..draw_call.instance_count.get(),
-            );
-        } else {
-            render_pass.draw(0..draw_call.geometry.count, 0..draw_call.instance_count.get());
+    #[test]
+    fn scissor_resets_between_scissored_draws_and_is_clamped() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        for scissor in [Some((16, 8, 100, 4)), None, Some((16, 8, 100, 4)), None] {
+            pass.draw_calls.push(DrawCall {
+                scissor,
+                ..draw_call(pipeline)
+            });
+        }
+        let (recorded, stats) = record(&renderer, &mut pass);
+
+        let scissors: Vec<_> = recorded
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                RenderCommand::SetScissorRect(x, y, width, height) => {
+                    Some((*x, *y, *width, *height))
+                }
+                _ => None,
+            })
+            .collect();
+        let (clamped, full) = ((16, 8, 48, 4), (0, 0, 64, 32));
+        assert_eq!(scissors, [clamped, full, clamped, full]);
+        assert_eq!(stats.draws_submitted, 4);
+    }
+
+    #[test]
+    fn passes_into_one_target_keep_their_viewports() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        // Split screen: the left and right halves of the same target
+        let halves = [Viewport::new(0.0, 0.0, 32.0, 32.0), Viewport::new(32.0, 0.0, 32.0, 32.0)];
+        for viewport in halves {
+            let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+            pass.viewport = Some(viewport);
+            pass.draw_calls.push(draw_call(pipeline));
+            let (recorded, _) = record(&renderer, &mut pass);
+
+            let viewports: Vec<_> = recorded
+                .commands
+                .iter()
+                .filter_map(|command| match command {
+                    RenderCommand::SetViewport(viewport) => Some(*viewport),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(viewports, [viewport]);
+        }
+    }
+
+    #[test]
+    fn stale_handles_skip_the_draw() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        // A different material, so the pipeline isn't shared through the cache
+        let removed = renderer.create_render_pipeline(&Material {
+            multisample: wgpu::MultisampleState {
+                mask: 1,
+                ..Default::default()
+            },
+            ..tests::material(format)
+        });
+        renderer.remove_render_pipeline(removed);
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        pass.draw_calls.extend([draw_call(removed), draw_call(pipeline), draw_call(removed)]);
+        let (recorded, stats) = record(&renderer, &mut pass);
+
+        assert_eq!(stats.draws_skipped, 2);
+        assert_eq!(stats.draws_submitted, 1);
+        let draws = recorded.commands.iter().filter(|c| matches!(c, RenderCommand::Draw(..)));
+        assert_eq!(draws.count(), 1);
+    }
+
+    #[cfg(feature = "debug-markers")]
+    #[test]
+    fn debug_groups_use_material_labels() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        pass.draw_calls.push(draw_call(pipeline));
+        let (recorded, _) = record(&renderer, &mut pass);
+
+        let groups: Vec<_> = recorded
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                RenderCommand::PushDebugGroup(label) => Some(label.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(groups, ["Test"]);
+    }
+
+    #[test]
+    fn bundled_passes_keep_submission_order() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        // Equal keys, told apart by their instance counts; the scissored ones can't be bundled.
+        // Bundles validate vertex ranges, so the draws need a real vertex buffer.
+        let points = Geometry::point_cloud(renderer.context().device(), &[glam::Vec3::ZERO; 3]);
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        for (instances, scissor) in
+            [(1, Some((0, 0, 8, 8))), (2, None), (3, Some((0, 0, 8, 8))), (4, None)]
+        {
+            pass.draw_calls.push(DrawCall {
+                geometry: points.clone(),
+                instance_count: NonZeroU32::new(instances).unwrap(),
+                scissor,
+                ..draw_call(pipeline)
+            });
+        }
+        let immediate_manager = renderer.immeadiate_manager.borrow();
+        pass.cache_bundle(
+            renderer.context().device(),
+            &immediate_manager,
+            &renderer.render_pipelines,
+            &renderer.bind_groups,
+        );
+        drop(immediate_manager);
+        let (recorded, stats) = record(&renderer, &mut pass);
+
+        let instances: Vec<_> = recorded
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                RenderCommand::Draw(_, instances) => Some(instances.end),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(instances, [1, 3]);
+        assert_eq!(stats.bundled_draws, 2);
+        let order: Vec<_> = pass.draw_calls.iter().map(|d| d.instance_count.get()).collect();
+        assert_eq!(order, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn identical_draws_bind_each_group_once() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&Material {
+            bind_groups: vec![vec![]; 4],
+            ..tests::material(format)
+        });
+        let bind_groups: SmallVec<_> =
+            (0..4).map(|_| renderer.create_bindgroup(&[], &[])).collect();
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        for _ in 0..3 {
+            let mut draw_call = draw_call(pipeline);
+            draw_call.shader_data.bind_groups = bind_groups.clone();
+            pass.draw_calls.push(draw_call);
+        }
+        let (recorded, stats) = record(&renderer, &mut pass);
+
+        let slots: Vec<_> = recorded
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                RenderCommand::SetBindGroup(index, ..) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(slots, [0, 1, 2, 3]);
+        assert_eq!(stats.bind_group_switches, 4);
+        assert_eq!(stats.draws_submitted, 3);
+    }
+
+    #[test]
+    fn bundles_over_removed_pipelines_are_dropped() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        let points = Geometry::point_cloud(renderer.context().device(), &[glam::Vec3::ZERO; 3]);
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        pass.draw_calls.push(DrawCall {
+            geometry: points,
+            ..draw_call(pipeline)
+        });
+        let immediate_manager = renderer.immeadiate_manager.borrow();
+        pass.cache_bundle(
+            renderer.context().device(),
+            &immediate_manager,
+            &renderer.render_pipelines,
+            &renderer.bind_groups,
+        );
+        drop(immediate_manager);
+        renderer.remove_render_pipeline(pipeline);
+        let (recorded, stats) = record(&renderer, &mut pass);
+
+        assert!(pass.bundle.is_none());
+        assert!(!recorded.commands.iter().any(|c| matches!(c, RenderCommand::ExecuteBundle(_))));
+        assert_eq!(stats.bundled_draws, 0);
+        assert_eq!(stats.draws_skipped, 1);
+    }
+
+    #[test]
+    fn bundles_report_what_they_recorded() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = renderer.create_render_pipeline(&tests::material(format));
+        let render_target = RenderTarget::builder(64, 32, format)
+            .depth_format(None)
+            .build(renderer.context().device());
+
+        // The second draw's immediate data is larger than the pipeline declares
+        let points = Geometry::point_cloud(renderer.context().device(), &[glam::Vec3::ZERO; 3]);
+        let mut pass = RenderPass::clear(&render_target, wgpu::Color::BLACK, 1.0);
+        for size in [64, 128] {
+            let mut draw_call = DrawCall {
+                geometry: points.clone(),
+                ..draw_call(pipeline)
+            };
+            draw_call.shader_data.immediates = Some(renderer.create_immediate(size));
+            pass.draw_calls.push(draw_call);
         }
+        let immediate_manager = renderer.immeadiate_manager.borrow();
+        pass.cache_bundle(
+            renderer.context().device(),
+            &immediate_manager,
+            &renderer.render_pipelines,
+            &renderer.bind_groups,
+        );
+        drop(immediate_manager);
+        let bundle = pass.bundle.as_ref().unwrap();
+        assert_eq!((bundle.draws(), bundle.draws_skipped()), (1, 1));
+
+        let (_, stats) = record(&renderer, &mut pass);
+        assert_eq!(stats.bundled_draws, 1);
+        assert_eq!(stats.draws_skipped, 1);
+        assert_eq!(stats.draws_submitted, 0);
     }
 }
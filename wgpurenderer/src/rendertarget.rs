@@ -0,0 +1,518 @@
+use smallvec::SmallVec;
+
+#[derive(Debug, Clone)]
+pub struct ColorAttachment {
+    pub view: wgpu::TextureView,
+    pub depth_slice: Option<u32>,
+    pub resolve_target: Option<wgpu::TextureView>,
+    pub ops: wgpu::Operations<wgpu::Color>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DepthStencilAttachment {
+    pub view: wgpu::TextureView,
+    pub depth_ops: Option<wgpu::Operations<f32>>,
+    pub stencil_ops: Option<wgpu::Operations<u32>>,
+}
+
+impl ColorAttachment {
+    /// Attachment rendering into `view`, cleared to transparent black and stored.
+    pub fn builder(view: wgpu::TextureView) -> ColorAttachmentBuilder {
+        ColorAttachmentBuilder {
+            attachment: ColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            },
+        }
+    }
+}
+
+impl DepthStencilAttachment {
+    /// Attachment for `view` clearing depth to 1.0 and stencil to 0 and storing both, for the
+    /// aspects its format has.
+    pub fn builder(view: wgpu::TextureView) -> DepthStencilAttachmentBuilder {
+        let format = view.texture().format();
+        DepthStencilAttachmentBuilder {
+            attachment: DepthStencilAttachment {
+                depth_ops: format.has_depth_aspect().then_some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: format.has_stencil_aspect().then_some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                view,
+            },
+        }
+    }
+}
+
+/// Sets the load and store operations of a [`ColorAttachment`], e.g. loading for passes that
+/// accumulate into the previous contents.
+#[derive(Debug, Clone)]
+pub struct ColorAttachmentBuilder {
+    attachment: ColorAttachment,
+}
+
+impl ColorAttachmentBuilder {
+    pub fn clear(mut self, color: wgpu::Color) -> Self {
+        self.attachment.ops.load = wgpu::LoadOp::Clear(color);
+        self
+    }
+
+    /// Keeps the previous contents.
+    pub fn load(mut self) -> Self {
+        self.attachment.ops.load = wgpu::LoadOp::Load;
+        self
+    }
+
+    pub fn store(mut self) -> Self {
+        self.attachment.ops.store = wgpu::StoreOp::Store;
+        self
+    }
+
+    /// Drops the rendered contents after the pass, e.g. for a multisampled texture that is
+    /// only needed until it's resolved.
+    pub fn discard(mut self) -> Self {
+        self.attachment.ops.store = wgpu::StoreOp::Discard;
+        self
+    }
+
+    pub fn resolve_target(mut self, view: wgpu::TextureView) -> Self {
+        self.attachment.resolve_target = Some(view);
+        self
+    }
+
+    pub fn depth_slice(mut self, depth_slice: u32) -> Self {
+        self.attachment.depth_slice = Some(depth_slice);
+        self
+    }
+
+    pub fn build(self) -> ColorAttachment {
+        self.attachment
+    }
+}
+
+/// Sets the load and store operations of a [`DepthStencilAttachment`]. Operations on an
+/// aspect the format lacks are ignored.
+#[derive(Debug, Clone)]
+pub struct DepthStencilAttachmentBuilder {
+    attachment: DepthStencilAttachment,
+}
+
+impl DepthStencilAttachmentBuilder {
+    pub fn clear_depth(mut self, depth: f32) -> Self {
+        if let Some(ops) = &mut self.attachment.depth_ops {
+            ops.load = wgpu::LoadOp::Clear(depth);
+        }
+        self
+    }
+
+    /// Keeps the previous depth, e.g. after a depth pre-pass.
+    pub fn load_depth(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.depth_ops {
+            ops.load = wgpu::LoadOp::Load;
+        }
+        self
+    }
+
+    pub fn store_depth(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.depth_ops {
+            ops.store = wgpu::StoreOp::Store;
+        }
+        self
+    }
+
+    pub fn discard_depth(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.depth_ops {
+            ops.store = wgpu::StoreOp::Discard;
+        }
+        self
+    }
+
+    pub fn clear_stencil(mut self, stencil: u32) -> Self {
+        if let Some(ops) = &mut self.attachment.stencil_ops {
+            ops.load = wgpu::LoadOp::Clear(stencil);
+        }
+        self
+    }
+
+    pub fn load_stencil(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.stencil_ops {
+            ops.load = wgpu::LoadOp::Load;
+        }
+        self
+    }
+
+    pub fn store_stencil(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.stencil_ops {
+            ops.store = wgpu::StoreOp::Store;
+        }
+        self
+    }
+
+    pub fn discard_stencil(mut self) -> Self {
+        if let Some(ops) = &mut self.attachment.stencil_ops {
+            ops.store = wgpu::StoreOp::Discard;
+        }
+        self
+    }
+
+    pub fn build(self) -> DepthStencilAttachment {
+        self.attachment
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderTarget {
+    pub color_attachments: SmallVec<[ColorAttachment; 1]>,
+    pub depth_stencil_attachment: Option<DepthStencilAttachment>,
+}
+
+impl RenderTarget {
+    pub fn builder(width: u32, height: u32, format: wgpu::TextureFormat) -> RenderTargetBuilder {
+        RenderTargetBuilder::new(width, height, format)
+    }
+
+    /// Allocates a surface-less color target (readable via `COPY_SRC`) with a `Depth32Float`
+    /// depth attachment. Both are cleared on load.
+    pub fn offscreen(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        RenderTargetBuilder::new(width, height, format).build(device)
+    }
+
+    /// Recreates every attachment texture at the new size, keeping format, sample count and
+    /// usage. Views are recreated with the default descriptor. Zero sizes, e.g. of a minimized
+    /// window, and the current size are ignored.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if width == 0 || height == 0 || self.size() == Some((width, height)) {
+            return;
+        }
+
+        for attachment in &mut self.color_attachments {
+            attachment.view = resized_view(device, &attachment.view, width, height);
+            if let Some(resolve_target) = &mut attachment.resolve_target {
+                *resolve_target = resized_view(device, resolve_target, width, height);
+            }
+        }
+
+        if let Some(attachment) = &mut self.depth_stencil_attachment {
+            attachment.view = resized_view(device, &attachment.view, width, height);
+        }
+    }
+
+    /// Format of the depth-stencil attachment, which the `DepthStencilState` of the pipelines
+    /// drawing into the target has to use.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        Some(self.depth_stencil_attachment.as_ref()?.view.texture().format())
+    }
+
+    /// Format of every color attachment, in `@location` order.
+    pub fn color_formats(&self) -> SmallVec<[wgpu::TextureFormat; 4]> {
+        self.color_attachments.iter().map(|attachment| attachment.view.texture().format()).collect()
+    }
+
+    /// Sample count of the attachments, which the pipelines drawing into the target have to
+    /// use in their `MultisampleState`.
+    pub fn sample_count(&self) -> u32 {
+        let view = match self.color_attachments.first() {
+            Some(attachment) => &attachment.view,
+            None => match &self.depth_stencil_attachment {
+                Some(attachment) => &attachment.view,
+                None => return 1,
+            },
+        };
+        view.texture().sample_count()
+    }
+
+    /// Size of the first attachment's texture, color before depth.
+    pub fn size(&self) -> Option<(u32, u32)> {
+        let view = match self.color_attachments.first() {
+            Some(attachment) => &attachment.view,
+            None => &self.depth_stencil_attachment.as_ref()?.view,
+        };
+        let size = view.texture().size();
+        Some((size.width, size.height))
+    }
+
+    /// Reads the first color attachment back to the CPU, preferring its resolve target when
+    /// multisampled.
+    #[cfg(feature = "image")]
+    pub fn read_to_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<image::RgbaImage, crate::readback::ReadbackError> {
+        let attachment = self
+            .color_attachments
+            .first()
+            .ok_or(crate::readback::ReadbackError::MissingAttachment)?;
+        let view = attachment.resolve_target.as_ref().unwrap_or(&attachment.view);
+
+        crate::readback::read_rgba_image(device, queue, view.texture())
+    }
+}
+
+/// Creates the color, resolve and depth textures of a [`RenderTarget`]. With a sample count
+/// above 1 the color texture is multisampled and a single-sampled resolve texture is wired in
+/// as its resolve target, unless disabled to resolve into e.g. the surface instead.
+/// [`RenderTargetBuilder::depth_only`] creates targets without color, e.g. for shadow maps.
+#[derive(Debug, Clone)]
+pub struct RenderTargetBuilder {
+    width: u32,
+    height: u32,
+    formats: Vec<wgpu::TextureFormat>,
+    sample_count: u32,
+    resolve: bool,
+    depth_format: Option<wgpu::TextureFormat>,
+    stencil: bool,
+    clear_color: wgpu::Color,
+    usage: wgpu::TextureUsages,
+    depth_usage: wgpu::TextureUsages,
+}
+
+impl RenderTargetBuilder {
+    pub fn new(width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            formats: vec![format],
+            sample_count: 1,
+            resolve: true,
+            depth_format: Some(wgpu::TextureFormat::Depth32Float),
+            stencil: false,
+            clear_color: wgpu::Color::TRANSPARENT,
+            usage: wgpu::TextureUsages::COPY_SRC,
+            depth_usage: wgpu::TextureUsages::empty(),
+        }
+    }
+
+    /// Target with only a depth attachment of `depth_format`, sampleable through
+    /// `TEXTURE_BINDING`. Pipelines drawing into it need no fragment targets, see
+    /// [`crate::Material::depth_only`].
+    pub fn depth_only(width: u32, height: u32, depth_format: wgpu::TextureFormat) -> Self {
+        Self {
+            formats: vec![],
+            depth_format: Some(depth_format),
+            depth_usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            ..Self::new(width, height, depth_format)
+        }
+    }
+
+    /// Adds another color attachment after the existing ones, written by the fragment output
+    /// at the next `@location`. Used for G-buffers and other multiple render target passes.
+    pub fn color_attachment(mut self, format: wgpu::TextureFormat) -> Self {
+        self.formats.push(format);
+        self
+    }
+
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Lowers the sample count to the highest one every attachment format supports on the
+    /// adapter of `context`, see [`crate::Context::supported_sample_count`].
+    pub fn supported_sample_count(mut self, context: &crate::Context) -> Self {
+        let mut formats = self.formats.clone();
+        formats.extend(self.depth_format);
+        self.sample_count = context.supported_sample_count(&formats, self.sample_count);
+        self
+    }
+
+    /// Whether to allocate a resolve texture for a multisampled target.
+    pub fn resolve(mut self, resolve: bool) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    /// `None` leaves the target without a depth attachment.
+    pub fn depth_format(mut self, format: Option<wgpu::TextureFormat>) -> Self {
+        self.depth_format = format;
+        self
+    }
+
+    /// Replaces the depth format with one the device supports, see
+    /// [`crate::Context::supported_depth_format`].
+    pub fn supported_depth_format(mut self, context: &crate::Context) -> Self {
+        self.depth_format = self.depth_format.map(|format| context.supported_depth_format(format));
+        self
+    }
+
+    /// Clears and stores a stencil aspect. A depth format without one is replaced with
+    /// `Depth24PlusStencil8`.
+    pub fn stencil(mut self, stencil: bool) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    pub fn clear_color(mut self, color: wgpu::Color) -> Self {
+        self.clear_color = color;
+        self
+    }
+
+    /// Extra usage of the single-sampled texture holding the final image, i.e. the resolve
+    /// texture when multisampled. `RENDER_ATTACHMENT` is always added.
+    pub fn usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Extra usage of the depth texture, e.g. `COPY_SRC` for
+    /// [`crate::readback::pick_world_position`]. `RENDER_ATTACHMENT` is always added.
+    pub fn depth_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.depth_usage = usage;
+        self
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> RenderTarget {
+        let multisampled = self.sample_count > 1;
+        let resolved = multisampled && self.resolve;
+
+        let color_usage = if multisampled {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | self.usage
+        };
+        let color_label = if multisampled {
+            "MSAA Color Texture"
+        } else {
+            "Color Texture"
+        };
+
+        let color_attachments = self
+            .formats
+            .iter()
+            .map(|&format| ColorAttachment {
+                view: self.create_view(device, color_label, format, self.sample_count, color_usage),
+                depth_slice: None,
+                resolve_target: resolved.then(|| {
+                    self.create_view(
+                        device,
+                        "Resolve Texture",
+                        format,
+                        1,
+                        wgpu::TextureUsages::RENDER_ATTACHMENT | self.usage,
+                    )
+                }),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+            .collect();
+
+        let depth_format = match self.depth_format {
+            Some(format) if self.stencil && !format.has_stencil_aspect() => {
+                Some(wgpu::TextureFormat::Depth24PlusStencil8)
+            }
+            None if self.stencil => Some(wgpu::TextureFormat::Depth24PlusStencil8),
+            format => format,
+        };
+
+        let depth_stencil_attachment = depth_format.map(|format| DepthStencilAttachment {
+            view: self.create_view(
+                device,
+                "Depth Texture",
+                format,
+                self.sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | self.depth_usage,
+            ),
+            depth_ops: format.has_depth_aspect().then_some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: (self.stencil && format.has_stencil_aspect()).then_some(
+                wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Store,
+                },
+            ),
+        });
+
+        RenderTarget {
+            color_attachments,
+            depth_stencil_attachment,
+        }
+    }
+
+    fn create_view(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
+
+fn resized_view(
+    device: &wgpu::Device,
+    view: &wgpu::TextureView,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    let texture = view.texture();
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            ..texture.size()
+        },
+        mip_level_count: texture.mip_level_count(),
+        sample_count: texture.sample_count(),
+        dimension: texture.dimension(),
+        format: texture.format(),
+        usage: texture.usage(),
+        view_formats: &[],
+    });
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn resize_ignores_zero_sizes() {
+        let context = Context::noop();
+        let mut render_target =
+            RenderTarget::offscreen(context.device(), 64, 32, wgpu::TextureFormat::Rgba8Unorm);
+
+        render_target.resize(context.device(), 0, 0);
+        assert_eq!(render_target.size(), Some((64, 32)));
+
+        render_target.resize(context.device(), 128, 16);
+        assert_eq!(render_target.size(), Some((128, 16)));
+        let depth = render_target.depth_stencil_attachment.as_ref().unwrap();
+        assert_eq!(depth.view.texture().width(), 128);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{binding::Binding, utils::InstanceCounter};
+
+#[derive(Debug)]
+pub struct Sampler {
+    sampler: wgpu::Sampler,
+    binding_type: wgpu::SamplerBindingType,
+    counter: InstanceCounter,
+}
+
+impl Sampler {
+    pub fn builder() -> SamplerBuilder {
+        SamplerBuilder::new()
+    }
+
+    /// Trilinear, repeating sampler for regular material textures.
+    pub fn linear_repeat(device: &wgpu::Device) -> Self {
+        SamplerBuilder::new()
+            .address_mode(wgpu::AddressMode::Repeat)
+            .filter(wgpu::FilterMode::Linear, wgpu::MipmapFilterMode::Linear)
+            .build(device)
+    }
+
+    /// Point sampler clamped to the edge, e.g. for lookup tables and pixel art.
+    pub fn nearest_clamp(device: &wgpu::Device) -> Self {
+        SamplerBuilder::new().build(device)
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+impl Binding for Sampler {
+    fn binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::Sampler(self.binding_type)
+    }
+
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Sampler(&self.sampler)
+    }
+
+    fn counter(&self) -> Option<&InstanceCounter> {
+        Some(&self.counter)
+    }
+}
+
+impl Clone for Sampler {
+    fn clone(&self) -> Self {
+        self.counter.increment();
+
+        Self {
+            sampler: self.sampler.clone(),
+            binding_type: self.binding_type,
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.counter.decrement();
+    }
+}
+
+/// Defaults to a nearest, clamp-to-edge sampler without comparison.
+#[derive(Debug, Clone)]
+pub struct SamplerBuilder {
+    descriptor: wgpu::SamplerDescriptor<'static>,
+}
+
+impl SamplerBuilder {
+    pub fn new() -> Self {
+        Self {
+            descriptor: wgpu::SamplerDescriptor::default(),
+        }
+    }
+
+    /// Sets the same address mode for all three coordinates.
+    pub fn address_mode(mut self, mode: wgpu::AddressMode) -> Self {
+        self.descriptor.address_mode_u = mode;
+        self.descriptor.address_mode_v = mode;
+        self.descriptor.address_mode_w = mode;
+        self
+    }
+
+    pub fn address_modes(
+        mut self,
+        u: wgpu::AddressMode,
+        v: wgpu::AddressMode,
+        w: wgpu::AddressMode,
+    ) -> Self {
+        self.descriptor.address_mode_u = u;
+        self.descriptor.address_mode_v = v;
+        self.descriptor.address_mode_w = w;
+        self
+    }
+
+    /// Sets both the magnification and minification filters.
+    pub fn filter(mut self, filter: wgpu::FilterMode, mipmap: wgpu::MipmapFilterMode) -> Self {
+        self.descriptor.mag_filter = filter;
+        self.descriptor.min_filter = filter;
+        self.descriptor.mipmap_filter = mipmap;
+        self
+    }
+
+    pub fn mag_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.descriptor.mag_filter = filter;
+        self
+    }
+
+    pub fn min_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.descriptor.min_filter = filter;
+        self
+    }
+
+    pub fn mipmap_filter(mut self, filter: wgpu::MipmapFilterMode) -> Self {
+        self.descriptor.mipmap_filter = filter;
+        self
+    }
+
+    pub fn lod_clamp(mut self, min: f32, max: f32) -> Self {
+        self.descriptor.lod_min_clamp = min;
+        self.descriptor.lod_max_clamp = max;
+        self
+    }
+
+    /// Anisotropy level, 1 disables it. wgpu only allows values above 1 when every filter is
+    /// linear, otherwise it's reset to 1 on build.
+    pub fn anisotropy(mut self, level: u16) -> Self {
+        self.descriptor.anisotropy_clamp = level.max(1);
+        self
+    }
+
+    /// Makes this a comparison sampler (`sampler_comparison` in WGSL), e.g. for shadow maps.
+    pub fn compare(mut self, function: wgpu::CompareFunction) -> Self {
+        self.descriptor.compare = Some(function);
+        self
+    }
+
+    pub fn border_color(mut self, color: wgpu::SamplerBorderColor) -> Self {
+        self.descriptor.border_color = Some(color);
+        self
+    }
+
+    pub fn build(mut self, device: &wgpu::Device) -> Sampler {
+        let descriptor = &mut self.descriptor;
+        let all_linear = descriptor.mag_filter == wgpu::FilterMode::Linear
+            && descriptor.min_filter == wgpu::FilterMode::Linear
+            && descriptor.mipmap_filter == wgpu::MipmapFilterMode::Linear;
+
+        if descriptor.anisotropy_clamp > 1 && !all_linear {
+            log::warn!("Sampler anisotropy requires linear filtering, disabling it");
+            descriptor.anisotropy_clamp = 1;
+        }
+
+        let binding_type = if descriptor.compare.is_some() {
+            wgpu::SamplerBindingType::Comparison
+        } else if descriptor.mag_filter == wgpu::FilterMode::Nearest
+            && descriptor.min_filter == wgpu::FilterMode::Nearest
+            && descriptor.mipmap_filter == wgpu::MipmapFilterMode::Nearest
+        {
+            wgpu::SamplerBindingType::NonFiltering
+        } else {
+            wgpu::SamplerBindingType::Filtering
+        };
+
+        Sampler {
+            sampler: device.create_sampler(descriptor),
+            binding_type,
+            counter: InstanceCounter::new(),
+        }
+    }
+}
+
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
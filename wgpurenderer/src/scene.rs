@@ -0,0 +1,231 @@
+use crate::{
+    DrawCall, Geometry, Handle, Immediate, Renderer, ShaderData, transform::Transform, utils,
+};
+use glam::Mat4;
+use smallvec::SmallVec;
+use std::num::NonZeroU32;
+
+#[derive(Debug, Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    transform: Transform,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    geometry: Option<Handle<Geometry>>,
+    pipeline: Option<Handle<wgpu::RenderPipeline>>,
+    bind_groups: SmallVec<[Handle<wgpu::BindGroup>; 3]>,
+    /// World matrix of the last [`Scene::collect_draw_calls`], allocated on the first one.
+    immediate: Option<Immediate>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node and returns its index. `parent` must be an index returned earlier.
+    pub fn add_node(&mut self, transform: Transform, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+
+        if let Some(parent) = parent {
+            match self.nodes.get_mut(parent) {
+                Some(node) => node.children.push(index),
+                None => utils::cold_panic("Scene node parent doesn't exist"),
+            }
+        }
+
+        self.nodes.push(Node {
+            transform,
+            parent,
+            children: vec![],
+            geometry: None,
+            pipeline: None,
+            bind_groups: SmallVec::new(),
+            immediate: None,
+        });
+        index
+    }
+
+    pub fn node(&self, index: usize) -> Option<&Node> {
+        self.nodes.get(index)
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> Option<&mut Node> {
+        self.nodes.get_mut(index)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// World matrix of every node, indexed like the nodes. Nodes that can't be reached from a
+    /// root (i.e. are part of a parent cycle) fall back to their local matrix.
+    pub fn world_transforms(&mut self) -> Vec<Mat4> {
+        let mut world = vec![Mat4::IDENTITY; self.nodes.len()];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(i, _)| (i, Mat4::IDENTITY))
+            .collect();
+
+        while let Some((index, parent_world)) = stack.pop() {
+            if visited[index] {
+                log::warn!("Scene node {index} is reachable twice, skipping");
+                continue;
+            }
+            visited[index] = true;
+
+            let node = &mut self.nodes[index];
+            world[index] = node.transform.world_model(parent_world);
+            stack.extend(node.children.iter().map(|&child| (child, world[index])));
+        }
+
+        for (index, node) in self.nodes.iter_mut().enumerate() {
+            if !visited[index] {
+                log::warn!("Scene node {index} is part of a parent cycle");
+                world[index] = node.transform.get_model();
+            }
+        }
+
+        world
+    }
+
+    /// Emits a draw call for every node with both a geometry and a pipeline. The node's world
+    /// matrix is passed as the first 64 bytes of immediate data, which every node allocates
+    /// once and overwrites on later calls, so draw calls kept from an earlier call see the new
+    /// matrices.
+    pub fn collect_draw_calls(&mut self, renderer: &Renderer) -> Vec<DrawCall> {
+        let world = self.world_transforms();
+        let mut draw_calls = vec![];
+
+        for (node, world) in self.nodes.iter_mut().zip(world) {
+            let (Some(geometry), Some(pipeline)) = (node.geometry, node.pipeline) else {
+                continue;
+            };
+            let Some(geometry) = renderer.geometry(geometry) else {
+                log::warn!("Scene node references a removed geometry");
+                continue;
+            };
+
+            let immediate =
+                node.immediate.get_or_insert_with(|| renderer.create_immediate(size_of::<Mat4>()));
+            immediate.write(0, bytemuck::bytes_of(&world));
+
+            draw_calls.push(DrawCall {
+                geometry: geometry.clone(),
+                shader_data: ShaderData {
+                    immediates: Some(immediate.clone()),
+                    bind_groups: node.bind_groups.clone(),
+                },
+                instance_count: NonZeroU32::MIN,
+                render_pipeline_handle: pipeline,
+                scissor: None,
+                stencil_reference: None,
+            });
+        }
+
+        draw_calls
+    }
+}
+
+/// The clone gets its own immediate data on the next [`Scene::collect_draw_calls`].
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        Self {
+            transform: self.transform,
+            parent: self.parent,
+            children: self.children.clone(),
+            geometry: self.geometry,
+            pipeline: self.pipeline,
+            bind_groups: self.bind_groups.clone(),
+            immediate: None,
+        }
+    }
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    pub fn geometry(&self) -> Option<Handle<Geometry>> {
+        self.geometry
+    }
+
+    pub fn set_geometry(&mut self, geometry: Option<Handle<Geometry>>) {
+        self.geometry = geometry;
+    }
+
+    pub fn pipeline(&self) -> Option<Handle<wgpu::RenderPipeline>> {
+        self.pipeline
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: Option<Handle<wgpu::RenderPipeline>>) {
+        self.pipeline = pipeline;
+    }
+
+    pub fn bind_groups(&self) -> &[Handle<wgpu::BindGroup>] {
+        &self.bind_groups
+    }
+
+    pub fn set_bind_groups(&mut self, bind_groups: &[Handle<wgpu::BindGroup>]) {
+        self.bind_groups = SmallVec::from_slice(bind_groups);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, tests};
+
+    #[test]
+    fn draw_calls_reuse_node_immediates() {
+        let mut renderer = Renderer::with_context(Context::noop());
+        let material = tests::material(wgpu::TextureFormat::Rgba8Unorm);
+        let pipeline = renderer.create_render_pipeline(&material);
+        let geometry = Geometry::cylinder(renderer.context().device(), 1.0, 1.0, 8);
+        let geometry = renderer.add_geometry(geometry);
+
+        let mut scene = Scene::new();
+        let node = scene.add_node(Transform::default(), None);
+        scene.node_mut(node).unwrap().set_geometry(Some(geometry));
+        scene.node_mut(node).unwrap().set_pipeline(Some(pipeline));
+
+        let immediate_id =
+            |draw_calls: &[DrawCall]| draw_calls[0].shader_data.immediates.as_ref().unwrap().id;
+        let first = immediate_id(&scene.collect_draw_calls(&renderer));
+        scene.node_mut(node).unwrap().transform_mut().set_position(glam::Vec3::X);
+        let draw_calls = scene.collect_draw_calls(&renderer);
+
+        assert_eq!(immediate_id(&draw_calls), first);
+        let world: [u8; 64] = draw_calls[0].shader_data.immediates.as_ref().unwrap().read(0);
+        assert_eq!(world, *bytemuck::bytes_of(&Mat4::from_translation(glam::Vec3::X)));
+    }
+}
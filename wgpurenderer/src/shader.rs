@@ -0,0 +1,268 @@
+mod composed;
+mod layout;
+pub mod modules;
+pub mod presets;
+
+pub use composed::{ComposedShader, SourceMap};
+pub use layout::{ImmediateRange, LayoutMismatch, ShaderError, ShaderLayout};
+use rustc_hash::FxHashSet;
+use std::borrow::Cow;
+
+/// Source a [`crate::Material`] is compiled from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderSource {
+    Wgsl(Cow<'static, str>),
+    /// SPIR-V words, translated by naga's SPIR-V frontend. Feeding SPIR-V to the driver
+    /// unchanged would need `Features::PASSTHROUGH_SHADERS` and isn't supported here.
+    #[cfg(feature = "spirv")]
+    SpirV(Cow<'static, [u32]>),
+}
+
+impl ShaderSource {
+    /// Reads a SPIR-V binary in either byte order.
+    ///
+    /// # Panics
+    ///
+    /// If `bytes` doesn't start with the SPIR-V magic number or isn't a whole number of words.
+    #[cfg(feature = "spirv")]
+    pub fn from_spirv(bytes: &[u8]) -> Self {
+        Self::SpirV(Cow::Owned(wgpu::util::make_spirv_raw(bytes).into_owned()))
+    }
+
+    pub fn create_module(&self, device: &wgpu::Device) -> wgpu::ShaderModule {
+        let source = match self {
+            Self::Wgsl(source) => wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+            #[cfg(feature = "spirv")]
+            Self::SpirV(words) => wgpu::ShaderSource::SpirV(Cow::Borrowed(words)),
+        };
+
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source,
+        })
+    }
+
+    /// [`Self::create_module`] that reports failures as [`ShaderError::Compilation`] instead of
+    /// the device's uncaptured error handler. Warnings are logged, errors are listed with their
+    /// `line:column` in the source. Native backends compile while creating the module, so the
+    /// future is ready right away there; on the web it waits for the browser's compiler.
+    pub fn compile_async(
+        &self,
+        device: &wgpu::Device,
+    ) -> impl Future<Output = Result<wgpu::ShaderModule, ShaderError>> + use<> {
+        compile_async(self, device, None)
+    }
+}
+
+fn compile_async(
+    source: &ShaderSource,
+    device: &wgpu::Device,
+    source_map: Option<SourceMap>,
+) -> impl Future<Output = Result<wgpu::ShaderModule, ShaderError>> + use<> {
+    let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = source.create_module(device);
+    let error = scope.pop();
+
+    async move {
+        let info = module.get_compilation_info().await;
+        let errors = compilation_errors(&info.messages, source_map.as_ref());
+        let error = error.await;
+        if !errors.is_empty() {
+            return Err(ShaderError::Compilation(errors));
+        }
+        // Not every backend reports its errors as compilation messages
+        match error {
+            Some(error) => Err(ShaderError::Compilation(error.to_string())),
+            None => Ok(module),
+        }
+    }
+}
+
+/// Logs warnings and info messages, returns the errors one per line.
+fn compilation_errors(
+    messages: &[wgpu::CompilationMessage],
+    source_map: Option<&SourceMap>,
+) -> String {
+    let mut errors = vec![];
+    for message in messages {
+        let text = match (message.location, source_map) {
+            (Some(location), Some(source_map)) => format!(
+                "{}:{}: {}",
+                source_map.describe_line(location.line_number),
+                location.line_position,
+                message.message
+            ),
+            (Some(location), None) => {
+                format!("{}:{}: {}", location.line_number, location.line_position, message.message)
+            }
+            (None, _) => message.message.clone(),
+        };
+        match message.message_type {
+            wgpu::CompilationMessageType::Error => errors.push(text),
+            wgpu::CompilationMessageType::Warning => log::warn!("Shader compilation: {text}"),
+            wgpu::CompilationMessageType::Info => log::info!("Shader compilation: {text}"),
+        }
+    }
+    errors.join("\n")
+}
+
+impl From<&'static str> for ShaderSource {
+    fn from(source: &'static str) -> Self {
+        Self::Wgsl(Cow::Borrowed(source))
+    }
+}
+
+impl From<String> for ShaderSource {
+    fn from(source: String) -> Self {
+        Self::Wgsl(Cow::Owned(source))
+    }
+}
+
+/// Strips `//` and (nested) `/* */` comments and collapses whitespace runs into a single space,
+/// or a single newline when the run contained one. Tokens are never joined, since a comment
+/// counts as whitespace too. WGSL has no string literals, so nothing else needs preserving.
+pub fn minify(source: &str) -> String {
+    let mut minified = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    // `Some(true)` when the pending whitespace run contains a newline.
+    let mut pending: Option<bool> = None;
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                pending.get_or_insert(false);
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0
+                    && let Some(c) = chars.next()
+                {
+                    match (c, chars.peek()) {
+                        ('/', Some('*')) => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        ('*', Some('/')) => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        ('\n', _) => pending = Some(true),
+                        _ => {}
+                    }
+                }
+                pending.get_or_insert(false);
+            }
+            (c, _) if c.is_whitespace() => {
+                let newline = c == '\n' || pending == Some(true);
+                pending = Some(newline);
+            }
+            (c, _) => {
+                if let Some(newline) = pending.take()
+                    && !minified.is_empty()
+                {
+                    minified.push(if newline {
+                        '\n'
+                    } else {
+                        ' '
+                    });
+                }
+                minified.push(c);
+            }
+        }
+    }
+
+    minified
+}
+
+/// Prefixes the top-level `fn`, `struct`, `const`, `override`, `var` and `alias` names of
+/// `source` and every reference to them inside the module with `{prefix}_`, so modules that
+/// declare the same names can be composed. The source is [`minify`]-ed first.
+///
+/// This is a token rewrite, not a WGSL parser. References to another module's symbols have to
+/// use the prefixed name, and struct members or local variables that share a top-level name
+/// are renamed as well, except after a `.`. Entry points get renamed too, so the pipeline has
+/// to use the prefixed entry point name.
+pub fn namespace(prefix: &str, source: &str) -> String {
+    let source = minify(source);
+    let symbols = top_level_symbols(&source);
+
+    let mut namespaced = String::with_capacity(source.len() + symbols.len() * prefix.len());
+    let mut previous = None;
+    let mut rest = source.as_str();
+
+    while let Some(c) = rest.chars().next() {
+        if is_ident_start(c) {
+            let end = rest.find(|c| !is_ident_continue(c)).unwrap_or(rest.len());
+            let ident = &rest[..end];
+            if symbols.contains(ident) && !matches!(previous, Some('.' | '@')) {
+                namespaced.push_str(prefix);
+                namespaced.push('_');
+            }
+            namespaced.push_str(ident);
+            rest = &rest[end..];
+            previous = Some('a');
+            continue;
+        }
+
+        if !c.is_whitespace() {
+            previous = Some(c);
+        }
+        namespaced.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    namespaced
+}
+
+/// Concatenates `(prefix, source)` modules, each passed through [`namespace`].
+pub fn compose_namespaced(modules: &[(&str, &str)]) -> String {
+    modules.iter().map(|(prefix, source)| namespace(prefix, source)).collect::<Vec<_>>().join("\n")
+}
+
+fn top_level_symbols(source: &str) -> FxHashSet<&str> {
+    const DECLARATIONS: [&str; 6] = ["fn", "struct", "const", "override", "var", "alias"];
+
+    let mut symbols = FxHashSet::default();
+    let mut depth = 0u32;
+    let mut expect_name = false;
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        if is_ident_start(c) {
+            let end = rest.find(|c| !is_ident_continue(c)).unwrap_or(rest.len());
+            let ident = &rest[..end];
+            if expect_name {
+                symbols.insert(ident);
+                expect_name = false;
+            } else if depth == 0 && DECLARATIONS.contains(&ident) {
+                expect_name = true;
+            }
+            rest = &rest[end..];
+            continue;
+        }
+
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            // `var<storage, read>` has its address space between the keyword and the name.
+            '<' if expect_name => {
+                let end = rest.find('>').unwrap_or(rest.len() - 1);
+                rest = &rest[end..];
+            }
+            _ => {}
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    symbols
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
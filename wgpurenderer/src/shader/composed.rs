@@ -0,0 +1,172 @@
+use super::{ShaderError, ShaderLayout, ShaderSource};
+
+/// WGSL source concatenated from named modules, remembering which lines came from which module
+/// so errors can point at `module 2 (lighting), line 4` instead of a line of the joined source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposedShader {
+    source: String,
+    source_map: SourceMap,
+}
+
+impl ComposedShader {
+    /// Joins `(name, source)` modules in order, starting each on a new line. Sources are kept
+    /// verbatim so local line numbers stay exact; modules passed through [`super::namespace`]
+    /// are minified, which renumbers their lines.
+    pub fn from_modules(modules: &[(&str, &str)]) -> Self {
+        let mut source = String::with_capacity(modules.iter().map(|(_, m)| m.len() + 1).sum());
+        let mut source_map = SourceMap {
+            modules: Vec::with_capacity(modules.len()),
+        };
+
+        let mut next_line = 1;
+        for (name, module) in modules {
+            if !source.is_empty() && !source.ends_with('\n') {
+                source.push('\n');
+                next_line += 1;
+            }
+            source.push_str(module);
+            source_map.modules.push(ModuleLines {
+                name: name.to_string(),
+                first_line: next_line,
+                line_count: module.lines().count() as u32,
+            });
+            next_line += module.matches('\n').count() as u32;
+        }
+
+        Self {
+            source,
+            source_map,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// Module index and 1-based line within it of 1-based line `joined_line` of the source,
+    /// see [`SourceMap::translate_line`].
+    pub fn translate_error_line(&self, joined_line: u32) -> Option<(usize, u32)> {
+        self.source_map.translate_line(joined_line)
+    }
+
+    /// [`ShaderLayout::from_wgsl`] with parse and validation errors prefixed by the module and
+    /// line they point at.
+    pub fn layout(&self) -> Result<ShaderLayout, ShaderError> {
+        ShaderLayout::from_wgsl_mapped(&self.source, Some(&self.source_map))
+    }
+
+    /// [`ShaderSource::compile_async`] with error locations given per module.
+    pub fn compile_async(
+        &self,
+        device: &wgpu::Device,
+    ) -> impl Future<Output = Result<wgpu::ShaderModule, ShaderError>> + use<> {
+        super::compile_async(&self.to_source(), device, Some(self.source_map.clone()))
+    }
+
+    pub fn to_source(&self) -> ShaderSource {
+        self.source.clone().into()
+    }
+}
+
+/// Line ranges of the modules of a [`ComposedShader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    modules: Vec<ModuleLines>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ModuleLines {
+    name: String,
+    first_line: u32,
+    line_count: u32,
+}
+
+impl SourceMap {
+    /// Module index and 1-based line within it of 1-based line `joined_line`, `None` past the
+    /// end of the source.
+    pub fn translate_line(&self, joined_line: u32) -> Option<(usize, u32)> {
+        // Modules are sorted by their first line, empty ones share it with the next
+        let index = self
+            .modules
+            .partition_point(|module| module.first_line <= joined_line)
+            .checked_sub(1)?;
+        let module = &self.modules[index];
+        let line = joined_line - module.first_line + 1;
+        (line <= module.line_count).then_some((index, line))
+    }
+
+    pub fn module_name(&self, index: usize) -> Option<&str> {
+        Some(&self.modules.get(index)?.name)
+    }
+
+    /// `module {index} ({name}), line {line}`, or `line {joined_line}` if it's not in any module.
+    pub fn describe_line(&self, joined_line: u32) -> String {
+        match self.translate_line(joined_line) {
+            Some((index, line)) => {
+                format!("module {index} ({}), line {line}", self.modules[index].name)
+            }
+            None => format!("line {joined_line}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::modules;
+
+    const UNLIT: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+@group(1) @binding(0)
+var<uniform> model: ModelUniforms;
+@group(1) @binding(1)
+var<uniform> color: vec4<f32>;
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * model.model * vec4<f32>(in.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return color;
+}
+"#;
+
+    fn uniform(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    #[test]
+    fn composed_shader_reflects_bindings() {
+        let shader = ComposedShader::from_modules(&[
+            ("camera", modules::uniforms::CAMERA),
+            ("model", modules::uniforms::MODEL),
+            ("input", modules::vertex_inputs::POSITION),
+            ("unlit", UNLIT),
+        ]);
+        let layout = shader.layout().unwrap();
+
+        let (vertex, fragment) = (wgpu::ShaderStages::VERTEX, wgpu::ShaderStages::FRAGMENT);
+        assert_eq!(
+            layout.bind_groups,
+            [vec![uniform(0, vertex)], vec![uniform(0, vertex), uniform(1, fragment)]]
+        );
+        assert_eq!(layout.vertex_attributes, wgpu::vertex_attr_array![0 => Float32x3]);
+        assert_eq!(layout.fragment_locations, [0]);
+    }
+}
@@ -0,0 +1,702 @@
+use super::SourceMap;
+use crate::Geometry;
+use std::fmt;
+use wgpu::naga;
+
+/// Resource bindings and vertex inputs of a WGSL module, reflected with naga so they can't
+/// drift from the shader source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderLayout {
+    /// Layout entries indexed by bind group, in the shape of [`crate::Material::bind_groups`].
+    /// Groups without bindings are empty. Visibility covers every entry point that uses the
+    /// binding.
+    pub bind_groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+    /// Vertex attributes of the first vertex entry point, sorted by location and tightly packed
+    /// into a single buffer.
+    pub vertex_attributes: Vec<wgpu::VertexAttribute>,
+    /// Sorted `@location`s written by the first fragment entry point. Each needs a matching
+    /// [`crate::Fragment::targets`] entry and color attachment.
+    pub fragment_locations: Vec<u32>,
+    /// Immediate data block, reflected from a `var<immediate>` or declared by hand.
+    pub immediate_range: Option<ImmediateRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImmediateRange {
+    /// Bytes from offset 0, where [`crate::Immediate`] data is always set.
+    pub size: u32,
+    /// Stages whose entry points read the block. Informational: wgpu pipeline layouts only
+    /// declare the size and make immediate data visible to every stage.
+    pub visibility: wgpu::ShaderStages,
+}
+
+impl ShaderLayout {
+    pub fn from_wgsl(source: &str) -> Result<Self, ShaderError> {
+        Self::from_wgsl_mapped(source, None)
+    }
+
+    /// [`Self::from_wgsl`] prefixing errors with the module and line from `source_map`.
+    pub(crate) fn from_wgsl_mapped(
+        source: &str,
+        source_map: Option<&SourceMap>,
+    ) -> Result<Self, ShaderError> {
+        let locate =
+            |location: Option<naga::SourceLocation>, error: String| match (location, source_map) {
+                (Some(location), Some(source_map)) => {
+                    format!("{}: {error}", source_map.describe_line(location.line_number))
+                }
+                _ => error,
+            };
+
+        let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+            ShaderError::Parse(locate(error.location(source), error.emit_to_string(source)))
+        })?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|error| {
+            ShaderError::Validation(locate(error.location(source), error.emit_to_string(source)))
+        })?;
+
+        let mut layout = Self::default();
+
+        for (handle, global) in module.global_variables.iter() {
+            let visibility = module
+                .entry_points
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !info.get_entry_point(*i)[handle].is_empty())
+                .fold(wgpu::ShaderStages::NONE, |stages, (_, entry_point)| {
+                    stages | shader_stage(entry_point.stage)
+                });
+
+            if global.space == naga::AddressSpace::Immediate {
+                layout.immediate_range = Some(ImmediateRange {
+                    size: module.types[global.ty].inner.size(module.to_ctx()),
+                    visibility,
+                });
+                continue;
+            }
+
+            let Some(binding) = &global.binding else {
+                continue;
+            };
+            if visibility.is_empty() {
+                continue;
+            }
+
+            let ty = binding_type(&module, global).ok_or(ShaderError::UnsupportedBinding {
+                group: binding.group,
+                binding: binding.binding,
+            })?;
+
+            let group = binding.group as usize;
+            if layout.bind_groups.len() <= group {
+                layout.bind_groups.resize(group + 1, vec![]);
+            }
+            layout.bind_groups[group].push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty,
+                count: None,
+            });
+        }
+
+        for entries in &mut layout.bind_groups {
+            entries.sort_by_key(|entry| entry.binding);
+        }
+
+        if let Some(entry_point) =
+            module.entry_points.iter().find(|e| e.stage == naga::ShaderStage::Vertex)
+        {
+            let mut inputs = vec![];
+            for argument in &entry_point.function.arguments {
+                match &module.types[argument.ty].inner {
+                    naga::TypeInner::Struct {
+                        members,
+                        ..
+                    } => {
+                        inputs.extend(members.iter().map(|m| (m.binding.as_ref(), m.ty)));
+                    }
+                    _ => inputs.push((argument.binding.as_ref(), argument.ty)),
+                }
+            }
+
+            for (binding, ty) in inputs {
+                let Some(naga::Binding::Location {
+                    location,
+                    ..
+                }) = binding
+                else {
+                    continue;
+                };
+                let format = vertex_format(&module.types[ty].inner)
+                    .ok_or(ShaderError::UnsupportedVertexInput(*location))?;
+                layout.vertex_attributes.push(wgpu::VertexAttribute {
+                    format,
+                    offset: 0,
+                    shader_location: *location,
+                });
+            }
+
+            layout.vertex_attributes.sort_by_key(|attribute| attribute.shader_location);
+            pack_vertex_attributes(&mut layout.vertex_attributes);
+        }
+
+        if let Some(result) = module
+            .entry_points
+            .iter()
+            .find(|e| e.stage == naga::ShaderStage::Fragment)
+            .and_then(|entry_point| entry_point.function.result.as_ref())
+        {
+            let bindings = match &module.types[result.ty].inner {
+                naga::TypeInner::Struct {
+                    members,
+                    ..
+                } => members.iter().filter_map(|m| m.binding.as_ref()).collect(),
+                _ => result.binding.iter().collect::<Vec<_>>(),
+            };
+
+            layout.fragment_locations = bindings
+                .into_iter()
+                .filter_map(|binding| match binding {
+                    naga::Binding::Location {
+                        location,
+                        ..
+                    } => Some(*location),
+                    naga::Binding::BuiltIn(_) => None,
+                })
+                .collect();
+            layout.fragment_locations.sort_unstable();
+            layout.fragment_locations.dedup();
+        }
+
+        Ok(layout)
+    }
+
+    pub fn with_immediates(mut self, size: u32, visibility: wgpu::ShaderStages) -> Self {
+        self.immediate_range = Some(ImmediateRange {
+            size,
+            visibility,
+        });
+        self
+    }
+
+    /// Immediate size for the pipeline layout, see [`crate::Material::immediate_size`].
+    pub fn immediate_size(&self) -> u32 {
+        self.immediate_range.map_or(0, |range| range.size)
+    }
+
+    /// Checks that `size` bytes of immediate data, e.g. an `#[immediate]` struct's `SIZE`,
+    /// fit into the declared range.
+    pub fn validate_immediate(&self, size: usize) -> Result<(), ShaderError> {
+        let declared = self.immediate_size();
+        if size > declared as usize {
+            return Err(ShaderError::ImmediateTooLarge {
+                size,
+                declared,
+            });
+        }
+        Ok(())
+    }
+
+    /// Combines two layouts, e.g. a shared camera layout with a per-material one. Bind groups
+    /// are merged by index, vertex attributes are repacked by location and the immediate range
+    /// covers both. The same binding in
+    /// both layouts is only allowed with the same type, its visibility is then combined.
+    pub fn merge(mut self, other: ShaderLayout) -> Result<ShaderLayout, ShaderError> {
+        if self.bind_groups.len() < other.bind_groups.len() {
+            self.bind_groups.resize(other.bind_groups.len(), vec![]);
+        }
+
+        for (group, (entries, others)) in
+            self.bind_groups.iter_mut().zip(other.bind_groups).enumerate()
+        {
+            for other in others {
+                match entries.iter_mut().find(|entry| entry.binding == other.binding) {
+                    Some(entry) if entry.ty == other.ty && entry.count == other.count => {
+                        entry.visibility |= other.visibility;
+                    }
+                    Some(_) => {
+                        return Err(ShaderError::BindingCollision {
+                            group: group as u32,
+                            binding: other.binding,
+                        });
+                    }
+                    None => entries.push(other),
+                }
+            }
+            entries.sort_by_key(|entry| entry.binding);
+        }
+
+        for attribute in other.vertex_attributes {
+            if self.vertex_attributes.iter().any(|a| a.shader_location == attribute.shader_location)
+            {
+                return Err(ShaderError::VertexLocationCollision(attribute.shader_location));
+            }
+            self.vertex_attributes.push(attribute);
+        }
+        self.vertex_attributes.sort_by_key(|attribute| attribute.shader_location);
+        pack_vertex_attributes(&mut self.vertex_attributes);
+
+        self.immediate_range = match (self.immediate_range, other.immediate_range) {
+            (Some(a), Some(b)) => Some(ImmediateRange {
+                size: a.size.max(b.size),
+                visibility: a.visibility | b.visibility,
+            }),
+            (a, b) => a.or(b),
+        };
+
+        self.fragment_locations.extend(other.fragment_locations);
+        self.fragment_locations.sort_unstable();
+        self.fragment_locations.dedup();
+
+        Ok(self)
+    }
+
+    /// Stride of the tightly packed vertex buffer described by `vertex_attributes`.
+    pub fn vertex_stride(&self) -> wgpu::BufferAddress {
+        self.vertex_attributes.iter().map(|attribute| attribute.format.size()).sum()
+    }
+
+    /// Checks that every vertex input of the shader is fed by one of `geometry`'s buffers with
+    /// a compatible format, and reports the first location that isn't. Formats are compatible
+    /// when they have the same component count and scalar kind, so `Unorm8x4` can feed a
+    /// `vec4<f32>`. Extra geometry attributes are ignored.
+    pub fn validate_vertex_format(&self, geometry: &Geometry) -> Result<(), LayoutMismatch> {
+        self.validate_vertex_attributes(
+            geometry.buffers.iter().flat_map(|buffer| buffer.layout.attributes.iter()),
+        )
+    }
+
+    /// [`Self::validate_vertex_format`] against the attributes of any set of vertex buffers.
+    pub(crate) fn validate_vertex_attributes<'a>(
+        &self,
+        attributes: impl Iterator<Item = &'a wgpu::VertexAttribute> + Clone,
+    ) -> Result<(), LayoutMismatch> {
+        for expected in &self.vertex_attributes {
+            let location = expected.shader_location;
+            let Some(provided) =
+                attributes.clone().find(|attribute| attribute.shader_location == location)
+            else {
+                return Err(LayoutMismatch::MissingLocation(location));
+            };
+
+            if vertex_format_shape(provided.format) != vertex_format_shape(expected.format) {
+                return Err(LayoutMismatch::FormatMismatch {
+                    location,
+                    shader: expected.format,
+                    geometry: provided.format,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderError {
+    Parse(String),
+    Validation(String),
+    UnsupportedBinding {
+        group: u32,
+        binding: u32,
+    },
+    UnsupportedVertexInput(u32),
+    BindingCollision {
+        group: u32,
+        binding: u32,
+    },
+    VertexLocationCollision(u32),
+    ImmediateTooLarge {
+        size: usize,
+        declared: u32,
+    },
+    /// Immediate data has to be a multiple of `wgpu::IMMEDIATE_DATA_ALIGNMENT` bytes long.
+    ImmediateMisaligned(usize),
+    /// Errors reported by the device while compiling the module.
+    Compilation(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => write!(f, "failed to parse shader: {error}"),
+            Self::Validation(error) => write!(f, "shader validation failed: {error}"),
+            Self::UnsupportedBinding {
+                group,
+                binding,
+            } => {
+                write!(f, "binding {binding} of group {group} has an unsupported type")
+            }
+            Self::UnsupportedVertexInput(location) => {
+                write!(f, "vertex input at location {location} has an unsupported type")
+            }
+            Self::BindingCollision {
+                group,
+                binding,
+            } => {
+                write!(f, "binding {binding} of group {group} is declared with different types")
+            }
+            Self::VertexLocationCollision(location) => {
+                write!(f, "vertex input location {location} is declared twice")
+            }
+            Self::ImmediateTooLarge {
+                size,
+                declared,
+            } => {
+                write!(f, "{size} bytes of immediate data exceed the declared {declared} bytes")
+            }
+            Self::ImmediateMisaligned(size) => {
+                write!(f, "{size} bytes of immediate data aren't a multiple of 4")
+            }
+            Self::Compilation(errors) => write!(f, "shader compilation failed: {errors}"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// First difference between a shader's vertex inputs and the vertex buffers feeding them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMismatch {
+    /// The shader reads a location no vertex buffer provides.
+    MissingLocation(u32),
+    FormatMismatch {
+        location: u32,
+        shader: wgpu::VertexFormat,
+        geometry: wgpu::VertexFormat,
+    },
+}
+
+impl fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingLocation(location) => {
+                write!(f, "no vertex attribute at shader location {location}")
+            }
+            Self::FormatMismatch {
+                location,
+                shader,
+                geometry,
+            } => {
+                write!(
+                    f,
+                    "vertex input {location} is {shader:?}, the geometry provides {geometry:?}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutMismatch {}
+
+fn pack_vertex_attributes(attributes: &mut [wgpu::VertexAttribute]) {
+    let mut offset = 0;
+    for attribute in attributes {
+        attribute.offset = offset;
+        offset += attribute.format.size();
+    }
+}
+
+fn shader_stage(stage: naga::ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        naga::ShaderStage::Task => wgpu::ShaderStages::TASK,
+        naga::ShaderStage::Mesh => wgpu::ShaderStages::MESH,
+    }
+}
+
+fn binding_type(module: &naga::Module, global: &naga::GlobalVariable) -> Option<wgpu::BindingType> {
+    let buffer = |ty| wgpu::BindingType::Buffer {
+        ty,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    };
+
+    match global.space {
+        naga::AddressSpace::Uniform => return Some(buffer(wgpu::BufferBindingType::Uniform)),
+        naga::AddressSpace::Storage {
+            access,
+        } => {
+            return Some(buffer(wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            }));
+        }
+        naga::AddressSpace::Handle => {}
+        _ => return None,
+    }
+
+    match module.types[global.ty].inner {
+        naga::TypeInner::Sampler {
+            comparison: true,
+        } => Some(wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)),
+        naga::TypeInner::Sampler {
+            comparison: false,
+        } => Some(wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)),
+        naga::TypeInner::Image {
+            dim,
+            arrayed,
+            class,
+        } => {
+            let view_dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+            };
+
+            match class {
+                naga::ImageClass::Sampled {
+                    kind,
+                    multi,
+                } => Some(wgpu::BindingType::Texture {
+                    sample_type: match kind {
+                        naga::ScalarKind::Float => wgpu::TextureSampleType::Float {
+                            filterable: !multi,
+                        },
+                        naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        _ => return None,
+                    },
+                    view_dimension,
+                    multisampled: multi,
+                }),
+                naga::ImageClass::Depth {
+                    multi,
+                } => Some(wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension,
+                    multisampled: multi,
+                }),
+                naga::ImageClass::Storage {
+                    format,
+                    access,
+                } => Some(wgpu::BindingType::StorageTexture {
+                    access: match (
+                        access.contains(naga::StorageAccess::LOAD),
+                        access.contains(naga::StorageAccess::STORE),
+                    ) {
+                        (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                        (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                        _ => wgpu::StorageTextureAccess::WriteOnly,
+                    },
+                    format: storage_format(format),
+                    view_dimension,
+                }),
+                naga::ImageClass::External => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Bgra8Unorm => Tf::Bgra8Unorm,
+        Sf::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Ufloat => Tf::Rg11b10Ufloat,
+        Sf::R64Uint => Tf::R64Uint,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+/// Scalar kind the shader sees and component count of `format`.
+fn vertex_format_shape(format: wgpu::VertexFormat) -> (naga::ScalarKind, u8) {
+    use naga::ScalarKind as Sk;
+    use wgpu::VertexFormat as Vf;
+
+    match format {
+        Vf::Uint8 | Vf::Uint16 | Vf::Uint32 => (Sk::Uint, 1),
+        Vf::Uint8x2 | Vf::Uint16x2 | Vf::Uint32x2 => (Sk::Uint, 2),
+        Vf::Uint32x3 => (Sk::Uint, 3),
+        Vf::Uint8x4 | Vf::Uint16x4 | Vf::Uint32x4 => (Sk::Uint, 4),
+        Vf::Sint8 | Vf::Sint16 | Vf::Sint32 => (Sk::Sint, 1),
+        Vf::Sint8x2 | Vf::Sint16x2 | Vf::Sint32x2 => (Sk::Sint, 2),
+        Vf::Sint32x3 => (Sk::Sint, 3),
+        Vf::Sint8x4 | Vf::Sint16x4 | Vf::Sint32x4 => (Sk::Sint, 4),
+        Vf::Unorm8
+        | Vf::Snorm8
+        | Vf::Unorm16
+        | Vf::Snorm16
+        | Vf::Float16
+        | Vf::Float32
+        | Vf::Float64 => (Sk::Float, 1),
+        Vf::Unorm8x2
+        | Vf::Snorm8x2
+        | Vf::Unorm16x2
+        | Vf::Snorm16x2
+        | Vf::Float16x2
+        | Vf::Float32x2
+        | Vf::Float64x2 => (Sk::Float, 2),
+        Vf::Float32x3 | Vf::Float64x3 => (Sk::Float, 3),
+        Vf::Unorm8x4
+        | Vf::Snorm8x4
+        | Vf::Unorm16x4
+        | Vf::Snorm16x4
+        | Vf::Float16x4
+        | Vf::Float32x4
+        | Vf::Float64x4
+        | Vf::Unorm10_10_10_2
+        | Vf::Unorm8x4Bgra => (Sk::Float, 4),
+    }
+}
+
+fn vertex_format(inner: &naga::TypeInner) -> Option<wgpu::VertexFormat> {
+    use wgpu::VertexFormat as Vf;
+
+    let (scalar, size) = match *inner {
+        naga::TypeInner::Scalar(scalar) => (scalar, 1),
+        naga::TypeInner::Vector {
+            size,
+            scalar,
+        } => (scalar, size as u8),
+        _ => return None,
+    };
+
+    Some(match (scalar.kind, scalar.width, size) {
+        (naga::ScalarKind::Float, 4, 1) => Vf::Float32,
+        (naga::ScalarKind::Float, 4, 2) => Vf::Float32x2,
+        (naga::ScalarKind::Float, 4, 3) => Vf::Float32x3,
+        (naga::ScalarKind::Float, 4, 4) => Vf::Float32x4,
+        (naga::ScalarKind::Float, 2, 1) => Vf::Float16,
+        (naga::ScalarKind::Float, 2, 2) => Vf::Float16x2,
+        (naga::ScalarKind::Float, 2, 4) => Vf::Float16x4,
+        (naga::ScalarKind::Sint, 4, 1) => Vf::Sint32,
+        (naga::ScalarKind::Sint, 4, 2) => Vf::Sint32x2,
+        (naga::ScalarKind::Sint, 4, 3) => Vf::Sint32x3,
+        (naga::ScalarKind::Sint, 4, 4) => Vf::Sint32x4,
+        (naga::ScalarKind::Uint, 4, 1) => Vf::Uint32,
+        (naga::ScalarKind::Uint, 4, 2) => Vf::Uint32x2,
+        (naga::ScalarKind::Uint, 4, 3) => Vf::Uint32x3,
+        (naga::ScalarKind::Uint, 4, 4) => Vf::Uint32x4,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(
+        binding: u32,
+        ty: wgpu::BufferBindingType,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    #[test]
+    fn merge_combines_groups_and_attributes() {
+        let uniform = wgpu::BufferBindingType::Uniform;
+        let (vertex, fragment) = (wgpu::ShaderStages::VERTEX, wgpu::ShaderStages::FRAGMENT);
+        let camera = ShaderLayout {
+            bind_groups: vec![vec![buffer(0, uniform, vertex)]],
+            vertex_attributes: wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            ..Default::default()
+        };
+        let material = ShaderLayout {
+            bind_groups: vec![
+                vec![buffer(0, uniform, fragment)],
+                vec![buffer(0, uniform, fragment)],
+            ],
+            vertex_attributes: wgpu::vertex_attr_array![1 => Float32x2].to_vec(),
+            ..Default::default()
+        };
+
+        let merged = camera.merge(material).unwrap();
+
+        assert_eq!(
+            merged.bind_groups,
+            [vec![buffer(0, uniform, vertex | fragment)], vec![buffer(0, uniform, fragment)]]
+        );
+        assert_eq!(
+            merged.vertex_attributes,
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2]
+        );
+    }
+
+    #[test]
+    fn merge_rejects_collisions() {
+        let visibility = wgpu::ShaderStages::VERTEX;
+        let camera = ShaderLayout {
+            bind_groups: vec![vec![buffer(0, wgpu::BufferBindingType::Uniform, visibility)]],
+            vertex_attributes: wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            ..Default::default()
+        };
+        let storage = wgpu::BufferBindingType::Storage {
+            read_only: true,
+        };
+        let other_binding = ShaderLayout {
+            bind_groups: vec![vec![buffer(0, storage, visibility)]],
+            ..Default::default()
+        };
+        let other_attribute = ShaderLayout {
+            vertex_attributes: wgpu::vertex_attr_array![0 => Float32x2].to_vec(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            camera.clone().merge(other_binding),
+            Err(ShaderError::BindingCollision {
+                group: 0,
+                binding: 0,
+            })
+        );
+        assert_eq!(camera.merge(other_attribute), Err(ShaderError::VertexLocationCollision(0)));
+    }
+}
@@ -1,4 +1,4 @@
-/// Готовые модули шейдерного кода для переиспользования
+//! Готовые модули шейдерного кода для переиспользования
 
 /// Общие uniform структуры
 pub mod uniforms {
@@ -18,6 +18,17 @@ struct ModelUniforms {
     model: mat4x4<f32>,
     normal_matrix: mat3x3<f32>,
 }
+"#;
+
+    /// Глобальные данные кадра (`FrameGlobals`), обновляются `Renderer` раз в кадр.
+    /// Обычно привязываются рядом с камерой: `@group(0) @binding(1)`
+    pub const FRAME_GLOBALS: &str = r#"
+struct FrameGlobals {
+    time: f32,
+    delta_time: f32,
+    frame: u32,
+    resolution: vec2<f32>,
+}
 "#;
 
     /// Стандартная структура для освещения
@@ -51,6 +62,15 @@ struct PbrMaterial {
     roughness: f32,
     emissive: vec3<f32>,
 }
+"#;
+
+    /// Матрица источника света для теней. `bias` вычитается из глубины фрагмента перед
+    /// сравнением с картой теней
+    pub const SHADOW: &str = r#"
+struct ShadowUniforms {
+    light_view_projection: mat4x4<f32>,
+    bias: f32,
+}
 "#;
 }
 
@@ -205,5 +225,193 @@ fn blinn_phong_specular(
 fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
     return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
 }
+"#;
+
+    /// GGX (Trowbridge-Reitz) распределение микрограней. `roughness` перцептивная,
+    /// alpha = roughness²
+    pub const DISTRIBUTION_GGX: &str = r#"
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / max(3.14159265 * d * d, 1e-7);
+}
+"#;
+
+    /// Schlick-GGX геометрическое затенение для одного направления,
+    /// k = (roughness + 1)² / 8 для прямого освещения
+    pub const GEOMETRY_SCHLICK_GGX: &str = r#"
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+"#;
+
+    /// Smith: затенение для направлений на камеру и на свет, требует `GEOMETRY_SCHLICK_GGX`
+    pub const GEOMETRY_SMITH: &str = r#"
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    return geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+}
+"#;
+
+    /// Cook-Torrance BRDF для `PbrMaterial`, умноженный на n·l: отражённый свет от источника
+    /// единичной яркости в направлении `l`. `n`, `v`, `l` нормализованы. Roughness перцептивная
+    /// (0 — зеркало, 1 — полностью матовая): GGX берёт alpha = roughness², Smith —
+    /// k = (roughness + 1)² / 8. Диэлектрики отражают F0 = 0.04, металлы — `base_color`.
+    /// Требует `PBR_MATERIAL`, `FRESNEL_SCHLICK`, `DISTRIBUTION_GGX`, `GEOMETRY_SCHLICK_GGX`
+    /// и `GEOMETRY_SMITH`
+    pub const COOK_TORRANCE: &str = r#"
+fn cook_torrance(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, pbr: PbrMaterial) -> vec3<f32> {
+    let albedo = pbr.base_color.rgb;
+    let f0 = mix(vec3<f32>(0.04), albedo, pbr.metallic);
+    let h = normalize(v + l);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 1e-4);
+
+    let d = distribution_ggx(max(dot(n, h), 0.0), pbr.roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, pbr.roughness);
+    let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+
+    let specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - pbr.metallic) * albedo / 3.14159265;
+    return (diffuse + specular) * n_dot_l;
+}
+"#;
+}
+
+/// Доступ к SSBO данным объектов
+pub mod storage {
+    /// Массив данных объектов из SSBO (`Renderer::ssbo_buffer::<T>()`), индексируемый
+    /// `@builtin(instance_index)`. Перед модулем нужно объявить `struct ObjectData`,
+    /// совпадающую с `T`, и `const INSTANCE_GROUP: u32` с номером группы.
+    ///
+    /// Шаг массива в WGSL — размер структуры, округлённый до её выравнивания, а на CPU
+    /// элементы лежат вплотную с шагом `size_of::<T>()`. Поэтому размер `T` должен быть
+    /// кратен 16 байтам, если в ней есть `vec3`, `vec4` или матрицы: `vec3` дополняется
+    /// явным полем-паддингом.
+    ///
+    /// Элемент объекта — его слот (`Renderer::ssbo_index::<T>(id)`), слоты выдаются по
+    /// порядку первой загрузки. Инстансы draw call всегда нумеруются с 0, поэтому
+    /// объекты в слотах `0..n` с общей геометрией и материалом рисуются одним вызовом
+    /// с `instance_count = n` вместо `n` отдельных
+    pub const INSTANCES: &str = r#"
+@group(INSTANCE_GROUP) @binding(0)
+var<storage, read> instances: array<ObjectData>;
+
+fn instance_data(instance_index: u32) -> ObjectData {
+    return instances[instance_index];
+}
+"#;
+}
+
+/// Тени
+pub mod shadows {
+    /// PCF 3x3 по карте теней через `sampler_comparison`. `light_clip` — позиция фрагмента,
+    /// умноженная на view-projection источника света. Возвращает долю освещённости 0..1,
+    /// вне карты теней фрагмент считается освещённым
+    pub const PCF: &str = r#"
+fn shadow_pcf(
+    shadow_map: texture_depth_2d,
+    shadow_sampler: sampler_comparison,
+    light_clip: vec4<f32>,
+    bias: f32,
+) -> f32 {
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z - bias);
+        }
+    }
+    return lit / 9.0;
+}
+"#;
+}
+
+/// Постобработка HDR цвета. Сцена рендерится в линейный HDR таргет без sRGB
+/// (например `Rgba16Float`), а tone mapping применяется в финальном проходе на swapchain:
+/// `exposure` → `reinhard`/`aces_filmic` → `linear_to_srgb`. Последний шаг не нужен, если
+/// формат swapchain уже `*Srgb` — тогда преобразование делает железо при записи
+pub mod post {
+    /// Экспозиция в стопах: `ev = 0` не меняет цвет, `+1` — вдвое ярче
+    pub const EXPOSURE: &str = r#"
+fn exposure(color: vec3<f32>, ev: f32) -> vec3<f32> {
+    return color * exp2(ev);
+}
+"#;
+
+    /// Reinhard по каналам, сжимает 0..∞ в 0..1
+    pub const REINHARD: &str = r#"
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (vec3<f32>(1.0) + color);
+}
+"#;
+
+    /// Аппроксимация ACES filmic (Narkowicz), результат обрезается до 0..1
+    pub const ACES_FILMIC: &str = r#"
+fn aces_filmic(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp(color * (a * color + b) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+"#;
+
+    /// То же, что [`super::utils::LINEAR_TO_SRGB`]
+    pub const LINEAR_TO_SRGB: &str = super::utils::LINEAR_TO_SRGB;
+}
+
+/// Скайбокс
+pub mod skybox {
+    /// Vertex/fragment пара для cubemap скайбокса. Рисуется полноэкранным треугольником
+    /// (`draw(0..3, 0..1)`) без вершинного буфера, требует `uniforms::CAMERA`.
+    /// Глубина пишется в 1.0, поэтому нужен `CompareFunction::LessEqual`.
+    pub const SKYBOX: &str = r#"
+struct SkyboxOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+
+@group(1) @binding(0)
+var skybox_texture: texture_cube<f32>;
+@group(1) @binding(1)
+var skybox_sampler: sampler;
+
+@vertex
+fn vs_skybox(@builtin(vertex_index) index: u32) -> SkyboxOutput {
+    // (0, 0), (0, 2), (2, 0): counter-clockwise once y is flipped, so back-face culling keeps it
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    let ndc = uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    let view_rotation = mat3x3<f32>(camera.view[0].xyz, camera.view[1].xyz, camera.view[2].xyz);
+    let view_direction = vec3<f32>(
+        ndc.x / camera.projection[0][0],
+        ndc.y / camera.projection[1][1],
+        -1.0,
+    );
+
+    var out: SkyboxOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.direction = transpose(view_rotation) * view_direction;
+    return out;
+}
+
+@fragment
+fn fs_skybox(in: SkyboxOutput) -> @location(0) vec4<f32> {
+    return textureSample(skybox_texture, skybox_sampler, in.direction);
+}
 "#;
 }
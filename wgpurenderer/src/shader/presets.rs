@@ -0,0 +1,446 @@
+//! Complete shaders assembled from [`super::modules`], with the entry points `vs_main` and
+//! `fs_main` expected by [`crate::Renderer::create_render_pipeline`].
+
+use super::{ShaderLayout, ShaderSource, modules};
+use crate::utils;
+
+const BLINN_PHONG: &str = r#"
+struct BlinnPhongMaterial {
+    base_color: vec4<f32>,
+    specular_strength: f32,
+    shininess: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+
+@group(1) @binding(0)
+var<uniform> model: ModelUniforms;
+@group(1) @binding(1)
+var<uniform> material: BlinnPhongMaterial;
+
+@group(2) @binding(0)
+var<uniform> lighting: LightingUniforms;
+@group(2) @binding(1)
+var<uniform> point_lights: array<PointLight, MAX_POINT_LIGHTS>;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let world_position = model.model * vec4<f32>(in.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normalize(model.normal_matrix * in.normal);
+    out.uv = in.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let view_dir = normalize(camera.position - in.world_position);
+
+    let sun = lighting.directional_light;
+    let sun_dir = normalize(-sun.direction);
+    let sun_radiance = sun.color * sun.intensity * directional_shadow(in.world_position);
+    var diffuse = sun_radiance * phong_diffuse(normal, sun_dir);
+    var specular = sun_radiance * blinn_phong_specular(normal, sun_dir, view_dir, material.shininess);
+
+    let count = min(lighting.num_point_lights, MAX_POINT_LIGHTS);
+    for (var i = 0u; i < count; i++) {
+        let light = point_lights[i];
+        let to_light = light.position - in.world_position;
+        let distance = length(to_light);
+        let light_dir = to_light / max(distance, 1e-4);
+        let falloff = clamp(1.0 - distance / light.range, 0.0, 1.0);
+        let radiance = light.color * light.intensity * falloff * falloff;
+
+        diffuse += radiance * phong_diffuse(normal, light_dir);
+        specular += radiance * blinn_phong_specular(normal, light_dir, view_dir, material.shininess);
+    }
+
+    let ambient = lighting.ambient_color * lighting.ambient_intensity;
+    let color = material.base_color.rgb * (ambient + diffuse) + specular * material.specular_strength;
+    return vec4<f32>(color, material.base_color.a);
+}
+"#;
+
+const PBR: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+
+@group(1) @binding(0)
+var<uniform> model: ModelUniforms;
+@group(1) @binding(1)
+var<uniform> material: PbrMaterial;
+
+@group(2) @binding(0)
+var<uniform> lighting: LightingUniforms;
+@group(2) @binding(1)
+var<uniform> point_lights: array<PointLight, MAX_POINT_LIGHTS>;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let world_position = model.model * vec4<f32>(in.position, 1.0);
+    let normal = normalize(model.normal_matrix * in.normal);
+    let tangent = normalize(model.normal_matrix * in.tangent.xyz);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = normal;
+    out.uv = in.uv;
+    out.tangent = tangent;
+    out.bitangent = cross(normal, tangent) * in.tangent.w;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    let v = normalize(camera.position - in.world_position);
+    let albedo = material.base_color.rgb;
+
+    let sun = lighting.directional_light;
+    let sun_radiance = sun.color * sun.intensity * directional_shadow(in.world_position);
+    var color = cook_torrance(n, v, normalize(-sun.direction), material) * sun_radiance;
+
+    let count = min(lighting.num_point_lights, MAX_POINT_LIGHTS);
+    for (var i = 0u; i < count; i++) {
+        let light = point_lights[i];
+        let to_light = light.position - in.world_position;
+        let distance = length(to_light);
+        let falloff = clamp(1.0 - distance / light.range, 0.0, 1.0);
+        let radiance = light.color * light.intensity * falloff * falloff;
+        color += cook_torrance(n, v, to_light / max(distance, 1e-4), material) * radiance;
+    }
+
+    color += lighting.ambient_color * lighting.ambient_intensity * albedo + material.emissive;
+    return vec4<f32>(color, material.base_color.a);
+}
+"#;
+
+const NO_SHADOW: &str = r#"
+fn directional_shadow(world_position: vec3<f32>) -> f32 {
+    return 1.0;
+}
+"#;
+
+const SHADOW_RECEIVER: &str = r#"
+@group(3) @binding(0)
+var<uniform> shadow: ShadowUniforms;
+@group(3) @binding(1)
+var shadow_map: texture_depth_2d;
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+fn directional_shadow(world_position: vec3<f32>) -> f32 {
+    let light_clip = shadow.light_view_projection * vec4<f32>(world_position, 1.0);
+    return shadow_pcf(shadow_map, shadow_sampler, light_clip, shadow.bias);
+}
+"#;
+
+const SHADOW_DEPTH: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+
+@group(1) @binding(0)
+var<uniform> model: ModelUniforms;
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * model.model * vec4<f32>(in.position, 1.0);
+}
+"#;
+
+const UNLIT: &str = r#"
+struct UnlitMaterial {
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniforms;
+
+@group(1) @binding(0)
+var<uniform> model: ModelUniforms;
+@group(1) @binding(1)
+var<uniform> material: UnlitMaterial;
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * model.model * vec4<f32>(in.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return material.color;
+}
+"#;
+
+const FULLSCREEN: &str = r#"
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // (0, 0), (0, 2), (2, 0): counter-clockwise once y is flipped, so back-face culling keeps it
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(input_texture, input_sampler, in.uv);
+}
+"#;
+
+/// Rasterizer depth bias for the shadow pass, set as `depth_stencil.bias` of the
+/// [`shadow_depth`] material. Without it, surfaces facing the light compare against their own
+/// quantized depth and shadow themselves in stripes ("shadow acne"). The slope term grows the
+/// offset for surfaces at grazing angles to the light, where acne is worst. Too much bias
+/// detaches shadows from their casters ("peter panning"); the receiver side `bias` in
+/// `ShadowUniforms` covers what's left, typically around `0.001..0.005` for a 2048² map.
+pub const SHADOW_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: 2,
+    slope_scale: 2.0,
+    clamp: 0.0,
+};
+
+/// Depth-only shader for rendering a shadow map from a light's
+/// [`crate::camera::Camera`], e.g. one from [`crate::camera::Camera::directional_light`].
+/// Only has a vertex stage, so the material needs `fragment: None`, a depth format that can be
+/// sampled and [`SHADOW_DEPTH_BIAS`].
+///
+/// Bindings: group 0 `CameraUniforms` filled from the light camera, group 1 `ModelUniforms`.
+/// Reads only the position at `@location(0)`, so the meshes' usual vertex layout works as is.
+pub fn shadow_depth() -> (ShaderSource, ShaderLayout) {
+    reflect(
+        [
+            modules::uniforms::CAMERA,
+            modules::uniforms::MODEL,
+            modules::vertex_inputs::POSITION,
+            SHADOW_DEPTH,
+        ]
+        .concat(),
+    )
+}
+
+/// Flat colored shader reading only the position at `@location(0)`, for
+/// [`crate::Geometry::point_cloud`] and [`crate::Geometry::line_list`] debug geometry, or any
+/// other mesh. The material's `primitive.topology` has to match the geometry's; points are
+/// always rasterized one pixel large.
+///
+/// Bindings, all uniform buffers: group 0 `CameraUniforms`, group 1 `ModelUniforms`, then
+/// `UnlitMaterial { color }`.
+pub fn unlit() -> (ShaderSource, ShaderLayout) {
+    reflect(
+        [
+            modules::uniforms::CAMERA,
+            modules::uniforms::MODEL,
+            modules::vertex_inputs::POSITION,
+            UNLIT,
+        ]
+        .concat(),
+    )
+}
+
+/// Fullscreen pass copying an input texture, the starting point for screen-space effects. The
+/// vertex stage builds a triangle covering the target from `@builtin(vertex_index)`, so the
+/// material has no vertex buffers and is drawn with [`crate::RenderPass::fullscreen`]. UV
+/// `(0, 0)` is the top left corner. The triangle is counter-clockwise, so it survives
+/// `cull_mode: Some(Face::Back)` with the default front face.
+///
+/// Bindings: group 0 the input as `texture_2d<f32>`, then a filtering sampler.
+pub fn fullscreen() -> (ShaderSource, ShaderLayout) {
+    reflect([modules::vertex_outputs::WITH_UV, FULLSCREEN].concat())
+}
+
+/// Lit shader for [`crate::geometry::MeshVertex`] meshes: ambient, one directional light and up
+/// to `max_point_lights` point lights with Blinn-Phong specular.
+///
+/// Bindings, all uniform buffers:
+/// - group 0: `CameraUniforms`
+/// - group 1: `ModelUniforms`, then `BlinnPhongMaterial { base_color, specular_strength,
+///   shininess }`
+/// - group 2: `LightingUniforms`, then `array<PointLight, max_point_lights>`; only the first
+///   `num_point_lights` are used
+pub fn standard_lit(max_point_lights: u32) -> (ShaderSource, ShaderLayout) {
+    compose(
+        max_point_lights,
+        &[
+            modules::vertex_inputs::STANDARD,
+            modules::vertex_outputs::STANDARD,
+            modules::lighting::PHONG_DIFFUSE,
+            modules::lighting::BLINN_PHONG_SPECULAR,
+            NO_SHADOW,
+            BLINN_PHONG,
+        ],
+    )
+}
+
+/// [`standard_lit`] with the directional light shadowed by a shadow map rendered with
+/// [`shadow_depth`].
+///
+/// Adds group 3: `ShadowUniforms { light_view_projection, bias }` with the light camera's
+/// view-projection, then the shadow map as `texture_depth_2d` and a comparison sampler, e.g.
+/// `Sampler::builder().compare(wgpu::CompareFunction::LessEqual)` with linear filtering
+/// and clamp-to-edge addressing.
+pub fn standard_lit_shadowed(max_point_lights: u32) -> (ShaderSource, ShaderLayout) {
+    compose(
+        max_point_lights,
+        &[
+            modules::uniforms::SHADOW,
+            modules::vertex_inputs::STANDARD,
+            modules::vertex_outputs::STANDARD,
+            modules::lighting::PHONG_DIFFUSE,
+            modules::lighting::BLINN_PHONG_SPECULAR,
+            modules::shadows::PCF,
+            SHADOW_RECEIVER,
+            BLINN_PHONG,
+        ],
+    )
+}
+
+/// Metallic-roughness PBR with a Cook-Torrance BRDF (GGX, Smith, Schlick) for the full vertex
+/// format with tangents. Outputs linear HDR color.
+///
+/// Bindings match [`standard_lit`], with `PbrMaterial` as the material in group 1.
+pub fn pbr(max_point_lights: u32) -> (ShaderSource, ShaderLayout) {
+    compose(
+        max_point_lights,
+        &[
+            modules::uniforms::PBR_MATERIAL,
+            modules::vertex_inputs::FULL,
+            modules::vertex_outputs::FULL,
+            modules::lighting::FRESNEL_SCHLICK,
+            modules::lighting::DISTRIBUTION_GGX,
+            modules::lighting::GEOMETRY_SCHLICK_GGX,
+            modules::lighting::GEOMETRY_SMITH,
+            modules::lighting::COOK_TORRANCE,
+            NO_SHADOW,
+            PBR,
+        ],
+    )
+}
+
+/// [`pbr`] with a shadowed directional light, bound as in [`standard_lit_shadowed`].
+pub fn pbr_shadowed(max_point_lights: u32) -> (ShaderSource, ShaderLayout) {
+    compose(
+        max_point_lights,
+        &[
+            modules::uniforms::PBR_MATERIAL,
+            modules::uniforms::SHADOW,
+            modules::vertex_inputs::FULL,
+            modules::vertex_outputs::FULL,
+            modules::lighting::FRESNEL_SCHLICK,
+            modules::lighting::DISTRIBUTION_GGX,
+            modules::lighting::GEOMETRY_SCHLICK_GGX,
+            modules::lighting::GEOMETRY_SMITH,
+            modules::lighting::COOK_TORRANCE,
+            modules::shadows::PCF,
+            SHADOW_RECEIVER,
+            PBR,
+        ],
+    )
+}
+
+/// Prepends the camera, model and lighting uniforms to `parts` and reflects the layout.
+fn compose(max_point_lights: u32, parts: &[&str]) -> (ShaderSource, ShaderLayout) {
+    let mut source = format!("const MAX_POINT_LIGHTS: u32 = {}u;\n", max_point_lights.max(1));
+    for part in [modules::uniforms::CAMERA, modules::uniforms::MODEL, modules::uniforms::LIGHTING]
+        .iter()
+        .chain(parts)
+    {
+        source.push_str(part);
+    }
+    reflect(source)
+}
+
+fn reflect(source: String) -> (ShaderSource, ShaderLayout) {
+    let layout = ShaderLayout::from_wgsl(&source)
+        .unwrap_or_else(|_| utils::cold_panic("Shader preset failed to compile"));
+    (source.into(), layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIFORM: wgpu::BindingType = wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    };
+
+    fn binding_types(layout: &ShaderLayout) -> Vec<Vec<wgpu::BindingType>> {
+        let group =
+            |entries: &Vec<wgpu::BindGroupLayoutEntry>| entries.iter().map(|e| e.ty).collect();
+        layout.bind_groups.iter().map(group).collect()
+    }
+
+    fn attribute_formats(layout: &ShaderLayout) -> Vec<(u32, wgpu::VertexFormat)> {
+        layout.vertex_attributes.iter().map(|a| (a.shader_location, a.format)).collect()
+    }
+
+    #[test]
+    fn presets_reflect_their_documented_layouts() {
+        use wgpu::VertexFormat::*;
+
+        let (_, shadow) = shadow_depth();
+        assert_eq!(binding_types(&shadow), [vec![UNIFORM], vec![UNIFORM]]);
+        assert_eq!(attribute_formats(&shadow), [(0, Float32x3)]);
+        assert!(shadow.fragment_locations.is_empty());
+
+        let (_, unlit) = unlit();
+        assert_eq!(binding_types(&unlit), [vec![UNIFORM], vec![UNIFORM, UNIFORM]]);
+        assert_eq!(attribute_formats(&unlit), [(0, Float32x3)]);
+
+        let (_, fullscreen) = fullscreen();
+        let texture = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float {
+                filterable: true,
+            },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let sampler = wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering);
+        assert_eq!(binding_types(&fullscreen), [vec![texture, sampler]]);
+        assert!(fullscreen.vertex_attributes.is_empty());
+
+        let lit = vec![vec![UNIFORM], vec![UNIFORM, UNIFORM], vec![UNIFORM, UNIFORM]];
+        let mut shadowed = lit.clone();
+        shadowed.push(vec![
+            UNIFORM,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+        ]);
+        let standard = [(0, Float32x3), (1, Float32x3), (2, Float32x2)];
+        let full = [(0, Float32x3), (1, Float32x3), (2, Float32x2), (3, Float32x4)];
+
+        // 0 point lights still declares a one element array, so the layout doesn't change.
+        for max_point_lights in [0, 4] {
+            for (preset, groups, attributes) in [
+                (standard_lit as fn(u32) -> _, &lit, &standard[..]),
+                (standard_lit_shadowed, &shadowed, &standard),
+                (pbr, &lit, &full),
+                (pbr_shadowed, &shadowed, &full),
+            ] {
+                let (_, layout) = preset(max_point_lights);
+                assert_eq!(&binding_types(&layout), groups);
+                assert_eq!(attribute_formats(&layout), attributes);
+                assert_eq!(layout.fragment_locations, [0]);
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use crate::types::*;
+use crate::utils::*;
 use sorted_vec::SortedVec;
-use std::{cell::RefCell, ops::Range, rc::Rc};
+use std::ops::Range;
+use wgpu::util::StagingBelt;
+
+/// Staging belt chunk size. Large enough for ~16k 64-byte objects per chunk.
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 1 << 20;
 
 pub trait UniformData: bytemuck::NoUninit {}
 
@@ -10,6 +14,7 @@ impl<T> UniformData for T where T: bytemuck::NoUninit {}
 struct SsboPoolInner {
     ssbo_map: TypeIdMap<ManagedSsbo>,
     ssbo_ids: IdPool,
+    staging_belt: Option<StagingBelt>,
 }
 
 impl SsboPoolInner {
@@ -18,10 +23,11 @@ impl SsboPoolInner {
 
         let slice = bytemuck::bytes_of(val);
 
-        match entry.find_by_id(uniform.id) {
+        let offset = match entry.find_by_id(uniform.id) {
             Ok(index) => {
                 let offset = entry.entries[index].offset_in_buffer;
                 entry.staging_buffer[offset..offset + entry.type_info.size].copy_from_slice(slice);
+                offset
             }
             Err(_) => {
                 let offset = entry.staging_buffer.len();
@@ -30,8 +36,14 @@ impl SsboPoolInner {
                     id: uniform.id,
                     offset_in_buffer: offset,
                 });
+                offset
             }
-        }
+        };
+        entry.mark_updated(offset..offset + slice.len());
+    }
+
+    fn reserve<T: bytemuck::NoUninit>(&mut self, count: usize) {
+        self.get_ssbo::<T>().reserve(count);
     }
 
     fn get_ssbo<T: bytemuck::NoUninit>(&mut self) -> &mut ManagedSsbo {
@@ -40,12 +52,32 @@ impl SsboPoolInner {
         self.ssbo_map.entry(key).or_insert_with(|| ManagedSsbo::new::<T>())
     }
 
+    fn flush(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        let belt = self
+            .staging_belt
+            .get_or_insert_with(|| StagingBelt::new(device.clone(), STAGING_CHUNK_SIZE));
+
+        for ssbo in self.ssbo_map.values_mut() {
+            ssbo.flush(encoder, device, belt);
+        }
+        belt.finish();
+    }
+
+    fn recall(&mut self) {
+        if let Some(belt) = &mut self.staging_belt {
+            belt.recall();
+        }
+    }
+
     fn remove_uniform(&mut self, id: InstanceId) {
+        // The id is recycled even if nothing was ever uploaded for it
+        self.ssbo_ids.free(id);
+
         // TODO: free buffers
+        // An object can hold data of several types, each with its own entry
         for data in self.ssbo_map.values_mut() {
             if let Ok(index) = data.find_by_id(id) {
                 data.entries.remove_index(index);
-                return;
             }
         }
     }
@@ -53,16 +85,83 @@ impl SsboPoolInner {
 
 #[derive(Debug, Clone)]
 pub(crate) struct SsboPool {
-    inner: Rc<RefCell<SsboPoolInner>>,
+    inner: Shared<SsboPoolInner>,
 }
 
 impl SsboPool {
     pub fn new() -> Self {
         Self {
-            inner: Rc::new(RefCell::new(SsboPoolInner::default())),
+            inner: Shared::default(),
+        }
+    }
+
+    pub fn create_object(&self) -> ObjectData {
+        let id = self.inner.borrow_mut().ssbo_ids.get_next();
+
+        ObjectData {
+            id,
+            ssbo_pool: self.clone(),
+            counter: InstanceCounter::new(),
         }
     }
 
+    /// Records uploads of the staged data of every SSBO into `encoder`, (re)allocating GPU
+    /// buffers as needed. All writes go through one staging belt whose chunks are reused across
+    /// frames; call [`Self::recall`] once `encoder` is submitted.
+    pub fn flush(&self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.inner.borrow_mut().flush(encoder, device);
+    }
+
+    /// Returns the staging chunks of the last flush for reuse.
+    pub fn recall(&self) {
+        self.inner.borrow_mut().recall();
+    }
+
+    /// GPU buffer holding the data of type `T`, available after the first flush.
+    pub fn buffer<T: bytemuck::NoUninit>(&self) -> Option<wgpu::Buffer> {
+        self.inner.borrow().ssbo_map.get(&TypeId::new::<T>())?.buffer.clone()
+    }
+
+    /// Makes room for `count` objects of type `T` in total, both in the CPU staging data and
+    /// in the GPU buffer allocated by the next flush.
+    pub fn reserve<T: bytemuck::NoUninit>(&self, count: usize) {
+        self.inner.borrow_mut().reserve::<T>(count);
+    }
+
+    /// Number of objects with data of type `T`.
+    pub fn entry_count<T: bytemuck::NoUninit>(&self) -> usize {
+        self.inner.borrow().ssbo_map.get(&TypeId::new::<T>()).map_or(0, |ssbo| ssbo.entries.len())
+    }
+
+    pub fn contains<T: bytemuck::NoUninit>(&self, id: InstanceId) -> bool {
+        self.inner
+            .borrow()
+            .ssbo_map
+            .get(&TypeId::new::<T>())
+            .is_some_and(|ssbo| ssbo.find_by_id(id).is_ok())
+    }
+
+    /// Ids of the objects with data of type `T`, sorted.
+    pub fn ids<T: bytemuck::NoUninit>(&self) -> Vec<InstanceId> {
+        self.inner
+            .borrow()
+            .ssbo_map
+            .get(&TypeId::new::<T>())
+            .map_or_else(Vec::new, |ssbo| ssbo.entries.iter().map(|entry| entry.id).collect())
+    }
+
+    /// Element index of object `id` in the SSBO of type `T`, as seen by `array<T>` in a shader.
+    /// Assigned in order of the first upload and kept for the object's lifetime.
+    pub fn index_of<T: bytemuck::NoUninit>(&self, id: InstanceId) -> Option<u32> {
+        let inner = self.inner.borrow();
+        let ssbo = inner.ssbo_map.get(&TypeId::new::<T>())?;
+        let index = ssbo.find_by_id(id).ok()?;
+        ssbo.entries[index]
+            .offset_in_buffer
+            .checked_div(ssbo.type_info.size)
+            .map(|index| index as u32)
+    }
+
     fn upload_uniform<T: bytemuck::NoUninit>(&self, uniform: &ObjectData, val: &T) {
         self.inner.borrow_mut().upload_uniform(uniform, val);
     }
@@ -96,7 +195,9 @@ struct ManagedSsbo {
     updated_range: Range<usize>,
     entries: SortedVec<BufferEntry>,
     staging_buffer: StagingBuffer,
-    buffer: wgpu::Buffer,
+    /// Bytes requested through `reserve`, the GPU buffer is allocated at least this large.
+    reserved_size: usize,
+    buffer: Option<wgpu::Buffer>,
 }
 
 impl ManagedSsbo {
@@ -106,10 +207,85 @@ impl ManagedSsbo {
             updated_range: 0..0,
             entries: SortedVec::new(),
             staging_buffer: vec![],
-            buffer: todo!(),
+            reserved_size: 0,
+            buffer: None,
+        }
+    }
+
+    fn flush(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        belt: &mut StagingBelt,
+    ) {
+        let mut range = self.take_updated_range();
+        if range.is_empty() {
+            return;
+        }
+
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let required = self.staging_buffer.len().next_multiple_of(alignment) as wgpu::BufferAddress;
+        if self.buffer.as_ref().is_none_or(|buffer| buffer.size() < required) {
+            let max_binding_size =
+                device.limits().max_storage_buffer_binding_size as wgpu::BufferAddress;
+            if required > max_binding_size {
+                // Still uploaded, but binding the whole buffer fails validation
+                log::error!(
+                    "SSBO of {} needs {required} bytes, above the {max_binding_size} byte storage \
+                     binding limit",
+                    self.type_info.name
+                );
+            }
+            let reserved = self.reserved_size.next_multiple_of(alignment) as wgpu::BufferAddress;
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.type_info.name),
+                size: required.max(reserved).next_power_of_two(),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            // A fresh buffer has none of the older data
+            range = 0..self.staging_buffer.len();
+        }
+
+        // Copies have to start and end 4-byte aligned, a ragged tail is zero-padded
+        let start = range.start / alignment * alignment;
+        let end = range.end.next_multiple_of(alignment);
+        let Some(size) = wgpu::BufferSize::new((end - start) as wgpu::BufferAddress) else {
+            return;
+        };
+
+        let buffer = self.buffer.as_ref().unwrap();
+        let mut view = belt.write_buffer(encoder, buffer, start as wgpu::BufferAddress, size);
+        let data_end = end.min(self.staging_buffer.len());
+        let (data, padding) = view.split_at_mut(data_end - start);
+        data.copy_from_slice(&self.staging_buffer[start..data_end]);
+        padding.fill(0);
+    }
+
+    fn reserve(&mut self, count: usize) {
+        let size = count * self.type_info.size;
+        self.staging_buffer.reserve(size.saturating_sub(self.staging_buffer.len()));
+        self.entries.reserve(count.saturating_sub(self.entries.len()));
+        self.reserved_size = self.reserved_size.max(size);
+    }
+
+    /// Grows `updated_range` to cover `range`.
+    fn mark_updated(&mut self, range: Range<usize>) {
+        if self.updated_range.is_empty() {
+            self.updated_range = range;
+        } else {
+            self.updated_range.start = self.updated_range.start.min(range.start);
+            self.updated_range.end = self.updated_range.end.max(range.end);
         }
     }
 
+    /// Bytes written since the last flush, resetting the tracking. Scattered writes are merged
+    /// into their bounding range, uploading the untouched bytes in between as well, so a flush
+    /// is always a single copy.
+    fn take_updated_range(&mut self) -> Range<usize> {
+        std::mem::replace(&mut self.updated_range, 0..0)
+    }
+
     fn find_by_id(&self, id: InstanceId) -> Result<usize, usize> {
         self.entries.binary_search_by_key(&id, |entry| entry.id)
     }
@@ -123,6 +299,10 @@ pub struct ObjectData {
 }
 
 impl ObjectData {
+    pub fn id(&self) -> InstanceId {
+        self.id
+    }
+
     pub fn upload<T: bytemuck::NoUninit>(&self, val: &T) {
         self.ssbo_pool.upload_uniform(self, val);
     }
@@ -130,7 +310,7 @@ impl ObjectData {
 
 impl Clone for ObjectData {
     fn clone(&self) -> Self {
-        self.counter.update(|v| v + 1);
+        self.counter.increment();
 
         Self {
             id: self.id,
@@ -142,10 +322,39 @@ impl Clone for ObjectData {
 
 impl Drop for ObjectData {
     fn drop(&mut self) {
-        self.counter.update(|v| v - 1);
-
-        if self.counter.get() == 0 {
+        if self.counter.decrement() {
             self.ssbo_pool.remove_uniform(self.id);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_objects_recycle_their_ids() {
+        let pool = SsboPool::new();
+        let first = pool.create_object().id();
+
+        for _ in 0..4 {
+            let object = pool.create_object();
+            object.upload(&[1.0f32; 4]);
+            assert_eq!(object.id(), first);
+        }
+        assert_eq!(pool.entry_count::<[f32; 4]>(), 0);
+    }
+
+    #[test]
+    fn drop_removes_every_type() {
+        let pool = SsboPool::new();
+        let object = pool.create_object();
+        let id = object.id();
+        object.upload(&[1.0f32; 4]);
+        object.upload(&7u32);
+
+        drop(object);
+        assert!(!pool.contains::<[f32; 4]>(id));
+        assert!(!pool.contains::<u32>(id));
+    }
+}
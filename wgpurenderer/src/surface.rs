@@ -0,0 +1,214 @@
+use crate::{ColorAttachment, Context, DepthStencilAttachment, RenderTarget};
+use smallvec::smallvec;
+
+/// Window surface together with the attachments that have to follow its size: an optional
+/// multisampled color texture resolved into the surface and a depth texture.
+#[derive(Debug)]
+pub struct SurfaceState {
+    device: wgpu::Device,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: Option<wgpu::TextureView>,
+    /// Color the surface is cleared to at the start of each frame's render target.
+    pub clear_color: wgpu::Color,
+}
+
+impl SurfaceState {
+    /// Configures `surface` with an sRGB format when available and `Fifo` presentation.
+    /// A `sample_count` above 1 renders into a multisampled texture resolved into the surface,
+    /// lowered to what the adapter supports for the surface and depth formats; pipelines have
+    /// to use [`Self::sample_count`]. `depth_format` of `None` leaves frames without a depth
+    /// attachment, an unsupported one is replaced as in [`Context::supported_depth_format`];
+    /// pipelines have to use [`Self::depth_format`].
+    pub fn new(
+        context: &Context,
+        surface: wgpu::Surface<'static>,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        let surface_caps = surface.get_capabilities(context.adapter());
+        let format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        let depth_format = depth_format.map(|format| context.supported_depth_format(format));
+        let attachment_formats: Vec<_> = std::iter::once(format).chain(depth_format).collect();
+        let mut state = Self {
+            device: context.device().clone(),
+            surface,
+            config,
+            sample_count: context.supported_sample_count(&attachment_formats, sample_count),
+            depth_format,
+            msaa_view: None,
+            depth_view: None,
+            clear_color: wgpu::Color::BLACK,
+        };
+        state.configure();
+        state
+    }
+
+    /// Reconfigures the surface and recreates the attachments at the new size. Zero sizes,
+    /// e.g. of a minimized window, are ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == self.size() {
+            return;
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+        self.configure();
+    }
+
+    /// Requests a present mode, falling back to `Fifo` when the surface doesn't support it.
+    /// Returns the mode actually in use.
+    pub fn set_present_mode(
+        &mut self,
+        adapter: &wgpu::Adapter,
+        mode: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        let surface_caps = self.surface.get_capabilities(adapter);
+        self.config.present_mode = crate::select_present_mode(&surface_caps, mode);
+        self.surface.configure(&self.device, &self.config);
+        self.config.present_mode
+    }
+
+    pub fn config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.config
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_format
+    }
+
+    /// [`Self::current_frame`] that recovers from a lost or outdated surface: the surface is
+    /// reconfigured at its current size, attachments included, and acquiring is retried once.
+    /// A second failure, `Timeout` and `OutOfMemory` are returned as is; `Timeout` usually
+    /// means skipping the frame, `OutOfMemory` is fatal. `Other` is logged and returned.
+    pub fn acquire(&mut self) -> Result<SurfaceFrame, wgpu::SurfaceError> {
+        let result = match self.current_frame() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure();
+                self.current_frame()
+            }
+            result => result,
+        };
+
+        if let Err(wgpu::SurfaceError::Other) = result {
+            log::error!("Failed to acquire the surface texture");
+        }
+        result
+    }
+
+    /// Acquires the next surface texture and wraps it into a render target clearing color and
+    /// depth, ready to be assigned to a [`crate::renderpass::RenderPass`].
+    pub fn current_frame(&self) -> Result<SurfaceFrame, wgpu::SurfaceError> {
+        let texture = self.surface.get_current_texture()?;
+        let view = texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut color_attachment =
+            ColorAttachment::builder(self.msaa_view.clone().unwrap_or_else(|| view.clone()))
+                .clear(self.clear_color);
+        if self.msaa_view.is_some() {
+            color_attachment = color_attachment.resolve_target(view);
+        }
+
+        let depth_stencil_attachment = self
+            .depth_view
+            .as_ref()
+            .map(|view| DepthStencilAttachment::builder(view.clone()).build());
+
+        Ok(SurfaceFrame {
+            render_target: RenderTarget {
+                color_attachments: smallvec![color_attachment.build()],
+                depth_stencil_attachment,
+            },
+            texture,
+        })
+    }
+
+    fn configure(&mut self) {
+        self.surface.configure(&self.device, &self.config);
+
+        self.msaa_view = (self.sample_count > 1).then(|| {
+            self.create_attachment("Surface MSAA Texture", self.config.format, self.sample_count)
+        });
+        self.depth_view = self.depth_format.map(|format| {
+            self.create_attachment("Surface Depth Texture", format, self.sample_count)
+        });
+    }
+
+    fn create_attachment(
+        &self,
+        label: &str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::TextureView {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
+
+/// Surface texture acquired for one frame. Dropping it without [`SurfaceFrame::present`]
+/// discards the frame.
+#[derive(Debug)]
+pub struct SurfaceFrame {
+    /// Render target drawing into the surface texture, resolving multisampled color into it.
+    pub render_target: RenderTarget,
+    texture: wgpu::SurfaceTexture,
+}
+
+impl SurfaceFrame {
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture.texture
+    }
+
+    pub fn present(self) {
+        // Views of the surface texture have to be gone before presenting
+        drop(self.render_target);
+        self.texture.present();
+    }
+}
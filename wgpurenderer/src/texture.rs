@@ -0,0 +1,481 @@
+use crate::{binding::Binding, utils::InstanceCounter};
+use rustc_hash::FxHashMap;
+use std::fmt;
+#[cfg(feature = "image")]
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct Texture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    view_dimension: wgpu::TextureViewDimension,
+    counter: InstanceCounter,
+}
+
+impl Texture {
+    pub fn new(device: &wgpu::Device, descriptor: &wgpu::TextureDescriptor) -> Self {
+        let view_dimension = match descriptor.dimension {
+            wgpu::TextureDimension::D1 => wgpu::TextureViewDimension::D1,
+            wgpu::TextureDimension::D2 if descriptor.size.depth_or_array_layers > 1 => {
+                wgpu::TextureViewDimension::D2Array
+            }
+            wgpu::TextureDimension::D2 => wgpu::TextureViewDimension::D2,
+            wgpu::TextureDimension::D3 => wgpu::TextureViewDimension::D3,
+        };
+        Self::with_view_dimension(device, descriptor, view_dimension)
+    }
+
+    fn with_view_dimension(
+        device: &wgpu::Device,
+        descriptor: &wgpu::TextureDescriptor,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        let texture = device.create_texture(descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            view_dimension,
+            counter: InstanceCounter::new(),
+        }
+    }
+
+    /// Uploads tightly packed sRGB RGBA8 `pixels` into mip 0. With `mip_level_count > 1` the
+    /// texture is also usable as a render attachment so [`Self::generate_mipmaps`] can fill it.
+    pub fn from_rgba8(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        mip_level_count: u32,
+    ) -> Self {
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = Self::new(
+            device,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage,
+                view_formats: &[],
+            },
+        );
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        texture
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, TextureError> {
+        let image = image::open(path).map_err(TextureError::Image)?.to_rgba8();
+        Ok(Self::from_rgba8(device, queue, image.width(), image.height(), &image, 1))
+    }
+
+    /// Loads an image with a full mip chain generated on the GPU.
+    #[cfg(feature = "image")]
+    pub fn from_path_with_mips(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        generator: &mut MipmapGenerator,
+    ) -> Result<Self, TextureError> {
+        let image = image::open(path).map_err(TextureError::Image)?.to_rgba8();
+        let (width, height) = image.dimensions();
+        let texture =
+            Self::from_rgba8(device, queue, width, height, &image, mip_level_count(width, height));
+        texture.generate_mipmaps(device, queue, generator)?;
+        Ok(texture)
+    }
+
+    /// Uploads six sRGB RGBA8 faces of `size`x`size` pixels into a cube texture, in the
+    /// +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn cubemap_from_rgba8(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        };
+        let texture = Self::with_view_dimension(
+            device,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::TextureViewDimension::Cube,
+        );
+
+        for (layer, pixels) in faces.into_iter().enumerate() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size * 4),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..extent
+                },
+            );
+        }
+
+        texture
+    }
+
+    /// Loads a cubemap from six face images in the +X, -X, +Y, -Y, +Z, -Z order. All faces have
+    /// to be square and of the same size.
+    #[cfg(feature = "image")]
+    pub fn cubemap_from_paths(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: [impl AsRef<Path>; 6],
+    ) -> Result<Self, TextureError> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            faces.push(image::open(path).map_err(TextureError::Image)?.to_rgba8());
+        }
+
+        let size = faces[0].width();
+        for (face, image) in faces.iter().enumerate() {
+            if image.width() != size || image.height() != size {
+                return Err(TextureError::InvalidCubemapFace {
+                    face,
+                    width: image.width(),
+                    height: image.height(),
+                });
+            }
+        }
+
+        Ok(Self::cubemap_from_rgba8(
+            device,
+            queue,
+            size,
+            std::array::from_fn(|face| faces[face].as_raw().as_slice()),
+        ))
+    }
+
+    /// Fills mips `1..mip_level_count` of every layer by box-downsampling the previous level.
+    /// The format has to be filterable and renderable on any device, so block-compressed, integer
+    /// and depth formats are rejected. Mip chains for those have to be baked offline.
+    pub fn generate_mipmaps(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        generator: &mut MipmapGenerator,
+    ) -> Result<(), TextureError> {
+        let format = self.texture.format();
+        let features = format.guaranteed_format_features(device.features());
+        if format.is_compressed()
+            || !features.flags.contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+            || !features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        {
+            return Err(TextureError::UnsupportedFormat(format));
+        }
+
+        let required =
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if !self.texture.usage().contains(required) {
+            return Err(TextureError::MissingUsage(required - self.texture.usage()));
+        }
+
+        let mip_level_count = self.texture.mip_level_count();
+        if mip_level_count < 2 {
+            return Ok(());
+        }
+
+        let pipeline = generator.pipeline(device, format);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let level_view = |layer: u32, level: u32| {
+            self.texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+
+        for layer in 0..self.texture.depth_or_array_layers() {
+            for level in 1..mip_level_count {
+                let source = level_view(layer, level - 1);
+                let destination = level_view(layer, level);
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&generator.sampler),
+                        },
+                    ],
+                });
+
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &destination,
+                        depth_slice: None,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                    multiview_mask: None,
+                });
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+
+        queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        self.view_dimension
+    }
+}
+
+impl Binding for Texture {
+    fn binding_type(&self) -> wgpu::BindingType {
+        let sample_type = self.texture.format().sample_type(None, None).unwrap_or(
+            wgpu::TextureSampleType::Float {
+                filterable: true,
+            },
+        );
+
+        wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: self.view_dimension,
+            multisampled: self.texture.sample_count() > 1,
+        }
+    }
+
+    fn binding_resource(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::TextureView(&self.view)
+    }
+
+    fn counter(&self) -> Option<&InstanceCounter> {
+        Some(&self.counter)
+    }
+}
+
+impl Clone for Texture {
+    fn clone(&self) -> Self {
+        self.counter.increment();
+
+        Self {
+            texture: self.texture.clone(),
+            view: self.view.clone(),
+            view_dimension: self.view_dimension,
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.counter.decrement();
+    }
+}
+
+/// Shader, sampler and per-format pipelines of [`Texture::generate_mipmaps`], created once and
+/// shared by every texture of the device.
+#[derive(Debug)]
+pub struct MipmapGenerator {
+    module: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    pipelines: FxHashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/mipmap.wgsl").into()),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            module,
+            sampler,
+            pipelines: FxHashMap::default(),
+        }
+    }
+
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let module = &self.module;
+        let pipeline = self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Mipmap Pipeline"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        });
+        pipeline.clone()
+    }
+}
+
+/// Number of levels in a full mip chain down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    UnsupportedFormat(wgpu::TextureFormat),
+    MissingUsage(wgpu::TextureUsages),
+    InvalidCubemapFace {
+        face: usize,
+        width: u32,
+        height: u32,
+    },
+    #[cfg(feature = "image")]
+    Image(image::ImageError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => {
+                write!(f, "mipmaps can't be generated for texture format {format:?}")
+            }
+            Self::MissingUsage(usage) => write!(f, "texture is missing usage {usage:?}"),
+            Self::InvalidCubemapFace {
+                face,
+                width,
+                height,
+            } => write!(f, "cubemap face {face} is {width}x{height}, faces must be equal squares"),
+            #[cfg(feature = "image")]
+            Self::Image(e) => write!(f, "failed to load image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn mipmap_pipeline_is_built_once_per_format() {
+        let context = Context::noop();
+        let (device, queue) = (context.device(), context.queue());
+        let pixels = [255; 16 * 16 * 4];
+        let first = Texture::from_rgba8(device, queue, 16, 16, &pixels, mip_level_count(16, 16));
+        let second = Texture::from_rgba8(device, queue, 16, 16, &pixels, mip_level_count(16, 16));
+
+        let mut generator = MipmapGenerator::new(device);
+        first.generate_mipmaps(device, queue, &mut generator).unwrap();
+        second.generate_mipmaps(device, queue, &mut generator).unwrap();
+
+        assert_eq!(generator.pipelines.len(), 1);
+    }
+}
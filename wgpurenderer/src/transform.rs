@@ -1,3 +1,4 @@
+use bytemuck::{Pod, Zeroable};
 use glam::{Mat3, Mat4, Quat, Vec3};
 
 #[derive(Debug, Clone, Copy)]
@@ -39,6 +40,92 @@ impl Transform {
         self.invalidate_normal_matrix();
     }
 
+    /// Direction the transform faces, its local -Z axis as for cameras.
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::NEG_Z
+    }
+
+    /// Local +X axis.
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    /// Local +Y axis.
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    /// Moves by `delta` in parent space.
+    pub fn translate(&mut self, delta: Vec3) {
+        self.set_position(self.position + delta);
+    }
+
+    /// Moves by `delta` along the transform's own axes: `x` along [`Self::right`], `y` along
+    /// [`Self::up`] and `z` along [`Self::forward`]. Scale doesn't affect the distance.
+    pub fn translate_local(&mut self, delta: Vec3) {
+        self.translate(self.right() * delta.x + self.up() * delta.y + self.forward() * delta.z);
+    }
+
+    /// Applies `delta` after the current orientation (`delta * orientation`), i.e. rotates
+    /// around parent space axes. Rotating around the transform's own axes is
+    /// `set_orientation(orientation * delta)`.
+    pub fn rotate(&mut self, delta: Quat) {
+        self.set_orientation((delta * self.orientation).normalize());
+    }
+
+    /// Multiplies the scale componentwise by `factor`.
+    pub fn scale_by(&mut self, factor: Vec3) {
+        self.set_scale(self.scale * factor);
+    }
+
+    /// Rotates by `angle` radians around `axis` through `pivot`, moving the position around the
+    /// pivot and turning the orientation with it, e.g. for orbiting a target. `axis` doesn't
+    /// have to be normalized; a zero `axis` leaves the transform as is.
+    pub fn rotate_around(&mut self, pivot: Vec3, axis: Vec3, angle: f32) {
+        let axis = axis.normalize_or_zero();
+        if axis == Vec3::ZERO {
+            return;
+        }
+
+        let rotation = Quat::from_axis_angle(axis, angle);
+        self.position = pivot + rotation * (self.position - pivot);
+        self.orientation = (rotation * self.orientation).normalize();
+        self.invalidate_model();
+        self.invalidate_normal_matrix();
+    }
+
+    /// Whether an odd number of scale components is negative, i.e. the model matrix has a
+    /// negative determinant. Mirroring reverses the winding of every triangle, so with back-face
+    /// culling the pipeline has to use the opposite `front_face`, see [`Self::front_face`]. Only
+    /// looks at the local scale, nested transforms need [`Self::is_mirrored_in`].
+    pub fn is_mirrored(&self) -> bool {
+        self.scale.x * self.scale.y * self.scale.z < 0.0
+    }
+
+    /// [`Self::is_mirrored`] for the world matrix under `parent_world`, i.e. from the sign of
+    /// its determinant. Also catches mirroring inherited from an ancestor, which the local scale
+    /// doesn't show.
+    pub fn is_mirrored_in(&mut self, parent_world: Mat4) -> bool {
+        self.world_model(parent_world).determinant() < 0.0
+    }
+
+    /// `front_face` for drawing with this transform a mesh whose front faces wind as `front_face`.
+    pub fn front_face(&self, front_face: wgpu::FrontFace) -> wgpu::FrontFace {
+        match (self.is_mirrored(), front_face) {
+            (false, front_face) => front_face,
+            (true, wgpu::FrontFace::Ccw) => wgpu::FrontFace::Cw,
+            (true, wgpu::FrontFace::Cw) => wgpu::FrontFace::Ccw,
+        }
+    }
+
+    /// Compares position and scale componentwise and orientation by the angle between the
+    /// rotations, ignoring the cached matrices.
+    pub fn approx_eq(&self, other: &Transform, epsilon: f32) -> bool {
+        self.position.abs_diff_eq(other.position, epsilon)
+            && self.scale.abs_diff_eq(other.scale, epsilon)
+            && self.orientation.angle_between(other.orientation) <= epsilon
+    }
+
     fn is_invalid_model(&self) -> bool {
         self.model.w_axis.w == 0.0
     }
@@ -66,6 +153,27 @@ impl Transform {
         self.model
     }
 
+    /// Cached model matrix for read-only access through `&Transform`, `None` if a setter ran
+    /// since the last [`Self::get_model`]. Call `get_model` (or [`Self::to_gpu`]) once after
+    /// mutating, e.g. in the update pass, and this returns the same matrix until the next
+    /// change.
+    pub fn model_unchecked(&self) -> Option<Mat4> {
+        (!self.is_invalid_model()).then_some(self.model)
+    }
+
+    /// Cached normal matrix, see [`Self::model_unchecked`]. Refreshed by
+    /// [`Self::get_normal_matrix`] and [`Self::to_gpu`], not by `get_model` alone.
+    pub fn normal_matrix_unchecked(&self) -> Option<Mat3> {
+        (!self.is_invalid_normal_matrix()).then_some(self.normal_matrix)
+    }
+
+    /// Model matrix composed with the world matrix of the parent.
+    pub fn world_model(&mut self, parent_world: Mat4) -> Mat4 {
+        parent_world * self.get_model()
+    }
+
+    /// Inverse transpose of the model's upper 3x3. Normals stay outward under mirroring, only
+    /// the winding flips, see [`Self::is_mirrored`].
     pub fn get_normal_matrix(&mut self) -> Mat3 {
         if self.is_invalid_normal_matrix() {
             // Recompute normal matrix: transpose(inverse(mat3(model)))
@@ -77,6 +185,20 @@ impl Transform {
         self.normal_matrix
     }
 
+    /// Model and normal matrix laid out like `ModelUniforms` in `modules::uniforms::MODEL`.
+    pub fn to_gpu(&mut self) -> TransformGpu {
+        let normal_matrix = self.get_normal_matrix();
+
+        TransformGpu {
+            model: self.get_model().to_cols_array_2d(),
+            normal_matrix: [
+                normal_matrix.x_axis.extend(0.0).to_array(),
+                normal_matrix.y_axis.extend(0.0).to_array(),
+                normal_matrix.z_axis.extend(0.0).to_array(),
+            ],
+        }
+    }
+
     fn invalid_model() -> Mat4 {
         Mat4::ZERO
     }
@@ -99,3 +221,58 @@ impl Default for Transform {
         }
     }
 }
+
+/// std140/WGSL layout of a transform: a `mat3x3<f32>` takes three 16-byte columns.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct TransformGpu {
+    pub model: [[f32; 4]; 4],
+    pub normal_matrix: [[f32; 4]; 3],
+}
+
+const _: () = assert!(size_of::<TransformGpu>() == 112);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn rotate_around_orbits_the_pivot() {
+        let pivot = Vec3::new(1.0, 0.0, 0.0);
+        let mut transform = Transform::default();
+        transform.set_position(pivot + Vec3::X);
+
+        transform.rotate_around(pivot, Vec3::Y * 2.0, FRAC_PI_2);
+
+        assert!(transform.position().abs_diff_eq(pivot - Vec3::Z, 1e-6));
+        assert!(transform.forward().abs_diff_eq(Vec3::NEG_X, 1e-6));
+
+        let before = transform;
+        transform.rotate_around(pivot, Vec3::ZERO, FRAC_PI_2);
+        assert!(transform.approx_eq(&before, 0.0));
+        assert!(!transform.get_model().is_nan());
+    }
+
+    #[test]
+    fn mirroring_flips_winding_but_keeps_normals_outward() {
+        let mut mirrored = Transform::default();
+        mirrored.set_scale(Vec3::new(-1.0, 1.0, 1.0));
+
+        assert!(mirrored.is_mirrored());
+        assert!(mirrored.is_mirrored_in(Mat4::IDENTITY));
+        assert_eq!(mirrored.front_face(wgpu::FrontFace::Ccw), wgpu::FrontFace::Cw);
+        // The +X face of a mesh ends up at -X and has to face -X
+        let position = mirrored.get_model().transform_point3(Vec3::X);
+        let normal = mirrored.get_normal_matrix() * Vec3::X;
+        assert!(position.abs_diff_eq(Vec3::NEG_X, 1e-6));
+        assert!(normal.abs_diff_eq(Vec3::NEG_X, 1e-6));
+
+        // A child inherits the mirroring without a negative scale of its own
+        let mut child = Transform::default();
+        let parent_world = mirrored.get_model();
+        assert!(!child.is_mirrored());
+        assert!(child.is_mirrored_in(parent_world));
+        assert!(!mirrored.is_mirrored_in(parent_world));
+    }
+}
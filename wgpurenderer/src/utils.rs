@@ -1,29 +1,174 @@
 use nohash_hasher::{IntMap, IsEnabled};
-use std::{any, cell::Cell, rc::Rc};
+#[cfg(feature = "multithread")]
+use std::sync::{
+    Arc, PoisonError, RwLock, TryLockError,
+    atomic::{AtomicU32, Ordering},
+};
+use std::{
+    any,
+    ops::{Deref, DerefMut},
+};
+#[cfg(not(feature = "multithread"))]
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 pub type TypeIdMap<V> = IntMap<TypeId, V>;
 
 pub type StagingBuffer = Vec<u8>;
 
+#[cfg(not(feature = "multithread"))]
+type SharedCell<T> = Rc<RefCell<T>>;
+#[cfg(feature = "multithread")]
+type SharedCell<T> = Arc<RwLock<T>>;
+
+#[cfg(not(feature = "multithread"))]
+type SharedCount = Rc<Cell<u32>>;
+#[cfg(feature = "multithread")]
+type SharedCount = Arc<AtomicU32>;
+
+/// State shared between the renderer and the handles it gives out. `Rc<RefCell<T>>` by default,
+/// `Arc<RwLock<T>>` with the `multithread` feature, where a borrow blocks instead of panicking
+/// while a conflicting one is alive.
+#[derive(Debug)]
+pub struct Shared<T>(SharedCell<T>);
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self(SharedCell::new(value.into()))
+    }
+
+    #[cfg(not(feature = "multithread"))]
+    pub fn borrow(&self) -> impl Deref<Target = T> {
+        self.0.borrow()
+    }
+
+    #[cfg(not(feature = "multithread"))]
+    pub fn borrow_mut(&self) -> impl DerefMut<Target = T> {
+        self.0.borrow_mut()
+    }
+
+    /// Panics with `msg` while a conflicting borrow is alive. On one thread that can only be
+    /// reentrancy, so the message can name the cause.
+    #[cfg(not(feature = "multithread"))]
+    pub fn borrow_or_panic(&self, msg: &str) -> impl Deref<Target = T> {
+        match self.0.try_borrow() {
+            Ok(guard) => guard,
+            Err(_) => cold_panic(msg),
+        }
+    }
+
+    /// Mutable [`Self::borrow_or_panic`].
+    #[cfg(not(feature = "multithread"))]
+    pub fn borrow_mut_or_panic(&self, msg: &str) -> impl DerefMut<Target = T> {
+        match self.0.try_borrow_mut() {
+            Ok(guard) => guard,
+            Err(_) => cold_panic(msg),
+        }
+    }
+
+    /// `None` while any other borrow is alive, instead of panicking.
+    #[cfg(not(feature = "multithread"))]
+    pub fn try_borrow_mut(&self) -> Option<impl DerefMut<Target = T>> {
+        self.0.try_borrow_mut().ok()
+    }
+
+    // A panic while the lock was held leaves the data as consistent as the `RefCell` version
+    // would after an unwind, so poisoning is ignored.
+    #[cfg(feature = "multithread")]
+    pub fn borrow(&self) -> impl Deref<Target = T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "multithread")]
+    pub fn borrow_mut(&self) -> impl DerefMut<Target = T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Blocks like [`Self::borrow`]. A held lock is usually just another thread, which can't be
+    /// told apart from reentrancy, so `msg` is unused.
+    #[cfg(feature = "multithread")]
+    pub fn borrow_or_panic(&self, _msg: &str) -> impl Deref<Target = T> {
+        self.borrow()
+    }
+
+    /// Blocks like [`Self::borrow_mut`], see [`Self::borrow_or_panic`].
+    #[cfg(feature = "multithread")]
+    pub fn borrow_mut_or_panic(&self, _msg: &str) -> impl DerefMut<Target = T> {
+        self.borrow_mut()
+    }
+
+    /// `None` while the lock is held, instead of blocking.
+    #[cfg(feature = "multithread")]
+    pub fn try_borrow_mut(&self) -> Option<impl DerefMut<Target = T>> {
+        match self.0.try_write() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for Shared<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct InstanceCounter(Rc<Cell<u32>>);
+pub struct InstanceCounter(SharedCount);
 
 impl InstanceCounter {
     pub fn new() -> Self {
-        Self(Rc::new(Cell::new(1)))
+        Self(SharedCount::new(1.into()))
     }
 
+    #[cfg(not(feature = "multithread"))]
     pub fn increment(&self) {
         self.0.update(|v| v + 1);
     }
 
-    pub fn decrement(&self) {
-        self.0.update(|v| v - 1);
+    /// Returns whether this released the last handle, which is true for exactly one call.
+    #[cfg(not(feature = "multithread"))]
+    pub fn decrement(&self) -> bool {
+        let value = self.0.get() - 1;
+        self.0.set(value);
+        value == 0
     }
 
+    #[cfg(not(feature = "multithread"))]
     pub fn value(&self) -> u32 {
         self.0.get()
     }
+
+    #[cfg(feature = "multithread")]
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns whether this released the last handle, which is true for exactly one call even
+    /// when clones are dropped concurrently.
+    #[cfg(feature = "multithread")]
+    pub fn decrement(&self) -> bool {
+        self.0.fetch_sub(1, Ordering::AcqRel) == 1
+    }
+
+    #[cfg(feature = "multithread")]
+    pub fn value(&self) -> u32 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Address of the shared count, unique for as long as any clone of the counter exists.
+    pub fn id(&self) -> usize {
+        SharedCount::as_ptr(&self.0) as usize
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -39,6 +184,8 @@ impl InstanceId {
     }
 }
 
+impl IsEnabled for InstanceId {}
+
 impl From<u32> for InstanceId {
     fn from(value: u32) -> Self {
         Self::new(value)
@@ -123,3 +270,37 @@ impl IdPool {
 pub fn cold_panic(msg: &str) -> ! {
     panic!("{msg}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_last_decrement_releases() {
+        let counter = InstanceCounter::new();
+        counter.increment();
+        counter.increment();
+
+        let released: Vec<_> = (0..3).map(|_| counter.decrement()).collect();
+
+        assert_eq!(released, [false, false, true]);
+        assert_eq!(counter.value(), 0);
+    }
+
+    #[cfg(feature = "multithread")]
+    #[test]
+    fn concurrent_decrements_release_once() {
+        let counter = InstanceCounter::new();
+        for _ in 1..8 {
+            counter.increment();
+        }
+
+        let released = std::thread::scope(|scope| {
+            let handles: Vec<_> =
+                (0..8).map(|_| scope.spawn(|| counter.clone().decrement())).collect();
+            handles.into_iter().filter_map(|handle| handle.join().unwrap().then_some(())).count()
+        });
+
+        assert_eq!(released, 1);
+    }
+}